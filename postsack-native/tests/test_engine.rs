@@ -1,10 +1,11 @@
 use ps_core::{
     self,
     model::{self, Engine, Rect},
-    Config, DatabaseLike, Field, Filter, FormatType, Importerlike, ValueField,
+    CancelToken, Config, DatabaseLike, Field, Filter, FormatType, Identity, Importerlike, Metric,
+    ValueField,
 };
 use ps_database::Database;
-use ps_importer::mbox_importer;
+use ps_importer::importer;
 
 #[cfg(test)]
 mod tests {
@@ -29,6 +30,8 @@ mod tests {
         let mut engine = Engine::new::<Database>(&config).expect("Expected Engine");
         engine.start().expect("Expect to start engine");
         engine.wait().expect("Expected working wait");
+        // The three mboxes are copies of each other, the mails with a Message-ID are merged
+        assert_eq!(engine.duplicates(), 41);
         let segment = {
             let segments =
                 model::segmentations::layouted_segments(&mut engine, default_rect()).unwrap();
@@ -81,6 +84,78 @@ mod tests {
             model::segmentations::layouted_segments(&mut engine, default_rect()).unwrap();
         assert_eq!(segments.len(), 1);
     }
+
+    #[test]
+    fn test_engine_metric() {
+        initialize();
+        let config = create_database();
+        let mut engine = Engine::new::<Database>(&config).expect("Expected Engine");
+        engine.start().expect("Expect to start engine");
+        engine.wait().expect("Expected working wait");
+        let segments =
+            model::segmentations::layouted_segments(&mut engine, default_rect()).unwrap();
+        let count: usize = segments.iter().map(|e| e.weight).sum();
+
+        // Weigh the segments by the size of their mails
+        model::segmentations::set_metric(&mut engine, Metric::Size).expect("Expect setting metric");
+        engine.wait().expect("Expected working wait");
+        assert_eq!(model::segmentations::metric(&engine), Metric::Size);
+        let segment = {
+            let segments =
+                model::segmentations::layouted_segments(&mut engine, default_rect()).unwrap();
+            assert_eq!(segments.len(), 5);
+            // Every mail has more than a hundred bytes of headers
+            let size: usize = segments.iter().map(|e| e.weight).sum();
+            assert!(size > count * 100);
+            segments[0].clone()
+        };
+
+        // Going back to a segmentation with another metric recalculates it
+        engine.push(segment).unwrap();
+        engine.wait().expect("Expected working wait");
+        model::segmentations::set_metric(&mut engine, Metric::Count).unwrap();
+        engine.wait().expect("Expected working wait");
+        engine.pop();
+        engine.wait().expect("Expected working wait");
+        let segments =
+            model::segmentations::layouted_segments(&mut engine, default_rect()).unwrap();
+        assert_eq!(segments.iter().map(|e| e.weight).sum::<usize>(), count);
+        assert_eq!(engine.segmentations()[0].metric, Metric::Count);
+    }
+
+    #[test]
+    fn test_engine_latencies() {
+        initialize();
+        let path = "tests/resources/threads";
+        let mut config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        config.identities = Identity::parse_list("john@example.com");
+        let importer = importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let mut engine = Engine::new::<Database>(&config).expect("Expected Engine");
+        engine.start().expect("Expect to start engine");
+        engine.wait().expect("Expected working wait");
+
+        // The latencies are loaded asynchronously
+        assert!(model::latency::latencies(&mut engine).unwrap().is_none());
+        engine.wait().expect("Expected working wait");
+        let latencies = model::latency::latencies(&mut engine)
+            .unwrap()
+            .expect("Expected Latencies");
+        // John answered Jane after an hour, and Jane answered John after an hour
+        assert_eq!(
+            latencies,
+            &[model::latency::Latency {
+                correspondent: "jane@example.org".to_string(),
+                my_replies: 1,
+                my_median: Some(3600),
+                their_replies: 1,
+                their_median: Some(3600),
+            }]
+        );
+    }
 }
 
 fn default_rect() -> Rect {
@@ -95,9 +170,9 @@ fn default_rect() -> Rect {
 fn create_database() -> Config {
     let path = "tests/resources/mbox";
     let config = Config::new(None, path, vec!["".to_string()], FormatType::Mbox).expect("Config");
-    let importer = mbox_importer(config.clone());
+    let importer = importer(config.clone());
     let database = Database::new(&config.database_path).unwrap();
-    let (_receiver, handle) = importer.import(database).unwrap();
+    let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
     handle.join().expect("").expect("");
     config
 }
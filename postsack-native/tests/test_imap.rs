@@ -0,0 +1,212 @@
+use ps_core::{self, CancelToken, DatabaseLike, DatabaseQuery, FormatType, Importerlike};
+use ps_database::Database;
+use ps_importer;
+
+#[cfg(test)]
+mod tests {
+    use ps_core::{Config, Field, ImapConfig, Message, Query, QueryResult, Value};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use super::*;
+
+    /// The mailboxes of the fake server. `Archive` can't be selected
+    /// and should be skipped.
+    const LIST: &str = "* LIST (\\HasChildren) \"/\" \"INBOX\"\r\n\
+                        * LIST (\\Noselect \\HasChildren) \"/\" \"Archive\"\r\n\
+                        * LIST (\\HasNoChildren) \"/\" \"Archive/2021\"\r\n";
+
+    /// The `(flags, header)` of the mails in a mailbox
+    fn mails(mailbox: &str) -> Vec<(&'static str, String)> {
+        let header = |subject: &str| {
+            format!(
+                "From: Alice <alice@example.com>\r\n\
+                 To: Bob <bob@example.com>\r\n\
+                 Subject: {}\r\n\
+                 Date: Mon, 4 Jan 2021 10:00:00 +0000\r\n\
+                 \r\n",
+                subject
+            )
+        };
+        match mailbox {
+            "INBOX" => vec![
+                ("\\Seen", header("Lunch next week?")),
+                ("", header("Our spring sale")),
+            ],
+            "Archive/2021" => vec![("\\Seen \\Answered", header("Project status"))],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    /// Test that the IMAP importer reads all selectable mailboxes
+    /// from a (fake) server and maps the flags
+    fn test_imap_importer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            fake_server(stream);
+        });
+
+        let config = imap_config(port);
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+        server.join().expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+
+        let row = |subject: &str| {
+            mails
+                .iter()
+                .find_map(|s| match s {
+                    QueryResult::Normal(row)
+                        if row[&Field::Subject].to_string().trim() == subject =>
+                    {
+                        Some(row.clone())
+                    }
+                    _ => None,
+                })
+                .expect("Expected Mail")
+        };
+        let tags = |subject: &str| match row(subject)[&Field::MetaTags].value() {
+            Value::Array(n) => n
+                .iter()
+                .map(|e| e.as_str().unwrap().to_owned())
+                .collect::<Vec<String>>(),
+            _ => panic!(),
+        };
+
+        assert_eq!(tags("Lunch next week?"), vec!["INBOX"]);
+        assert_eq!(
+            row("Lunch next week?")[&Field::MetaIsSeen].value(),
+            &Value::Bool(true)
+        );
+        assert_eq!(
+            row("Our spring sale")[&Field::MetaIsSeen].value(),
+            &Value::Bool(false)
+        );
+        assert_eq!(tags("Project status"), vec!["Archive/2021"]);
+
+        // The password is not stored
+        let stored = Database::config(&config.database_path).unwrap();
+        let imap = stored.imap.expect("Expected IMAP config");
+        assert_eq!(imap.user, "bob");
+        assert!(imap.password.is_empty());
+    }
+
+    #[test]
+    /// Test that the import can be cancelled while the reader waits for the server
+    fn test_cancel_while_waiting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (finished, wait) = std::sync::mpsc::channel::<()>();
+        let server = std::thread::spawn(move || {
+            // The server never greets
+            let (_stream, _) = listener.accept().unwrap();
+            let _ = wait.recv();
+        });
+
+        let config = imap_config(port);
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let cancel = CancelToken::default();
+        let (receiver, handle) = importer.import(database, cancel.clone()).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        cancel.cancel();
+        handle.join().expect("").expect("");
+        let messages: Vec<Message> = receiver.try_iter().collect();
+        assert!(messages.iter().any(|m| matches!(m, Message::Cancelled)));
+        assert!(!messages.iter().any(|m| matches!(m, Message::Error(_))));
+
+        finished.send(()).unwrap();
+        server.join().expect("");
+    }
+
+    fn imap_config(port: u16) -> Config {
+        let mut config = Config::new(
+            None,
+            "imap://bob@127.0.0.1",
+            vec!["".to_string()],
+            FormatType::Imap,
+        )
+        .expect("Config");
+        config.sources[0].imap = Some(ImapConfig {
+            host: "127.0.0.1".to_owned(),
+            port,
+            tls: false,
+            user: "bob".to_owned(),
+            password: "secret".to_owned(),
+        });
+        config
+    }
+
+    /// Just enough of IMAP to answer the commands the importer sends
+    fn fake_server(stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        writer.write_all(b"* OK Fake IMAP ready\r\n").unwrap();
+
+        let mut selected = String::new();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            let mut parts = line.trim_end().splitn(3, ' ');
+            let tag = parts.next().unwrap_or_default().to_owned();
+            let command = parts.next().unwrap_or_default().to_uppercase();
+            let arguments = parts.next().unwrap_or_default().to_owned();
+            line.clear();
+
+            let response = match command.as_str() {
+                "LOGIN" if arguments == "\"bob\" \"secret\"" => String::new(),
+                "LOGIN" => {
+                    writer
+                        .write_all(format!("{} NO Wrong password\r\n", tag).as_bytes())
+                        .unwrap();
+                    continue;
+                }
+                "LIST" => LIST.to_owned(),
+                "EXAMINE" => {
+                    selected = arguments.trim_matches('"').to_owned();
+                    format!("* {} EXISTS\r\n* 0 RECENT\r\n", mails(&selected).len())
+                }
+                "FETCH" => mails(&selected)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (flags, header))| {
+                        format!(
+                            "* {} FETCH (UID {} FLAGS ({}) BODY[HEADER] {{{}}}\r\n{})\r\n",
+                            index + 1,
+                            index + 101,
+                            flags,
+                            header.len(),
+                            header
+                        )
+                    })
+                    .collect(),
+                "LOGOUT" => "* BYE\r\n".to_owned(),
+                _ => {
+                    writer
+                        .write_all(format!("{} BAD Unknown command\r\n", tag).as_bytes())
+                        .unwrap();
+                    continue;
+                }
+            };
+            writer
+                .write_all(format!("{}{} OK {} completed\r\n", response, tag, command).as_bytes())
+                .unwrap();
+            if command == "LOGOUT" {
+                break;
+            }
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use ps_core::{self, DatabaseLike, DatabaseQuery, FormatType, Importerlike};
+use ps_core::{self, CancelToken, DatabaseLike, DatabaseQuery, FormatType, Importerlike};
 use ps_database::Database;
 use ps_importer;
 
 #[cfg(test)]
 mod tests {
-    use ps_core::{Config, Field, Query, QueryResult};
+    use ps_core::{
+        Config, DisplayTimezone, DuplicatePolicy, Field, Filter, Identity, Message, Metric, Query,
+        QueryResult, QueryRow, Source, Value, ValueField,
+    };
     use std::sync::Once;
 
     use super::*;
@@ -25,22 +28,28 @@ mod tests {
     fn test_mbox_import() {
         initialize();
         let path = "tests/resources/mbox";
-        let config =
+        let mut config =
             Config::new(None, path, vec!["".to_string()], FormatType::Mbox).expect("Config");
+        // The three mboxes are copies of each other
+        config.duplicates = DuplicatePolicy::Keep;
         let importer = ps_importer::mbox_importer(config.clone());
         let database = Database::new(&config.database_path).unwrap();
-        let (_receiver, handle) = importer.import(database).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
         handle.join().expect("").expect("");
 
         let db = Database::new(&config.database_path).unwrap();
 
         let total_mails = db.total_mails().expect("Expected total mails");
-        assert_eq!(total_mails, 141);
+        // Each of the three mboxes has 48 mails. The first one is preceded by
+        // whitespace, which the previous `mbox-reader` based importer didn't find.
+        assert_eq!(total_mails, 144);
+        // 45 of them have a Message-ID, but there're only 4 different ones
+        assert_eq!(db.total_duplicates().unwrap(), 41);
 
         let mails = db.query(&Query::Normal {
             fields: vec![Field::Subject],
             filters: Vec::new(),
-            range: 0..141,
+            range: 0..144,
         });
         let mails = mails.expect("Expected Mails");
 
@@ -55,6 +64,128 @@ mod tests {
         assert!(subjects.contains(&" check bogus body header (from)".into()));
     }
 
+    #[test]
+    /// Test that encoded names and subjects are decoded
+    fn test_encoded_headers() {
+        initialize();
+        let path = "tests/resources/encoded";
+        let config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject, Field::SenderName, Field::ToName],
+                filters: Vec::new(),
+                range: 0..3,
+            })
+            .expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+
+        let rfc = row_with_subject(&mails, "If you can read this you understand the example.");
+        assert_eq!(rfc[&Field::SenderName].to_string(), "Keld Jørn Simonsen");
+        assert_eq!(rfc[&Field::ToName].to_string(), "André Pirard");
+
+        let german = row_with_subject(&mails, "Re: Grüße aus Köln");
+        assert_eq!(german[&Field::SenderName].to_string(), "Jürgen Müller");
+
+        let japanese = row_with_subject(&mails, "日本語のメール");
+        assert_eq!(japanese[&Field::SenderName].to_string(), "山田");
+    }
+
+    #[test]
+    /// Test that mails with malformed headers are imported partially
+    fn test_partially_parsed() {
+        initialize();
+        let path = "tests/resources/malformed";
+        let mut config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        config.timezone = DisplayTimezone::Offset(0);
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        // The last one is not an email
+        assert_eq!(db.total_mails().unwrap(), 2);
+        assert_eq!(db.total_partial().unwrap(), 2);
+
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![
+                    Field::Subject,
+                    Field::SenderLocalPart,
+                    Field::Day,
+                    Field::FailedHeaders,
+                ],
+                filters: Vec::new(),
+                range: 0..2,
+            })
+            .expect("Expected Mails");
+        let wrong_format = row_with_subject(&mails, "A date in the wrong format");
+        assert_eq!(wrong_format[&Field::SenderLocalPart].to_string(), "john");
+        assert_eq!(wrong_format[&Field::Day].value(), &Value::from(9));
+        assert_eq!(wrong_format[&Field::FailedHeaders].to_string(), "date");
+        // Without a date, the date it was received is used
+        let no_date = row_with_subject(&mails, "Re: A date in the wrong format");
+        assert_eq!(no_date[&Field::Day].value(), &Value::from(10));
+        assert_eq!(no_date[&Field::FailedHeaders].to_string(), "date");
+    }
+
+    #[test]
+    /// Test that the day, hour and weekday are in the time zone of the config
+    fn test_timezones() {
+        initialize();
+        // Sent at 00:44 -0700, 10:12 +0200 and 08:30 +0900
+        let path = "tests/resources/encoded";
+        let import = |timezone: DisplayTimezone| {
+            let mut config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+            config.timezone = timezone;
+            let importer = ps_importer::importer(config.clone());
+            let database = Database::new(&config.database_path).unwrap();
+            let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+            handle.join().expect("").expect("");
+
+            let saved = Database::config(&config.database_path).unwrap();
+            assert_eq!(saved.timezone, timezone);
+
+            let db = Database::new(&config.database_path).unwrap();
+            let mails = db
+                .query(&Query::Normal {
+                    fields: vec![Field::Day, Field::Hour, Field::Weekday],
+                    filters: Vec::new(),
+                    range: 0..3,
+                })
+                .expect("Expected Mails");
+            let mut times: Vec<(i64, i64, i64)> = mails
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Normal(row) => (
+                        row[&Field::Day].value().as_i64().unwrap(),
+                        row[&Field::Hour].value().as_i64().unwrap(),
+                        row[&Field::Weekday].value().as_i64().unwrap(),
+                    ),
+                    _ => panic!(),
+                })
+                .collect();
+            times.sort_unstable();
+            times
+        };
+        // In UTC, the last one was sent on Saturday evening
+        assert_eq!(
+            import(DisplayTimezone::Offset(0)),
+            vec![(9, 7, 5), (10, 8, 6), (10, 23, 6)]
+        );
+        // The wall clock of each sender
+        assert_eq!(
+            import(DisplayTimezone::Sender),
+            vec![(9, 0, 5), (10, 10, 6), (11, 8, 7)]
+        );
+    }
+
     /// Test that the AppleMail importer works
     #[test]
     /// FIXME: On windows we have an issue with the `\n` / `\r\n` line endings it seems
@@ -65,10 +196,13 @@ mod tests {
         let path = "tests/resources/applemail";
         let config =
             Config::new(None, path, vec!["".to_string()], FormatType::AppleMail).expect("Config");
-        let importer = ps_importer::applemail_importer(config.clone());
+        let importer = ps_importer::importer(config.clone());
 
         let (_receiver, handle) = importer
-            .import(Database::new(&config.database_path).unwrap())
+            .import(
+                Database::new(&config.database_path).unwrap(),
+                CancelToken::default(),
+            )
             .unwrap();
         handle.join().expect("").expect("");
         // The temporary database path
@@ -81,4 +215,1077 @@ mod tests {
         let mails = mails.expect("Expected Mails");
         assert_eq!(mails.len(), 4);
     }
+
+    #[test]
+    /// Test that the Maildir importer works and maps the folders and flags
+    fn test_maildir_importer() {
+        initialize();
+        let path = "tests/resources/maildir";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::Maildir).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 4);
+
+        let lunch = row_with_subject(&mails, "Lunch next week?");
+        assert_eq!(row_tags(&lunch), vec!["INBOX", "Replied", "Flagged"]);
+        assert_eq!(lunch[&Field::MetaIsSeen].value(), &Value::Bool(true));
+
+        let sale = row_with_subject(&mails, "Our spring sale");
+        assert_eq!(row_tags(&sale), vec!["INBOX"]);
+        assert_eq!(sale[&Field::MetaIsSeen].value(), &Value::Bool(false));
+
+        let status = row_with_subject(&mails, "Project status");
+        assert_eq!(row_tags(&status), vec!["Work/Projects", "Trashed"]);
+        assert_eq!(status[&Field::MetaIsSeen].value(), &Value::Bool(false));
+    }
+
+    #[test]
+    /// Test that the Thunderbird importer finds the extension-less mailboxes
+    fn test_thunderbird_importer() {
+        initialize();
+        let path = "tests/resources/thunderbird";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::Thunderbird).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        // The expunged mail is not imported
+        assert_eq!(mails.len(), 4);
+
+        let new_year = row_with_subject(&mails, "Happy new year");
+        assert_eq!(row_tags(&new_year), vec!["INBOX"]);
+        assert_eq!(new_year[&Field::MetaIsSeen].value(), &Value::Bool(true));
+
+        let status = row_with_subject(&mails, "Project status");
+        assert_eq!(row_tags(&status), vec!["Archives", "Archives/2021"]);
+        assert_eq!(status[&Field::MetaIsSeen].value(), &Value::Bool(false));
+    }
+
+    #[test]
+    /// Test that the Outlook importer converts the `.pst` file with `readpst`.
+    /// This is skipped if `readpst` (libpst) is not installed.
+    fn test_outlook_importer() {
+        initialize();
+        if ps_importer::check_requirements(&FormatType::Outlook).is_err() {
+            println!("Skipping the Outlook import, readpst is not installed");
+            return;
+        }
+        let path = "tests/resources/outlook";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::Outlook).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+
+        // The root folder of the file (`Outlook Data File`) is not a tag
+        let report = row_with_subject(&mails, "Quarterly report");
+        assert_eq!(row_tags(&report), vec!["Inbox"]);
+
+        let status = row_with_subject(&mails, "Project status");
+        assert_eq!(row_tags(&status), vec!["Inbox", "Inbox/Projects"]);
+    }
+
+    #[test]
+    /// Test that the mbox importer reads the mails out of a compressed mbox
+    fn test_mbox_archive_importer() {
+        initialize();
+        let path = "tests/resources/archives/INBOX.mbox.gz";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+
+        let status = row_with_subject(&mails, "Project status");
+        assert_eq!(row_tags(&status), vec!["INBOX"]);
+    }
+
+    #[test]
+    /// Test that the Maildir importer reads the folders out of a `.tar.gz`
+    fn test_maildir_archive_importer() {
+        initialize();
+        let path = "tests/resources/archives/maildir.tar.gz";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::Maildir).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 4);
+
+        let lunch = row_with_subject(&mails, "Lunch next week?");
+        assert_eq!(row_tags(&lunch), vec!["INBOX", "Replied", "Flagged"]);
+        assert_eq!(lunch[&Field::MetaIsSeen].value(), &Value::Bool(true));
+
+        let status = row_with_subject(&mails, "Project status");
+        assert_eq!(row_tags(&status), vec!["Work/Projects", "Trashed"]);
+    }
+
+    #[test]
+    /// Test that the Gmail importer reads a zipped GMVault export and
+    /// pairs the mails with their `.meta` files
+    fn test_gmail_archive_importer() {
+        initialize();
+        let path = "tests/resources/archives/gmvault.zip";
+        let config =
+            Config::new(None, path, vec!["".to_string()], FormatType::GmailVault).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+
+        let lunch = row_with_subject(&mails, "Lunch next week?");
+        assert_eq!(row_tags(&lunch), vec!["\\Inbox", "\\seen", "Friends"]);
+        assert_eq!(lunch[&Field::MetaIsSeen].value(), &Value::Bool(true));
+
+        let sale = row_with_subject(&mails, "Our spring sale");
+        assert_eq!(sale[&Field::MetaIsSeen].value(), &Value::Bool(false));
+    }
+
+    #[test]
+    /// Test that several sources are imported into one database
+    /// and that each mail knows its source
+    fn test_multiple_sources() {
+        initialize();
+        let sources = vec![
+            Source::new("tests/resources/maildir", FormatType::Maildir),
+            Source::new(
+                "tests/resources/archives/gmvault.zip",
+                FormatType::GmailVault,
+            ),
+        ];
+        let mut config = Config::with_sources(None::<&str>, sources, Vec::new()).expect("Config");
+        // Some of the mails are the same in both sources
+        config.duplicates = DuplicatePolicy::Keep;
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        assert_eq!(db.total_mails().unwrap(), 7);
+        let mut groups: Vec<(String, usize)> = db
+            .query(&Query::Grouped {
+                filters: Vec::new(),
+                group_by: Field::Source,
+                metric: Metric::Count,
+            })
+            .expect("Expected Groups")
+            .into_iter()
+            .map(|e| match e {
+                QueryResult::Grouped { value, count } => (value.to_string(), count),
+                _ => panic!(),
+            })
+            .collect();
+        groups.sort();
+        assert_eq!(
+            groups,
+            vec![
+                ("tests/resources/archives/gmvault.zip".to_string(), 3),
+                ("tests/resources/maildir".to_string(), 4)
+            ]
+        );
+
+        // The sources are saved with the database
+        let saved = Database::config(&config.database_path).unwrap();
+        let formats: Vec<FormatType> = saved.sources.iter().map(|e| e.format).collect();
+        assert_eq!(formats, vec![FormatType::Maildir, FormatType::GmailVault]);
+    }
+
+    #[test]
+    /// Test that the sources estimate the total while reading, and that the
+    /// exact total is sent at the end
+    fn test_write_total_estimate() {
+        initialize();
+        let sources = vec![
+            Source::new("tests/resources/maildir", FormatType::Maildir),
+            Source::new(
+                "tests/resources/archives/gmvault.zip",
+                FormatType::GmailVault,
+            ),
+        ];
+        let mut config = Config::with_sources(None::<&str>, sources, Vec::new()).expect("Config");
+        config.duplicates = DuplicatePolicy::Keep;
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let totals: Vec<usize> = receiver
+            .try_iter()
+            .filter_map(|e| match e {
+                Message::WriteTotal(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        // The files in the Maildir, then the mails in the archive on top of them
+        assert_eq!(totals, vec![4, 7, 7]);
+    }
+
+    #[test]
+    /// Test that a source that can't be read doesn't stop the other sources
+    fn test_failing_source() {
+        initialize();
+        let sources = vec![
+            Source::new("tests/resources/does-not-exist", FormatType::Maildir),
+            Source::new("tests/resources/maildir", FormatType::Maildir),
+        ];
+        let config = Config::with_sources(None::<&str>, sources, Vec::new()).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        // The failing source is reported instead of finishing normally
+        let messages: Vec<Message> = receiver.try_iter().collect();
+        assert!(messages.iter().any(|e| matches!(e, Message::Error(_))));
+        assert!(!messages.iter().any(|e| matches!(e, Message::Done)));
+
+        // But the mails of the other source are still imported
+        let db = Database::new(&config.database_path).unwrap();
+        assert_eq!(db.total_mails().unwrap(), 4);
+    }
+
+    #[test]
+    /// Test that the mails are matched against the identities of the user,
+    /// with wildcards and regardless of the case
+    fn test_identities() {
+        initialize();
+        let path = "tests/resources/maildir";
+        let mut config = Config::new(None, path, Vec::new(), FormatType::Maildir).expect("Config");
+        config.identities =
+            Identity::parse_list("Work: *@WORK.example.com; Private: john@example.com");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![Field::SenderLocalPart, Field::IsSend, Field::Identity],
+                filters: Vec::new(),
+                range: 0..4,
+            })
+            .expect("Expected Mails");
+        let mut identities: Vec<(String, bool, String)> = mails
+            .into_iter()
+            .map(|e| match e {
+                QueryResult::Normal(row) => (
+                    row[&Field::SenderLocalPart].to_string(),
+                    row[&Field::IsSend].value() == &Value::Bool(true),
+                    row[&Field::Identity].to_string(),
+                ),
+                _ => panic!(),
+            })
+            .collect();
+        identities.sort();
+        // Bob's address matches the work identity, all mails
+        // were sent to the private one
+        assert_eq!(
+            identities,
+            vec![
+                ("bob".to_string(), true, "Work".to_string()),
+                ("jane".to_string(), false, "Private".to_string()),
+                ("jane".to_string(), false, "Private".to_string()),
+                ("news".to_string(), false, "Private".to_string()),
+            ]
+        );
+
+        // The identities are saved with the database
+        let saved = Database::config(&config.database_path).unwrap();
+        assert_eq!(saved.identities, config.identities);
+    }
+
+    #[test]
+    /// Test that all recipients in `To`, `Cc` and `Bcc` are stored,
+    /// and that an email is counted once per recipient
+    fn test_recipients() {
+        initialize();
+        let path = "tests/resources/recipients";
+        let mut config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        config.identities = Identity::parse_list("john@example.com");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let grouped = |filters: Vec<Filter>, group_by: Field| {
+            let mut counts: Vec<(String, usize)> = db
+                .query(&Query::Grouped {
+                    filters,
+                    group_by,
+                    metric: Metric::Count,
+                })
+                .expect("Expected Groups")
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Grouped { count, value } => (value.to_string(), count),
+                    _ => panic!(),
+                })
+                .collect();
+            counts.sort();
+            counts
+        };
+        // Who the user writes to most
+        assert_eq!(
+            grouped(
+                vec![Filter::Is(ValueField::bool(&Field::IsSend, true))],
+                Field::RecipientAddress
+            ),
+            vec![
+                ("alice@example.org".to_string(), 1),
+                ("bob@example.net".to_string(), 2),
+                ("jane@example.org".to_string(), 2),
+            ]
+        );
+        assert_eq!(
+            grouped(Vec::new(), Field::RecipientKind),
+            vec![
+                ("bcc".to_string(), 1),
+                ("cc".to_string(), 2),
+                ("to".to_string(), 3),
+            ]
+        );
+        // Filtering by a recipient lists each email once
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject],
+                filters: vec![Filter::Like(ValueField::string(
+                    &Field::RecipientAddress,
+                    "bob@example.net",
+                ))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+        let plan = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject],
+                filters: vec![Filter::Like(ValueField::string(
+                    &Field::RecipientName,
+                    "Doe, Bob",
+                ))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        assert_eq!(plan.len(), 1);
+        row_with_subject(&plan, "The plan");
+    }
+
+    #[test]
+    /// Test that mailing lists, newsletters and notifications are detected
+    fn test_mailing_lists() {
+        initialize();
+        let path = "tests/resources/lists";
+        let config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mut lists: Vec<(String, usize)> = db
+            .query(&Query::Grouped {
+                filters: vec![Filter::Is(ValueField::bool(&Field::IsBulk, true))],
+                group_by: Field::MailingList,
+                metric: Metric::Count,
+            })
+            .expect("Expected Groups")
+            .into_iter()
+            .map(|e| match e {
+                QueryResult::Grouped { count, value } => (value.to_string(), count),
+                _ => panic!(),
+            })
+            .collect();
+        lists.sort();
+        // The notification only has `Precedence: bulk`
+        assert_eq!(
+            lists,
+            vec![
+                ("".to_string(), 1),
+                ("shop.example.com".to_string(), 1),
+                ("users.lists.example.org".to_string(), 2),
+            ]
+        );
+
+        let personal = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject, Field::MailingList],
+                filters: vec![Filter::Is(ValueField::bool(&Field::IsBulk, false))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        assert_eq!(personal.len(), 1);
+        let lunch = row_with_subject(&personal, "Lunch?");
+        assert_eq!(lunch[&Field::MailingList].to_string(), "");
+    }
+
+    #[test]
+    /// Test that the threads are reconstructed from `References` and `In-Reply-To`
+    fn test_threads() {
+        initialize();
+        let path = "tests/resources/threads";
+        let mut config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        config.identities = Identity::parse_list("john@example.com");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mut threads: Vec<(String, usize)> = db
+            .query(&Query::Grouped {
+                filters: Vec::new(),
+                group_by: Field::Thread,
+                metric: Metric::Count,
+            })
+            .expect("Expected Groups")
+            .into_iter()
+            .map(|e| match e {
+                QueryResult::Grouped { count, value } => (value.to_string(), count),
+                _ => panic!(),
+            })
+            .collect();
+        threads.sort();
+        // The replies to a missing mail are still one thread
+        assert_eq!(
+            threads,
+            vec![
+                ("Dinner".to_string(), 3),
+                ("Question".to_string(), 1),
+                ("Re: An old thing".to_string(), 2),
+            ]
+        );
+
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![
+                    Field::Subject,
+                    Field::ThreadId,
+                    Field::ThreadSize,
+                    Field::ThreadPosition,
+                ],
+                filters: vec![Filter::Like(ValueField::string(&Field::Thread, "Dinner"))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        let last = row_with_subject(&mails, "Re: Dinner");
+        assert_eq!(last[&Field::ThreadId].to_string(), "dinner-1@example.org");
+        assert_eq!(last[&Field::ThreadSize].value(), &Value::from(3));
+
+        // Only the first mail of the dinner thread got a reply from the user
+        let answered = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject, Field::ThreadPosition],
+                filters: vec![Filter::Is(ValueField::bool(&Field::IsAnswered, true))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        assert_eq!(answered.len(), 1);
+        let dinner = row_with_subject(&answered, "Dinner");
+        assert_eq!(dinner[&Field::ThreadPosition].value(), &Value::from(1));
+    }
+
+    #[test]
+    /// Test that the attachments are found in the MIME tree
+    fn test_attachments() {
+        initialize();
+        let path = "tests/resources/attachments";
+        let config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        // All of the mails know their attachments
+        assert_eq!(db.total_outdated().unwrap(), 0);
+        let grouped = |filters: Vec<Filter>, group_by: Field| {
+            let mut groups: Vec<(String, usize)> = db
+                .query(&Query::Grouped {
+                    filters,
+                    group_by,
+                    metric: Metric::Count,
+                })
+                .expect("Expected Groups")
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Grouped { count, value } => (value.to_string(), count),
+                    _ => panic!(),
+                })
+                .collect();
+            groups.sort();
+            groups
+        };
+        assert_eq!(
+            grouped(Vec::new(), Field::AttachmentType),
+            vec![
+                ("application/pdf".to_string(), 2),
+                ("image/jpeg".to_string(), 1),
+                ("image/png".to_string(), 1),
+            ]
+        );
+        // Each email is counted once, even with several attachments
+        assert_eq!(
+            grouped(
+                vec![Filter::Like(ValueField::string(
+                    &Field::AttachmentType,
+                    "application/pdf"
+                ))],
+                Field::SenderDomain
+            ),
+            vec![
+                ("example.org".to_string(), 1),
+                ("shop.example.com".to_string(), 1),
+            ]
+        );
+        assert_eq!(
+            grouped(
+                vec![Filter::Is(ValueField::bool(&Field::HasAttachment, false))],
+                Field::SenderDomain
+            ),
+            vec![("example.net".to_string(), 1)]
+        );
+
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![
+                    Field::Subject,
+                    Field::AttachmentCount,
+                    Field::AttachmentsSize,
+                    Field::AttachmentName,
+                ],
+                filters: vec![Filter::Is(ValueField::bool(&Field::HasAttachment, true))],
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        assert_eq!(mails.len(), 3);
+        let report = row_with_subject(&mails, "The report");
+        assert_eq!(report[&Field::AttachmentCount].value(), &Value::from(2));
+        assert_eq!(report[&Field::AttachmentsSize].value(), &Value::from(17));
+        let holiday = row_with_subject(&mails, "Holiday pictures");
+        assert_eq!(
+            holiday[&Field::AttachmentName].to_string(),
+            "Strand überall.jpg"
+        );
+    }
+
+    #[test]
+    /// Test that the segments can be weighed by the size of the mails
+    fn test_sizes() {
+        initialize();
+        let path = "tests/resources/recipients";
+        let config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let mails = db
+            .query(&Query::Normal {
+                fields: vec![Field::Subject, Field::Size],
+                filters: Vec::new(),
+                range: 0..10,
+            })
+            .expect("Expected Mails");
+        let size = |subject: &str| {
+            let row = row_with_subject(&mails, subject);
+            row[&Field::Size].value().as_u64().expect("Expected Size") as usize
+        };
+        let (plan, again, reply) = (
+            size("The plan"),
+            size("The plan, again"),
+            size("Re: The plan, again"),
+        );
+        assert!(plan > 0 && again > 0 && reply > 0);
+
+        let weighed = |group_by: Field| {
+            let mut groups: Vec<(String, usize)> = db
+                .query(&Query::Grouped {
+                    filters: Vec::new(),
+                    group_by,
+                    metric: Metric::Size,
+                })
+                .expect("Expected Groups")
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Grouped { count, value } => (value.to_string(), count),
+                    _ => panic!(),
+                })
+                .collect();
+            groups.sort();
+            groups
+        };
+        assert_eq!(
+            weighed(Field::SenderDomain),
+            vec![
+                ("example.com".to_string(), plan + again),
+                ("example.org".to_string(), reply),
+            ]
+        );
+        // The first mail has two `To` recipients, but is added up once
+        assert_eq!(
+            weighed(Field::RecipientKind),
+            vec![
+                ("cc".to_string(), plan + reply),
+                ("to".to_string(), plan + again + reply),
+            ]
+        );
+    }
+
+    #[test]
+    /// Test that the decoded text of the mails can be searched
+    fn test_full_text() {
+        initialize();
+        let path = "tests/resources/bodies";
+        let config = Config::new(None, path, Vec::new(), FormatType::Mbox).expect("Config");
+        let importer = ps_importer::importer(config.clone());
+        let database = Database::new(&config.database_path).unwrap();
+        let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+
+        let db = Database::new(&config.database_path).unwrap();
+        let search = |text: &str| {
+            let mut subjects: Vec<String> = db
+                .query(&Query::Normal {
+                    fields: vec![Field::Subject],
+                    filters: vec![Filter::FullText(text.to_string())],
+                    range: 0..10,
+                })
+                .expect("Expected Mails")
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Normal(row) => row[&Field::Subject].to_string().trim().to_owned(),
+                    _ => panic!(),
+                })
+                .collect();
+            subjects.sort();
+            subjects
+        };
+        assert_eq!(search("montag"), vec!["Treffen".to_string()]);
+        assert_eq!(search("Monday"), vec!["Notes".to_string()]);
+        // Quoted-printable, base64 and HTML are decoded
+        assert_eq!(search("Grüße"), vec!["Treffen".to_string()]);
+        assert_eq!(search("order invoice"), vec!["Your order".to_string()]);
+        // Styles, the HTML alternative of a plain text and attachments are left out
+        assert!(search("color").is_empty());
+        assert!(search("Besprechung").is_empty());
+        assert!(search("agenda").is_empty());
+        // All the words have to be found
+        assert!(search("notes invoice").is_empty());
+
+        let groups = db
+            .query(&Query::Grouped {
+                filters: vec![Filter::FullText("the".to_string())],
+                group_by: Field::SenderDomain,
+                metric: Metric::Count,
+            })
+            .expect("Expected Groups");
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    /// Test that importing into an existing database only adds the new mails
+    fn test_update_database() {
+        initialize();
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("postsack-update-{}", std::process::id()));
+        let mails_path = folder.join("maildir");
+        copy_folder("tests/resources/maildir".as_ref(), &mails_path);
+        let database_path = folder.join("mails.sqlite");
+
+        let import = || {
+            let config = Config::new(
+                Some(&database_path),
+                &mails_path,
+                vec!["".to_string()],
+                FormatType::Maildir,
+            )
+            .expect("Config");
+            let importer = ps_importer::importer(config.clone());
+            let database = Database::new(&config.database_path).unwrap();
+            let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+            handle.join().expect("").expect("");
+            Database::new(&config.database_path)
+                .unwrap()
+                .total_mails()
+                .expect("Expected total mails")
+        };
+
+        assert_eq!(import(), 4);
+        // Nothing changed, so nothing is added
+        let before_update = ps_core::chrono::Utc::now();
+        assert_eq!(import(), 4);
+        // The update is recorded
+        let last_import = Database::new(&database_path)
+            .unwrap()
+            .last_import()
+            .expect("Expected meta")
+            .expect("Expected the last import time");
+        assert!(last_import.timestamp() >= before_update.timestamp());
+
+        // Reading a mail moves it to `cur` and adds the seen flag, which doesn't make it new
+        std::fs::rename(
+            mails_path.join("new/1614556800.M3P100.localhost"),
+            mails_path.join("cur/1614556800.M3P100.localhost;2,S"),
+        )
+        .unwrap();
+        assert_eq!(import(), 4);
+
+        // A new mail arrives
+        let content =
+            std::fs::read_to_string(mails_path.join("cur/1614556800.M3P100.localhost;2,S"))
+                .unwrap()
+                .replace("Subject:", "Subject: Re:")
+                .replace("<m3@example.com>", "<m5@example.com>");
+        std::fs::write(mails_path.join("new/1640995200.M5P100.localhost"), content).unwrap();
+        assert_eq!(import(), 5);
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    /// Test that updating a database adds the new mails to the existing threads
+    fn test_update_threads() {
+        initialize();
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("postsack-threads-{}", std::process::id()));
+        let mails_path = folder.join("threads");
+        copy_folder("tests/resources/threads".as_ref(), &mails_path);
+        let database_path = folder.join("mails.sqlite");
+
+        let import = || {
+            let mut config = Config::new(
+                Some(&database_path),
+                &mails_path,
+                Vec::new(),
+                FormatType::Mbox,
+            )
+            .expect("Config");
+            config.identities = Identity::parse_list("john@example.com");
+            let importer = ps_importer::importer(config.clone());
+            let database = Database::new(&config.database_path).unwrap();
+            let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+            handle.join().expect("").expect("");
+            let mut threads: Vec<(String, usize)> = Database::new(&config.database_path)
+                .unwrap()
+                .query(&Query::Grouped {
+                    filters: Vec::new(),
+                    group_by: Field::Thread,
+                    metric: Metric::Count,
+                })
+                .expect("Expected Groups")
+                .into_iter()
+                .map(|e| match e {
+                    QueryResult::Grouped { count, value } => (value.to_string(), count),
+                    _ => panic!(),
+                })
+                .collect();
+            threads.sort();
+            threads
+        };
+
+        assert_eq!(
+            import(),
+            vec![
+                ("Dinner".to_string(), 3),
+                ("Question".to_string(), 1),
+                ("Re: An old thing".to_string(), 2),
+            ]
+        );
+
+        // The missing first mail of a thread and a reply to another one arrive
+        let content = "From nobody Mon Sep 17 00:00:00 2001
+From: Alice <alice@example.org>
+To: John Doe <john@example.com>
+Date: Sat, 10 Jun 2006 08:00:00 +0000
+Message-ID: <old-1@example.org>
+Subject: An old thing
+
+The first one.
+
+From nobody Mon Sep 17 00:00:00 2001
+From: John Doe <john@example.com>
+To: Bob <bob@example.net>
+Date: Sat, 10 Jun 2006 10:00:00 +0000
+Message-ID: <question-2@example.com>
+In-Reply-To: <question-1@example.net>
+Subject: Re: Question
+
+Yes.
+";
+        std::fs::write(mails_path.join("later.mbox"), content).unwrap();
+        assert_eq!(
+            import(),
+            vec![
+                ("An old thing".to_string(), 3),
+                ("Dinner".to_string(), 3),
+                ("Question".to_string(), 2),
+            ]
+        );
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    /// Test that a cancelled import keeps the mails it wrote so far and
+    /// that updating the database only adds the rest
+    fn test_cancel_and_resume() {
+        initialize();
+        const TOTAL: usize = 3000;
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("postsack-resume-{}", std::process::id()));
+        let mails_path = folder.join("maildir");
+        std::fs::create_dir_all(mails_path.join("cur")).unwrap();
+        for n in 0..TOTAL {
+            let content = format!(
+                "From: Jane <jane@example.org>\nTo: John <john@example.com>\n\
+                 Date: Fri, 1 Jan 2021 12:00:00 +0000\nSubject: Mail {}\n\
+                 Message-ID: <resume-{}@example.org>\n\nHello\n",
+                n, n
+            );
+            let name = format!("1609502400.M{}P100.localhost;2,S", n);
+            std::fs::write(mails_path.join("cur").join(name), content).unwrap();
+        }
+        let database_path = folder.join("mails.sqlite");
+        let config = Config::new(
+            Some(&database_path),
+            &mails_path,
+            vec!["".to_string()],
+            FormatType::Maildir,
+        )
+        .expect("Config");
+        let database = || {
+            let mut database = Database::new(&config.database_path).unwrap();
+            database.set_checkpoint_interval(50);
+            database
+        };
+
+        // Cancel after some checkpoints were committed
+        let cancel = CancelToken::default();
+        let importer = ps_importer::importer(config.clone());
+        let (receiver, handle) = importer.import(database(), cancel.clone()).unwrap();
+        let mut written = 0;
+        let mut messages = Vec::new();
+        for message in receiver.iter() {
+            if matches!(message, Message::WriteOne) {
+                written += 1;
+                if written == 200 {
+                    cancel.cancel();
+                }
+            }
+            messages.push(message);
+        }
+        handle.join().expect("").expect("");
+        assert!(messages.iter().any(|m| matches!(m, Message::Cancelled)));
+        assert!(!messages.iter().any(|m| matches!(m, Message::Done)));
+        assert!(!messages.iter().any(|m| matches!(m, Message::Error(_))));
+        let imported = Database::new(&config.database_path)
+            .unwrap()
+            .total_mails()
+            .unwrap();
+        assert!(imported >= 200, "{} mails were kept", imported);
+        assert!(imported < TOTAL, "{} mails were imported", imported);
+
+        // Resuming adds the missing mails, and not the ones that are already there
+        let importer = ps_importer::importer(config.clone());
+        let (_receiver, handle) = importer.import(database(), CancelToken::default()).unwrap();
+        handle.join().expect("").expect("");
+        let db = Database::new(&config.database_path).unwrap();
+        assert_eq!(db.total_mails().unwrap(), TOTAL);
+        assert_eq!(db.total_duplicates().unwrap(), 0);
+
+        // The folder also has the `-wal` and `-shm` files of the database
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    /// Test that mails with the same Message-ID are merged into one
+    /// with the tags of all copies, or kept if configured
+    fn test_duplicates() {
+        initialize();
+        let path = "tests/resources/duplicates";
+        let import = |duplicates: DuplicatePolicy| {
+            let mut config =
+                Config::new(None, path, vec!["".to_string()], FormatType::Maildir).expect("Config");
+            config.duplicates = duplicates;
+            let importer = ps_importer::importer(config.clone());
+            let database = Database::new(&config.database_path).unwrap();
+            let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
+            handle.join().expect("").expect("");
+            Database::new(&config.database_path).unwrap()
+        };
+
+        let db = import(DuplicatePolicy::Merge);
+        let mails = db.query(&Query::Normal {
+            fields: vec![Field::Subject, Field::MetaTags, Field::MetaIsSeen],
+            filters: Vec::new(),
+            range: 0..10,
+        });
+        let mails = mails.expect("Expected Mails");
+        assert_eq!(mails.len(), 2);
+        let new_year = row_with_subject(&mails, "Happy new year");
+        let mut tags = row_tags(&new_year);
+        tags.sort();
+        assert_eq!(tags, vec!["Archive", "INBOX"]);
+        // Only the copy in the inbox was seen
+        assert_eq!(new_year[&Field::MetaIsSeen].value(), &Value::Bool(true));
+        assert_eq!(db.total_duplicates().unwrap(), 1);
+
+        let db = import(DuplicatePolicy::Keep);
+        assert_eq!(db.total_mails().unwrap(), 3);
+        assert_eq!(db.total_duplicates().unwrap(), 1);
+    }
+
+    #[test]
+    /// Test that the format of the test resources is detected
+    fn test_detect_format() {
+        initialize();
+        let detected = |path: &str| {
+            ps_importer::detect_format(path)
+                .first()
+                .map(|(format, _)| *format)
+        };
+        assert_eq!(detected("tests/resources/mbox"), Some(FormatType::Mbox));
+        assert_eq!(
+            detected("tests/resources/applemail"),
+            Some(FormatType::AppleMail)
+        );
+        assert_eq!(
+            detected("tests/resources/maildir"),
+            Some(FormatType::Maildir)
+        );
+        assert_eq!(
+            detected("tests/resources/thunderbird"),
+            Some(FormatType::Thunderbird)
+        );
+        assert_eq!(
+            detected("tests/resources/gmvault"),
+            Some(FormatType::GmailVault)
+        );
+        assert_eq!(
+            detected("tests/resources/outlook"),
+            Some(FormatType::Outlook)
+        );
+        assert_eq!(
+            detected("tests/resources/archives/gmvault.zip"),
+            Some(FormatType::GmailVault)
+        );
+        assert_eq!(
+            detected("tests/resources/archives/maildir.tar.gz"),
+            Some(FormatType::Maildir)
+        );
+        assert_eq!(
+            detected("tests/resources/archives/INBOX.mbox.gz"),
+            Some(FormatType::Mbox)
+        );
+        assert!(ps_importer::detect_format("tests/resources/does-not-exist").is_empty());
+
+        // A notmuch database is a Maildir with a `.notmuch` folder
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("postsack-detect-{}", std::process::id()));
+        copy_folder("tests/resources/maildir".as_ref(), &folder);
+        assert_eq!(
+            detected(folder.to_str().unwrap()),
+            Some(FormatType::Maildir)
+        );
+        std::fs::create_dir_all(folder.join(".notmuch/xapian")).unwrap();
+        assert_eq!(
+            detected(folder.to_str().unwrap()),
+            Some(FormatType::Notmuch)
+        );
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    fn copy_folder(from: &std::path::Path, to: &std::path::Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_folder(&entry.path(), &target);
+            } else {
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    fn row_with_subject(mails: &[QueryResult], subject: &str) -> QueryRow {
+        mails
+            .iter()
+            .find_map(|s| match s {
+                QueryResult::Normal(row) if row[&Field::Subject].to_string().trim() == subject => {
+                    Some(row.clone())
+                }
+                _ => None,
+            })
+            .expect("Expected Mail")
+    }
+
+    fn row_tags(row: &QueryRow) -> Vec<String> {
+        match row[&Field::MetaTags].value() {
+            Value::Array(n) => n.iter().map(|e| e.as_str().unwrap().into()).collect(),
+            _ => panic!(),
+        }
+    }
 }
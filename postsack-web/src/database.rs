@@ -6,8 +6,8 @@ use std::{ops::Range, path::Path};
 use ps_core::{
     crossbeam_channel::Sender,
     eyre::{bail, Result},
-    Config, DBMessage, DatabaseLike, DatabaseQuery, Field, Filter, Query, QueryResult, Value,
-    ValueField,
+    Config, DBMessage, DatabaseLike, DatabaseQuery, Field, Filter, Metric, Query, QueryResult,
+    Value, ValueField,
 };
 use ps_core::{OtherQuery, QueryRow};
 
@@ -31,6 +31,9 @@ impl Entry {
     fn value(&self, field: &Field) -> Value {
         match field {
             Field::Path => Value::String("".to_string()),
+            Field::Source => Value::String("".to_string()),
+            Field::Identity => Value::String("".to_string()),
+            Field::FailedHeaders => Value::String("".to_string()),
             Field::SenderDomain => Value::String(self.sender_domain.to_string()),
             Field::SenderLocalPart => Value::String(self.sender_local_part.to_string()),
             Field::SenderName => Value::String(self.sender_name.to_string()),
@@ -38,20 +41,51 @@ impl Entry {
             Field::ToName => Value::String(self.to_name.to_string()),
             Field::ToAddress => Value::String(self.to_address.to_string()),
             Field::ToGroup => Value::String("".to_string()),
+            Field::RecipientName => Value::String(self.to_name.to_string()),
+            Field::RecipientAddress => Value::String(self.to_address.to_string()),
+            Field::RecipientKind => Value::String("to".to_string()),
+            Field::MailingList => Value::String("".to_string()),
+            Field::Thread => Value::String(self.subject.to_string()),
+            Field::ThreadId => Value::String("".to_string()),
+            Field::ThreadSize => Value::Number(1.into()),
+            Field::ThreadPosition => Value::Number(1.into()),
+            Field::Correspondent => Value::String(match self.is_send {
+                true => self.to_address.to_string(),
+                false => format!("{}@{}", self.sender_local_part, self.sender_domain),
+            }),
+            Field::ReplyLatency => Value::Null,
+            Field::AttachmentType => Value::String("".to_string()),
+            Field::AttachmentName => Value::String("".to_string()),
 
             Field::Year => Value::Number(self.year.into()),
             Field::Month => Value::Number(self.month.into()),
             Field::Day => Value::Number(self.day.into()),
+            Field::Hour => Value::Number((self.timestamp / 3600 % 24).into()),
+            // The 1st of January 1970 was a Thursday
+            Field::Weekday => Value::Number(((self.timestamp / 86400 + 3) % 7 + 1).into()),
             Field::Timestamp => Value::Number(self.timestamp.into()),
+            Field::Size => Value::Number(self.size().into()),
+            Field::AttachmentCount => Value::Number(0.into()),
+            Field::AttachmentsSize => Value::Number(0.into()),
 
             Field::IsReply => Value::Bool(self.is_reply),
             Field::IsSend => Value::Bool(self.is_send),
+            Field::IsBulk => Value::Bool(false),
+            Field::IsAnswered => Value::Bool(false),
+            Field::IsReplied => Value::Bool(false),
+            Field::HasAttachment => Value::Bool(false),
 
             Field::MetaIsSeen => Value::Bool(false),
             Field::MetaTags => Value::Array(Vec::new()),
+            Field::MetaThreadId => Value::String("".to_string()),
         }
     }
 
+    /// The fake entries have no contents, so longer subjects make larger mails
+    fn size(&self) -> usize {
+        2000 + self.subject.len() * 100
+    }
+
     fn as_row(&self, fields: &[Field]) -> QueryRow {
         let mut row = QueryRow::new();
         for field in fields {
@@ -105,14 +139,19 @@ impl FakeDatabase {
         result
     }
 
-    fn query_grouped(&self, filters: &[Filter], group_by: &Field) -> Vec<QueryResult> {
+    fn query_grouped(
+        &self,
+        filters: &[Filter],
+        group_by: &Field,
+        metric: &Metric,
+    ) -> Vec<QueryResult> {
         let mut map = HashMap::<HashedValue, usize>::new();
-        for entry in self
-            .filtered(filters)
-            .map(|e| HashedValue(e.value(group_by)))
-        {
-            let entry = map.entry(entry).or_insert(0);
-            *entry += 1;
+        for entry in self.filtered(filters) {
+            let weight = match metric {
+                Metric::Count => 1,
+                Metric::Size => entry.size(),
+            };
+            *map.entry(HashedValue(entry.value(group_by))).or_insert(0) += weight;
         }
 
         let mut result = Vec::new();
@@ -181,6 +220,16 @@ impl FakeDatabase {
                             return false;
                         }
                     }
+                    // The fake entries have no bodies, so the subject is searched instead
+                    Filter::FullText(text) => {
+                        let subject = entry.subject.to_lowercase();
+                        if !text
+                            .split_whitespace()
+                            .all(|e| subject.contains(&e.to_lowercase()))
+                        {
+                            return false;
+                        }
+                    }
                 }
             }
             true
@@ -202,7 +251,11 @@ impl DatabaseQuery for FakeDatabase {
                 filters,
                 range,
             } => Ok(self.query_normal(fields, filters, range)),
-            Query::Grouped { filters, group_by } => Ok(self.query_grouped(filters, group_by)),
+            Query::Grouped {
+                filters,
+                group_by,
+                metric,
+            } => Ok(self.query_grouped(filters, group_by, metric)),
             Query::Other {
                 query: OtherQuery::All(q),
             } => Ok(self.query_other(q)),
@@ -229,6 +282,22 @@ impl DatabaseLike for FakeDatabase {
         Ok(ENTRIES.len())
     }
 
+    fn fingerprints(&self) -> Result<HashSet<i64>> {
+        Ok(HashSet::new())
+    }
+
+    fn total_duplicates(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn total_partial(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn total_outdated(&self) -> Result<usize> {
+        Ok(0)
+    }
+
     fn import(self) -> (Sender<DBMessage>, JoinHandle<Result<usize>>) {
         panic!()
     }
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::path::Path;
 use std::thread::JoinHandle;
 
@@ -20,6 +21,17 @@ pub trait DatabaseLike: DatabaseQuery + Clone {
     where
         Self: Sized;
     fn total_mails(&self) -> Result<usize>;
+    /// The fingerprints of all the emails that were imported before
+    fn fingerprints(&self) -> Result<HashSet<i64>>;
+    /// How many of the emails are copies of another email (same `Message-ID`),
+    /// whether they were merged during the import or not
+    fn total_duplicates(&self) -> Result<usize>;
+    /// How many of the emails could only be parsed partially
+    /// (see [`crate::EmailEntry::failed_headers`])
+    fn total_partial(&self) -> Result<usize>;
+    /// How many of the emails were imported by an older version that didn't
+    /// record their attachments and text yet. Only a new import adds them.
+    fn total_outdated(&self) -> Result<usize>;
     fn import(self) -> (Sender<DBMessage>, JoinHandle<Result<usize>>);
     fn save_config(&self, config: Config) -> Result<()>;
 }
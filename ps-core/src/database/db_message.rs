@@ -6,9 +6,16 @@ use crate::EmailEntry;
 pub enum DBMessage {
     /// Send for a successfuly parsed mail
     Mail(Box<EmailEntry>),
+    /// Send for a successfuly parsed mail that should be merged into
+    /// the mail with the same `Message-ID`, if there already is one.
+    /// See [`crate::DuplicatePolicy`]
+    MergeMail(Box<EmailEntry>),
     /// Send for any kind of error during reading / parsing
     Error(Report),
     /// Send once all parsing is done.
     /// This is used to break out of the receiving loop
     Done,
+    /// Send instead of `Done` if the import was cancelled. The emails
+    /// so far are kept, but the threads are only updated once it's complete
+    Cancelled,
 }
@@ -8,6 +8,15 @@ use std::ops::Range;
 
 pub const AMOUNT_FIELD_NAME: &str = "amount";
 
+/// The emails with one row for each of their recipients
+const RECIPIENTS_JOIN: &str = "JOIN recipients ON recipients.email_id = emails.rowid";
+
+/// The emails with one row for each of their attachments
+const ATTACHMENTS_JOIN: &str = "JOIN attachments ON attachments.email_id = emails.rowid";
+
+/// The emails with the full-text index of their bodies, one row for each
+const BODIES_JOIN: &str = "JOIN bodies ON bodies.rowid = emails.rowid";
+
 #[derive(Clone, Debug)]
 pub enum Filter {
     /// A database Like Operation
@@ -19,6 +28,34 @@ pub enum Filter {
     /// - Trying to handle values as strings
     Contains(ValueField),
     Is(ValueField),
+    /// Emails with all the words in their body. The words
+    /// are searched as they are, without any query syntax.
+    FullText(String),
+}
+
+impl Filter {
+    /// The field this filters by. Full-text filters don't filter a field.
+    pub fn field(&self) -> Option<&Field> {
+        match self {
+            Filter::Like(f) | Filter::NotLike(f) | Filter::Contains(f) | Filter::Is(f) => {
+                Some(&f.field)
+            }
+            Filter::FullText(_) => None,
+        }
+    }
+}
+
+/// The FTS5 query for the words in `text`, each quoted as a string so that
+/// characters like `-` or `*` aren't operators. `None` if there are no words.
+fn full_text_query(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|e| format!("\"{}\"", e.replace('"', "\"\"")))
+        .collect();
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoStaticStr, EnumIter)]
@@ -31,25 +68,61 @@ pub enum Field {
     Year,
     Month,
     Day,
+    Hour,
+    Weekday,
     Timestamp,
+    Size,
     ToGroup,
     ToName,
     ToAddress,
+    Source,
+    Identity,
+    FailedHeaders,
+    RecipientName,
+    RecipientAddress,
+    RecipientKind,
+    MailingList,
+    Thread,
+    ThreadId,
+    ThreadSize,
+    ThreadPosition,
+    Correspondent,
+    ReplyLatency,
+    AttachmentCount,
+    AttachmentsSize,
+    AttachmentType,
+    AttachmentName,
     IsReply,
     IsSend,
+    IsBulk,
+    IsAnswered,
+    IsReplied,
+    HasAttachment,
     Subject,
     MetaIsSeen,
     MetaTags,
+    MetaThreadId,
 }
 
 const INVALID_FIELDS: &[Field] = &[
     Field::Path,
     Field::Subject,
     Field::Timestamp,
+    Field::Size,
     Field::IsReply,
     Field::IsSend,
+    Field::IsBulk,
+    Field::IsAnswered,
+    Field::ThreadId,
+    Field::ThreadPosition,
+    Field::ReplyLatency,
+    Field::IsReplied,
+    Field::AttachmentsSize,
+    Field::AttachmentName,
+    Field::HasAttachment,
     Field::MetaIsSeen,
     Field::MetaTags,
+    Field::MetaThreadId,
 ];
 
 impl Field {
@@ -76,10 +149,40 @@ impl Field {
             Year => "Year",
             Month => "Month",
             Day => "Day",
+            Hour => "Hour",
+            Weekday => "Weekday",
+            Size => "Size",
             Subject => "Subject",
+            Source => "Source",
+            Identity => "Identity",
+            FailedHeaders => "Failed headers",
+            RecipientName => "Recipient name",
+            RecipientAddress => "Recipient address",
+            RecipientKind => "Recipient kind",
+            MailingList => "Mailing list",
+            Thread => "Thread",
+            ThreadSize => "Thread size",
+            Correspondent => "Correspondent",
+            ReplyLatency => "Reply time",
+            AttachmentCount => "Attachments",
+            AttachmentsSize => "Attachments size",
+            AttachmentType => "Attachment type",
+            AttachmentName => "Attachment name",
             _ => self.as_str(),
         }
     }
+
+    /// The join for the fields of the `recipients` and `attachments` tables.
+    /// An email has one row for each of its recipients and attachments,
+    /// so these are queried by joining the table.
+    pub fn join(&self) -> Option<&'static str> {
+        use Field::*;
+        match self {
+            RecipientName | RecipientAddress | RecipientKind => Some(RECIPIENTS_JOIN),
+            AttachmentType | AttachmentName => Some(ATTACHMENTS_JOIN),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for Field {
@@ -153,11 +256,45 @@ pub enum OtherQuery {
     All(Field),
 }
 
+/// What the groups of a `Query::Grouped` are weighed by
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+    /// The amount of emails
+    Count,
+    /// The total size of the emails in bytes
+    Size,
+}
+
+impl Default for Metric {
+    fn default() -> Self {
+        Metric::Count
+    }
+}
+
+impl Metric {
+    pub fn all() -> [Metric; 2] {
+        [Metric::Count, Metric::Size]
+    }
+
+    /// A human readable name
+    pub fn name(&self) -> &str {
+        match self {
+            Metric::Count => "Mails",
+            Metric::Size => "Size",
+        }
+    }
+}
+
+/// Databases from before the sizes were recorded don't know them (`NULL`).
+/// `sum` skips those emails, and the groups with none that are known are left out.
+const KNOWN_SIZE: &str = "HAVING sum(size) IS NOT NULL";
+
 #[derive(Clone, Debug)]
 pub enum Query {
     Grouped {
         filters: Vec<Filter>,
         group_by: Field,
+        metric: Metric,
     },
     Normal {
         fields: Vec<Field>,
@@ -177,6 +314,32 @@ impl Query {
             Query::Other { .. } => &[],
         }
     }
+
+    /// The joins for the tables that the fields of this query need
+    fn joins(&self) -> Vec<&'static str> {
+        let fields: Vec<&Field> = match self {
+            Query::Grouped { group_by, .. } => vec![group_by],
+            Query::Normal { fields, .. } => fields.iter().collect(),
+            Query::Other {
+                query: OtherQuery::All(field),
+            } => vec![field],
+        };
+        let mut joins = Vec::new();
+        let filtered = self.filters().iter().filter_map(|e| e.field());
+        for join in fields.into_iter().chain(filtered).filter_map(Field::join) {
+            if !joins.contains(&join) {
+                joins.push(join);
+            }
+        }
+        let searched = self.filters().iter().any(|e| match e {
+            Filter::FullText(text) => full_text_query(text).is_some(),
+            _ => false,
+        });
+        if searched {
+            joins.push(BODIES_JOIN);
+        }
+        joins
+    }
 }
 
 impl Query {
@@ -192,36 +355,81 @@ impl Query {
                         &format!("%{}%", f.to_string().to_lowercase()),
                     ),
                     Filter::Is(f) => whr.eq(f.field.into(), f.value()),
+                    // FTS5 treats `table = query` like `table MATCH query`
+                    Filter::FullText(text) => match full_text_query(text) {
+                        Some(query) => whr.eq("bodies", &query),
+                        None => continue,
+                    },
                 };
             }
             whr
         };
 
+        // With the recipients or attachments joined, an email can be in several
+        // rows. It is still counted once, and listed once in normal queries.
+        let joins = self.joins();
+        let table = std::iter::once("emails")
+            .chain(joins.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (amount, per_email) = match joins.is_empty() {
+            true => ("count(path)", ""),
+            false => ("count(DISTINCT emails.rowid)", "GROUP BY emails.rowid "),
+        };
+
         let (header, group_by) = match self {
-            Query::Grouped { group_by, .. } => (
+            // Each email has to be added up once, so the joined
+            // rows are reduced to one per email and group first
+            Query::Grouped {
+                group_by,
+                metric: Metric::Size,
+                ..
+            } if !joins.is_empty() => (
                 format!(
-                    "SELECT count(path) as {}, {} FROM emails",
+                    "SELECT sum(size) as {}, {} FROM (SELECT DISTINCT emails.rowid, size, {} FROM {}",
                     AMOUNT_FIELD_NAME,
-                    group_by.as_str()
+                    group_by.as_str(),
+                    group_by.as_str(),
+                    table
                 ),
-                format!("GROUP BY {}", group_by.as_str()),
+                format!(") GROUP BY {} {}", group_by.as_str(), KNOWN_SIZE),
+            ),
+            Query::Grouped {
+                group_by, metric, ..
+            } => (
+                format!(
+                    "SELECT {} as {}, {} FROM {}",
+                    match metric {
+                        Metric::Count => amount,
+                        Metric::Size => "sum(size)",
+                    },
+                    AMOUNT_FIELD_NAME,
+                    group_by.as_str(),
+                    table
+                ),
+                match metric {
+                    Metric::Count => format!("GROUP BY {}", group_by.as_str()),
+                    Metric::Size => format!("GROUP BY {} {}", group_by.as_str(), KNOWN_SIZE),
+                },
             ),
             Query::Normal { fields, range, .. } => {
                 let fields: Vec<&str> = fields.iter().map(|e| e.into()).collect();
                 (
-                    format!("SELECT {} FROM emails", fields.join(", ")),
-                    format!("LIMIT {}, {}", range.start, range.end - range.start),
+                    format!("SELECT {} FROM {}", fields.join(", "), table),
+                    format!(
+                        "{}LIMIT {}, {}",
+                        per_email,
+                        range.start,
+                        // SQLite limits are signed, `0..usize::MAX` returns all rows
+                        (range.end - range.start).min(i64::MAX as usize)
+                    ),
                 )
             }
             Query::Other {
                 query: OtherQuery::All(field),
             } => (
-                format!(
-                    "SELECT {} FROM emails GROUP BY {}",
-                    field.as_str(),
-                    field.as_str()
-                ),
-                format!(""),
+                format!("SELECT {} FROM {}", field.as_str(), table),
+                format!("GROUP BY {}", field.as_str()),
             ),
         };
 
@@ -247,7 +455,120 @@ mod tests {
                 Filter::Is(ValueField::usize(&Field::Year, 2021)),
             ],
             group_by: Field::Month,
+            metric: Metric::Count,
         };
         dbg!(&query.to_sql());
     }
+
+    #[test]
+    fn test_recipients_join() {
+        let query = Query::Grouped {
+            filters: vec![Filter::Is(ValueField::bool(&Field::IsSend, true))],
+            group_by: Field::RecipientAddress,
+            metric: Metric::Count,
+        };
+        let (sql, _) = query.to_sql();
+        assert!(sql.contains("JOIN recipients"));
+        assert!(sql.contains("count(DISTINCT emails.rowid)"));
+
+        let query = Query::Normal {
+            fields: vec![Field::Subject],
+            filters: vec![Filter::Like(ValueField::string(
+                &Field::RecipientKind,
+                "cc",
+            ))],
+            range: 0..10,
+        };
+        let (sql, _) = query.to_sql();
+        assert!(sql.contains("JOIN recipients"));
+        assert!(sql.contains("GROUP BY emails.rowid"));
+
+        let query = Query::Grouped {
+            filters: Vec::new(),
+            group_by: Field::SenderDomain,
+            metric: Metric::Count,
+        };
+        assert!(!query.to_sql().0.contains("recipients"));
+    }
+
+    #[test]
+    fn test_attachments_join() {
+        let query = Query::Grouped {
+            filters: vec![Filter::Like(ValueField::string(
+                &Field::RecipientKind,
+                "to",
+            ))],
+            group_by: Field::AttachmentType,
+            metric: Metric::Count,
+        };
+        let (sql, _) = query.to_sql();
+        assert!(sql.contains(
+            "FROM emails JOIN attachments ON attachments.email_id = emails.rowid JOIN recipients"
+        ));
+        assert!(sql.contains("count(DISTINCT emails.rowid)"));
+
+        // The attachment count is in the emails table
+        let query = Query::Grouped {
+            filters: vec![Filter::Is(ValueField::bool(&Field::HasAttachment, true))],
+            group_by: Field::AttachmentCount,
+            metric: Metric::Count,
+        };
+        assert!(!query.to_sql().0.contains("JOIN"));
+    }
+
+    #[test]
+    fn test_size_metric() {
+        let query = Query::Grouped {
+            filters: Vec::new(),
+            group_by: Field::SenderDomain,
+            metric: Metric::Size,
+        };
+        assert!(query.to_sql().0.contains("SELECT sum(size) as amount"));
+
+        // Emails with several recipients are added up once
+        let query = Query::Grouped {
+            filters: Vec::new(),
+            group_by: Field::RecipientAddress,
+            metric: Metric::Size,
+        };
+        let (sql, _) = query.to_sql();
+        assert!(sql.contains("FROM (SELECT DISTINCT emails.rowid, size, recipient_address"));
+        assert!(sql.contains(") GROUP BY recipient_address HAVING sum(size) IS NOT NULL"));
+    }
+
+    #[test]
+    fn test_full_text() {
+        let query = Query::Normal {
+            fields: vec![Field::Subject],
+            filters: vec![Filter::FullText("say \"hi\" -now".to_string())],
+            range: 0..10,
+        };
+        let (sql, values) = query.to_sql();
+        assert!(sql.contains("FROM emails JOIN bodies ON bodies.rowid = emails.rowid"));
+        assert_eq!(
+            values,
+            vec![Value::String(r#""say" """hi""" "-now""#.to_string())]
+        );
+
+        // Without words, there is nothing to search for
+        let query = Query::Normal {
+            fields: vec![Field::Subject],
+            filters: vec![Filter::FullText(" ".to_string())],
+            range: 0..10,
+        };
+        assert!(!query.to_sql().0.contains("bodies"));
+    }
+
+    #[test]
+    fn test_all_rows() {
+        let query = Query::Normal {
+            fields: vec![Field::Subject],
+            filters: vec![],
+            range: 0..usize::MAX,
+        };
+        assert!(query
+            .to_sql()
+            .0
+            .ends_with(&format!("LIMIT 0, {}", i64::MAX)));
+    }
 }
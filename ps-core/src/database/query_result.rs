@@ -6,7 +6,8 @@ pub type QueryRow = HashMap<Field, ValueField>;
 #[derive(Debug)]
 pub enum QueryResult {
     Grouped {
-        /// How many items did we find?
+        /// How many items did we find? For [`super::query::Metric::Size`],
+        /// how many bytes do they have
         count: usize,
         /// All the itmes that we grouped by including their values.
         /// So that we can use each of them to limit the next query.
@@ -1,15 +1,35 @@
 use eyre::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread::JoinHandle;
 
 use crate::DatabaseLike;
 
 pub trait Importerlike {
+    /// Import into `database`. Once `cancel` is cancelled, the import stops
+    /// and keeps the emails that were written until then.
     fn import<Database: DatabaseLike + 'static>(
         self,
         database: Database,
+        cancel: CancelToken,
     ) -> Result<(MessageReceiver, JoinHandle<Result<()>>)>;
 }
 
+/// Used to cancel a running import from another thread.
+/// All clones of a token share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// The message that informs of the importers progress
 #[derive(Debug)]
 pub enum Message {
@@ -23,7 +43,10 @@ pub enum Message {
     ReadTotal(usize),
     /// Whenever an item out of the total is read, this message will be emitted
     ReadOne,
-    /// Similar to [`Message::ReadTotal`]
+    /// Similar to [`Message::ReadTotal`]. As the emails are written while
+    /// they're still being read, this is sent multiple times with a refined
+    /// estimate (e.g. once the files are counted) and the exact total once
+    /// reading is done.
     WriteTotal(usize),
     /// Similar to `ReadOne`
     WriteOne,
@@ -32,6 +55,9 @@ pub enum Message {
     FinishingUp,
     /// Finally, this indicates that we're done.
     Done,
+    /// Instead of `Done`, if the import was cancelled via [`CancelToken`].
+    /// The emails that were written until then are in the database.
+    Cancelled,
     /// An error happened during processing
     Error(eyre::Report),
     /// A special case for macOS, where a permission error means we have to grant this app
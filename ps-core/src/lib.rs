@@ -51,10 +51,15 @@ mod types;
 
 pub use database::database_like::{DatabaseLike, DatabaseQuery};
 pub use database::db_message::DBMessage;
-pub use database::query::{Field, Filter, OtherQuery, Query, ValueField, AMOUNT_FIELD_NAME};
+pub use database::query::{
+    Field, Filter, Metric, OtherQuery, Query, ValueField, AMOUNT_FIELD_NAME,
+};
 pub use database::query_result::{QueryResult, QueryRow};
-pub use importer::{Importerlike, Message, MessageReceiver, MessageSender};
-pub use types::{Config, EmailEntry, EmailMeta, FormatType};
+pub use importer::{CancelToken, Importerlike, Message, MessageReceiver, MessageSender};
+pub use types::{
+    Attachment, Config, DisplayTimezone, DuplicatePolicy, EmailEntry, EmailMeta, Fingerprint,
+    FormatType, Identity, ImapConfig, Recipient, RecipientKind, Source,
+};
 
 // Re-Export some dependencies so they don't
 // need to be listed again in other Cargo tomls
@@ -3,7 +3,7 @@ use eyre::{bail, eyre, Report, Result};
 use std::sync::{Arc, RwLock};
 use std::thread::JoinHandle;
 
-use crate::{DatabaseLike, Importerlike, Message};
+use crate::{CancelToken, DatabaseLike, Importerlike, Message};
 
 #[derive(Debug, Default)]
 struct Data {
@@ -13,6 +13,7 @@ struct Data {
     write: usize,
     finishing: bool,
     done: bool,
+    cancelled: bool,
     error: Option<Report>,
     #[cfg(target_os = "macos")]
     missing_permissions: bool,
@@ -20,6 +21,7 @@ struct Data {
 
 #[derive(Clone, Debug, Copy)]
 pub struct Progress {
+    /// `0` until the total can be estimated
     pub total: usize,
     pub count: usize,
 }
@@ -28,6 +30,8 @@ pub struct Progress {
 pub struct State {
     pub finishing: bool,
     pub done: bool,
+    /// The import was cancelled, `done` is not set
+    pub cancelled: bool,
     pub written: usize,
     #[cfg(target_os = "macos")]
     pub missing_permissions: bool,
@@ -62,8 +66,9 @@ impl Adapter {
         &self,
         database: Database,
         importer: Importer,
+        cancel: CancelToken,
     ) -> Result<JoinHandle<Result<()>>> {
-        let (receiver, handle) = importer.import(database)?;
+        let (receiver, handle) = importer.import(database, cancel)?;
         let lock = self.producer_lock.clone();
         let handle = std::thread::spawn(move || {
             'outer: loop {
@@ -84,12 +89,24 @@ impl Adapter {
                             }
                         }
                         Message::WriteTotal(n) => write_guard.total_write = n,
-                        Message::WriteOne => write_guard.write += 1,
+                        Message::WriteOne => {
+                            write_guard.write += 1;
+                            // An estimated total can fall behind the written count
+                            if write_guard.total_write > 0
+                                && write_guard.total_write <= write_guard.write
+                            {
+                                write_guard.total_write = write_guard.write + 1;
+                            }
+                        }
                         Message::FinishingUp => write_guard.finishing = true,
                         Message::Done => {
                             write_guard.done = true;
                             break 'outer;
                         }
+                        Message::Cancelled => {
+                            write_guard.cancelled = true;
+                            break 'outer;
+                        }
                         Message::Error(e) => {
                             write_guard.error = Some(e);
                         }
@@ -129,6 +146,7 @@ impl Adapter {
         Ok(State {
             finishing: item.finishing,
             done: item.done,
+            cancelled: item.cancelled,
             written: item.write,
             #[cfg(target_os = "macos")]
             missing_permissions: item.missing_permissions,
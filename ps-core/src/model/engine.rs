@@ -4,14 +4,16 @@
 //! See also:
 //! - [`crate::model::segmentations`]
 //! - [`crate::model::items`]
+//! - [`crate::model::latency`]
 use eyre::{bail, Result};
 
 use lru::LruCache;
 
-use crate::database::query::{Field, Filter, OtherQuery, Query, ValueField};
+use crate::database::query::{Field, Filter, Metric, OtherQuery, Query, ValueField};
 use crate::model::link::Response;
 use crate::types::Config;
 
+use super::latency::{self, Latency};
 use super::link::Link;
 use super::segmentations;
 use super::types::{LoadingState, Segment, Segmentation};
@@ -29,6 +31,8 @@ pub(super) enum Action {
     LoadItems,
     /// Load all tags
     AllTags,
+    /// Load the reply latencies for the current `Segmentation`
+    LoadLatencies,
 }
 
 /// Interact with the `Database`, operate on `Segmentations`, `Segments`, and `Items`.
@@ -41,11 +45,23 @@ pub struct Engine {
     pub(super) segmentations: Vec<Segmentation>,
     /// Additional filters. See [`segmentations::set_filters`]
     pub(super) filters: Vec<Filter>,
+    /// What the `Segments` are weighed by. See [`segmentations::set_metric`]
+    pub(super) metric: Metric,
     /// This is a very simple cache from ranges to rows.
     /// It doesn't account for overlapping ranges.
     /// There's a lot of room for improvement here.
     pub(super) item_cache: LruCache<usize, LoadingState>,
     pub(super) known_tags: Vec<String>,
+    /// The amount of emails with the same `Message-ID` as another email
+    pub(super) duplicates: usize,
+    /// The amount of emails that could only be parsed partially
+    pub(super) partial: usize,
+    /// The amount of emails that were imported by an older version
+    pub(super) outdated: usize,
+    /// The reply latencies of the current `Segmentation`, once they're loaded.
+    /// See [`latency::latencies`]
+    pub(super) latencies: Option<Vec<Latency>>,
+    pub(super) latencies_requested: bool,
 }
 
 impl Engine {
@@ -56,14 +72,25 @@ impl Engine {
         #[cfg(target_arch = "wasm32")]
         let link = super::link::run::<_, Database>(config, Database::new(&config.database_path)?)?;
 
+        let database = Database::new(&config.database_path)?;
+        let duplicates = database.total_duplicates()?;
+        let partial = database.total_partial()?;
+        let outdated = database.total_outdated()?;
+
         let engine = Engine {
             link,
             search_stack: Vec::new(),
             group_by_stack: vec![default_group_by_stack(0).unwrap()],
             segmentations: Vec::new(),
             filters: Vec::new(),
+            metric: Metric::default(),
             item_cache: LruCache::new(10000),
             known_tags: Vec::new(),
+            duplicates,
+            partial,
+            outdated,
+            latencies: None,
+            latencies_requested: false,
         };
         Ok(engine)
     }
@@ -100,6 +127,24 @@ impl Engine {
         &self.known_tags
     }
 
+    /// How many of the emails are duplicates of other emails
+    /// (e.g. the same mail imported from different sources)
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    /// How many of the emails could only be parsed partially
+    /// (e.g. because of a malformed date)
+    pub fn partial(&self) -> usize {
+        self.partial
+    }
+
+    /// How many of the emails were imported by an older version that didn't
+    /// record their attachments and text yet
+    pub fn outdated(&self) -> usize {
+        self.outdated
+    }
+
     /// Return the current stack of `Segmentations`
     pub fn segmentations(&self) -> &[Segmentation] {
         &self.segmentations
@@ -164,8 +209,38 @@ impl Engine {
             e.selected = None
         }
 
+        // The metric might have changed since the last segmentation was calculated
+        if self
+            .segmentations
+            .last()
+            .map_or(false, |e| e.metric != self.metric)
+        {
+            let request = segmentations::make_query(self)
+                .and_then(|query| self.link.request(&query, Action::RecalculateSegmentation));
+            if let Err(e) = request {
+                tracing::error!("Could not recalculate the segmentation: {:?}", &e);
+            }
+        }
+
         // Remove any rows that were cached for this segmentation
         self.item_cache.clear();
+        self.clear_latencies();
+    }
+
+    /// The latencies have to be loaded again for a new `Segmentation`
+    fn clear_latencies(&mut self) {
+        self.latencies = None;
+        self.latencies_requested = false;
+    }
+
+    /// The filters for the emails in the current `Segmentation`:
+    /// the selected `Segments` and the additional [`Engine::filters`]
+    pub(super) fn query_filters(&self) -> Vec<Filter> {
+        self.search_stack
+            .iter()
+            .map(|e| Filter::Like(e.clone()))
+            .chain(self.filters.iter().cloned())
+            .collect()
     }
 
     /// Call this continously to retrieve calculation results and apply them.
@@ -185,12 +260,17 @@ impl Engine {
                 self.segmentations.push(p);
                 // Remove any rows that were cached for this segmentation
                 self.item_cache.clear();
+                self.clear_latencies();
             }
             Response::Grouped(_, Action::RecalculateSegmentation, p) => {
                 let len = self.segmentations.len();
                 self.segmentations[len - 1] = p;
                 // Remove any rows that were cached for this segmentation
                 self.item_cache.clear();
+                self.clear_latencies();
+            }
+            Response::Normal(Query::Normal { .. }, Action::LoadLatencies, r) => {
+                self.latencies = Some(latency::aggregate(&r));
             }
             Response::Normal(Query::Normal { range, .. }, Action::LoadItems, r) => {
                 for (index, row) in range.zip(r) {
@@ -9,7 +9,7 @@ use eyre::Result;
 use super::types::LoadingState;
 use super::{engine::Action, Engine};
 use crate::database::{
-    query::{Field, Filter, Query},
+    query::{Field, Query},
     query_result::QueryRow,
 };
 
@@ -75,18 +75,16 @@ pub fn count(engine: &Engine) -> usize {
 
 /// Make the query for retrieving items
 fn make_query(engine: &Engine, range: Range<usize>) -> Query {
-    let mut filters = Vec::new();
-    for entry in &engine.search_stack {
-        filters.push(Filter::Like(entry.clone()));
-    }
     Query::Normal {
-        filters,
+        filters: engine.query_filters(),
         fields: vec![
             Field::SenderDomain,
             Field::SenderLocalPart,
             Field::Subject,
             Field::Path,
-            Field::Timestamp,
+            Field::Year,
+            Field::Month,
+            Field::Day,
         ],
         range,
     }
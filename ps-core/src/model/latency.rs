@@ -0,0 +1,159 @@
+//! Operations related to the reply latencies of the current `Segmentation`
+//!
+//! Each email that got a reply from the other side (from the user for
+//! received emails, from the correspondent for sent emails) has a
+//! [`Field::ReplyLatency`]. These operations aggregate them into the
+//! median time it takes to answer each `Field::Correspondent`, both ways.
+
+use eyre::Result;
+
+use super::{engine::Action, Engine};
+use crate::database::{
+    query::{Field, Filter, Query, ValueField},
+    query_result::QueryRow,
+};
+
+/// The reply latencies between the user and one correspondent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Latency {
+    /// The address of the correspondent
+    pub correspondent: String,
+    /// How many emails of the correspondent the user answered
+    pub my_replies: usize,
+    /// The median seconds it took the user to answer them
+    pub my_median: Option<i64>,
+    /// How many emails of the user the correspondent answered
+    pub their_replies: usize,
+    /// The median seconds it took the correspondent to answer them
+    pub their_median: Option<i64>,
+}
+
+/// Return the reply latencies of the current `Segmentation`, sorted
+/// by the amount of replies.
+///
+/// If they're not loaded yet, they will be queried asynchronously
+/// from the database and `None` is returned.
+///
+/// # Arguments
+///
+/// * `engine` - The engine to use for retrieving data
+pub fn latencies(engine: &mut Engine) -> Result<Option<&[Latency]>> {
+    if engine.latencies.is_none() && !engine.latencies_requested {
+        engine.latencies_requested = true;
+        let request = make_query(engine);
+        engine.link.request(&request, Action::LoadLatencies)?;
+    }
+    Ok(engine.latencies.as_deref())
+}
+
+/// The median of `values`, the lower one if there are two
+pub fn median(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    Some(values[(values.len() - 1) / 2])
+}
+
+/// Aggregate the rows of the query from [`make_query`]
+pub(super) fn aggregate(rows: &[QueryRow]) -> Vec<Latency> {
+    use std::collections::HashMap;
+    // The latencies of the user and the correspondent, for each correspondent
+    let mut values: HashMap<String, (Vec<i64>, Vec<i64>)> = HashMap::new();
+    for row in rows {
+        let (correspondent, is_send, latency) = match (
+            row.get(&Field::Correspondent),
+            row.get(&Field::IsSend).and_then(|e| e.value().as_bool()),
+            row.get(&Field::ReplyLatency)
+                .and_then(|e| e.value().as_i64()),
+        ) {
+            (Some(a), Some(b), Some(c)) => (a.to_string(), b, c),
+            _ => continue,
+        };
+        let entry = values.entry(correspondent).or_default();
+        // A received email was answered by the user, a sent one by the correspondent
+        match is_send {
+            false => entry.0.push(latency),
+            true => entry.1.push(latency),
+        }
+    }
+    let mut latencies: Vec<Latency> = values
+        .into_iter()
+        .map(|(correspondent, (mut mine, mut theirs))| Latency {
+            correspondent,
+            my_replies: mine.len(),
+            my_median: median(&mut mine),
+            their_replies: theirs.len(),
+            their_median: median(&mut theirs),
+        })
+        .collect();
+    latencies.sort_by(|a, b| {
+        (b.my_replies + b.their_replies)
+            .cmp(&(a.my_replies + a.their_replies))
+            .then_with(|| a.correspondent.cmp(&b.correspondent))
+    });
+    latencies
+}
+
+/// Make the query for retrieving the latencies of all emails in
+/// the current `Segmentation` that got a reply
+fn make_query(engine: &Engine) -> Query {
+    let mut filters = engine.query_filters();
+    filters.push(Filter::Is(ValueField::bool(&Field::IsReplied, true)));
+    Query::Normal {
+        filters,
+        fields: vec![Field::Correspondent, Field::IsSend, Field::ReplyLatency],
+        range: 0..usize::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn row(correspondent: &str, is_send: bool, latency: i64) -> QueryRow {
+        let mut row = QueryRow::new();
+        row.insert(
+            Field::Correspondent,
+            ValueField::string(&Field::Correspondent, correspondent),
+        );
+        row.insert(Field::IsSend, ValueField::bool(&Field::IsSend, is_send));
+        row.insert(
+            Field::ReplyLatency,
+            ValueField::new(&Field::ReplyLatency, Value::Number(latency.into())),
+        );
+        row
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let rows = vec![
+            row("jane@example.org", false, 60),
+            row("jane@example.org", false, 3600),
+            row("jane@example.org", false, 120),
+            row("jane@example.org", true, 86400),
+            row("bob@example.net", true, 30),
+        ];
+        assert_eq!(
+            aggregate(&rows),
+            vec![
+                Latency {
+                    correspondent: "jane@example.org".to_owned(),
+                    my_replies: 3,
+                    my_median: Some(120),
+                    their_replies: 1,
+                    their_median: Some(86400),
+                },
+                Latency {
+                    correspondent: "bob@example.net".to_owned(),
+                    my_replies: 0,
+                    my_median: None,
+                    their_replies: 1,
+                    their_median: Some(30),
+                },
+            ]
+        );
+        assert_eq!(median(&mut [4, 1, 3, 2]), Some(2));
+    }
+}
@@ -18,7 +18,7 @@ use serde_json::Value;
 
 use crate::database::{
     database_like::{DatabaseLike, DatabaseQuery},
-    query::Query,
+    query::{Metric, Query},
     query_result::{QueryResult, QueryRow},
 };
 use crate::types::Config;
@@ -164,8 +164,8 @@ fn process_query<Context: Send + Sync + 'static>(
     context: Context,
 ) -> Result<Response<Context>> {
     let response = match query {
-        Query::Grouped { .. } => {
-            let segmentations = calculate_segmentations(&result)?;
+        Query::Grouped { metric, .. } => {
+            let segmentations = calculate_segmentations(&result, metric)?;
             Response::Grouped(query, context, segmentations)
         }
         Query::Normal { .. } => {
@@ -203,14 +203,14 @@ fn process_query<Context: Send + Sync + 'static>(
     Ok(response)
 }
 
-fn calculate_segmentations(result: &[QueryResult]) -> Result<Segmentation> {
+fn calculate_segmentations(result: &[QueryResult], metric: Metric) -> Result<Segmentation> {
     let mut segmentations = Vec::new();
     for r in result.iter() {
         let segmentation = r.try_into()?;
         segmentations.push(segmentation);
     }
 
-    Ok(Segmentation::new(segmentations))
+    Ok(Segmentation::new(segmentations, metric))
 }
 
 fn calculate_rows(result: &[QueryResult]) -> Result<Vec<QueryRow>> {
@@ -1,5 +1,6 @@
 mod engine;
 pub mod items;
+pub mod latency;
 mod link;
 pub mod segmentations;
 mod types;
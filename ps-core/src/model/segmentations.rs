@@ -8,6 +8,11 @@
 //! - [`crate::model::segmentations::aggregated_by`]
 //! - [`crate::model::segmentations::set_aggregation`]
 //!
+//! The `Segments` can be weighed by the amount or the size of their emails.
+//!
+//! - [`crate::model::segmentations::metric`]
+//! - [`crate::model::segmentations::set_metric`]
+//!
 //! A `Segmentation` can be changed to only return a `Range` of segments.
 //!
 //! - [`crate::model::segmentations::segments_range`]
@@ -25,7 +30,7 @@ use super::{
     types::{self, Aggregation, Segment},
     Engine,
 };
-use crate::database::query::{Field, Filter, Query};
+use crate::database::query::{Field, Filter, Metric, Query};
 use std::ops::RangeInclusive;
 
 /// Filter the `Range` of segments of the current `Segmentation`
@@ -84,6 +89,21 @@ pub fn set_filters(engine: &mut Engine, filters: &[Filter]) -> Result<()> {
         .request(&make_query(engine)?, Action::RecalculateSegmentation)
 }
 
+/// What the `Segments` of the `Segmentations` are weighed by
+pub fn metric(engine: &Engine) -> Metric {
+    engine.metric
+}
+
+/// Weigh the `Segments` by a different `Metric`, e.g. by the total size
+/// of their emails to see what uses the most storage.
+/// Setting this value will recalculate the current segmentation.
+pub fn set_metric(engine: &mut Engine, metric: Metric) -> Result<()> {
+    engine.metric = metric;
+    engine
+        .link
+        .request(&make_query(engine)?, Action::RecalculateSegmentation)
+}
+
 /// The fields available for the given aggregation
 ///
 /// As the user `pushes` Segmentations and dives into the data,
@@ -192,13 +212,7 @@ pub fn can_aggregate_more(engine: &Engine) -> bool {
 
 /// Perform the query that returns an aggregated `Segmentation`
 pub(super) fn make_query(engine: &Engine) -> Result<Query> {
-    let mut filters = Vec::new();
-    for entry in &engine.search_stack {
-        filters.push(Filter::Like(entry.clone()));
-    }
-    for entry in &engine.filters {
-        filters.push(entry.clone());
-    }
+    let filters = engine.query_filters();
     let last = engine
         .group_by_stack
         .last()
@@ -206,5 +220,6 @@ pub(super) fn make_query(engine: &Engine) -> Result<Query> {
     Ok(Query::Grouped {
         filters,
         group_by: *last,
+        metric: engine.metric,
     })
 }
@@ -9,7 +9,9 @@ use crate::database::{query::ValueField, query_result::QueryResult};
 #[derive(Debug, Clone)]
 pub struct Segment {
     pub field: ValueField,
-    pub count: usize,
+    /// The amount of emails or, depending on the [`crate::Metric`] of the
+    /// `Segmentation`, their total size. The area of the `rect` is based on it.
+    pub weight: usize,
     /// A TreeMap Rect
     pub rect: treemap::Rect,
 }
@@ -26,7 +28,7 @@ impl Segment {
 
 impl Mappable for Segment {
     fn size(&self) -> f64 {
-        self.count as f64
+        self.weight as f64
     }
 
     fn bounds(&self) -> &treemap::Rect {
@@ -50,7 +52,7 @@ impl<'a> TryFrom<&'a QueryResult> for Segment {
 
         Ok(Segment {
             field: field.clone(),
-            count: *count,
+            weight: *count,
             rect: treemap::Rect::new(),
         })
     }
@@ -2,6 +2,7 @@ use treemap::{self, TreemapLayout};
 
 use super::segment::Segment;
 use super::Rect;
+use crate::database::query::Metric;
 
 /// A small NewType so that we can keep all the `TreeMap` code in here and don't
 /// have to do the layout calculation in a widget.
@@ -10,14 +11,17 @@ pub struct Segmentation {
     items: Vec<Segment>,
     pub selected: Option<Segment>,
     pub range: Option<std::ops::RangeInclusive<usize>>,
+    /// What the `Segments` are weighed by
+    pub metric: Metric,
 }
 
 impl Segmentation {
-    pub fn new(items: Vec<Segment>) -> Self {
+    pub fn new(items: Vec<Segment>, metric: Metric) -> Self {
         Self {
             items,
             selected: None,
             range: None,
+            metric,
         }
     }
 
@@ -33,10 +37,10 @@ impl Segmentation {
         layout.layout_items(self.items(), bounds);
     }
 
-    /// The total amount of items in all the `Segments`.
-    /// E.g. the sum of the count of the `Segments`
-    pub fn element_count(&self) -> usize {
-        self.items.iter().map(|e| e.count).sum::<usize>()
+    /// The total weight of all the `Segments`. E.g. the
+    /// amount of emails for [`Metric::Count`]
+    pub fn total_weight(&self) -> usize {
+        self.items.iter().map(|e| e.weight).sum::<usize>()
     }
 
     /// The items in this `Segmentation`, with range applied
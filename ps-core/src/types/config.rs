@@ -1,10 +1,12 @@
+use chrono::{DateTime, FixedOffset, Local};
 use eyre::{eyre, Result};
 use serde_json::Value;
 use strum::{self, IntoEnumIterator};
 use strum_macros::{EnumIter, IntoStaticStr};
 
-use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
+use super::identity::Identity;
+
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
@@ -16,6 +18,11 @@ pub enum FormatType {
     AppleMail,
     GmailVault,
     Mbox,
+    Maildir,
+    Thunderbird,
+    Outlook,
+    Imap,
+    Notmuch,
 }
 
 impl FormatType {
@@ -28,13 +35,28 @@ impl FormatType {
             FormatType::AppleMail => "Apple Mail",
             FormatType::GmailVault => "Gmail Vault Download",
             FormatType::Mbox => "Mbox",
+            FormatType::Maildir => "Maildir",
+            FormatType::Thunderbird => "Thunderbird",
+            FormatType::Outlook => "Outlook (PST / OST)",
+            FormatType::Imap => "IMAP Server",
+            FormatType::Notmuch => "Notmuch",
         }
     }
+
+    /// Can the emails be read out of a `.zip` / `.tar.gz` / etc. archive
+    /// instead of a folder?
+    pub fn supports_archives(&self) -> bool {
+        matches!(
+            self,
+            FormatType::Mbox | FormatType::Maildir | FormatType::GmailVault
+        )
+    }
 }
 
 impl Default for FormatType {
     /// We return a different default, based on the platform we're on
-    /// FIXME: We don't have support for Outlook yet, so on windows we go with Mbox as well
+    /// FIXME: Outlook support requires `readpst`, which is rarely installed on windows,
+    /// so on windows we go with Mbox as well
     fn default() -> Self {
         #[cfg(target_os = "macos")]
         return FormatType::AppleMail;
@@ -56,6 +78,11 @@ impl From<&str> for FormatType {
             "apple" => FormatType::AppleMail,
             "gmailvault" => FormatType::GmailVault,
             "mbox" => FormatType::Mbox,
+            "maildir" => FormatType::Maildir,
+            "thunderbird" => FormatType::Thunderbird,
+            "outlook" => FormatType::Outlook,
+            "imap" => FormatType::Imap,
+            "notmuch" => FormatType::Notmuch,
             _ => panic!("Unknown format: {}", &format),
         }
     }
@@ -67,33 +94,158 @@ impl From<FormatType> for String {
             FormatType::AppleMail => "apple".to_owned(),
             FormatType::GmailVault => "gmailvault".to_owned(),
             FormatType::Mbox => "mbox".to_owned(),
+            FormatType::Maildir => "maildir".to_owned(),
+            FormatType::Thunderbird => "thunderbird".to_owned(),
+            FormatType::Outlook => "outlook".to_owned(),
+            FormatType::Imap => "imap".to_owned(),
+            FormatType::Notmuch => "notmuch".to_owned(),
         }
     }
 }
 
+/// The connection information for the `Imap` format
 #[derive(Debug, Clone)]
-pub struct Config {
-    /// The path to where the database should be stored
-    pub database_path: PathBuf,
+pub struct ImapConfig {
+    pub host: String,
+    pub port: u16,
+    /// Connect via TLS (usually port 993)
+    pub tls: bool,
+    pub user: String,
+    /// The password or app token. This is never stored in the database.
+    pub password: String,
+}
+
+impl ImapConfig {
+    /// The default port for IMAP over TLS
+    pub const TLS_PORT: u16 = 993;
+}
+
+impl Default for ImapConfig {
+    fn default() -> Self {
+        ImapConfig {
+            host: String::new(),
+            port: ImapConfig::TLS_PORT,
+            tls: true,
+            user: String::new(),
+            password: String::new(),
+        }
+    }
+}
+
+/// What to do with emails that have the same `Message-ID` as an
+/// email that was already imported. This happens when the same mailbox
+/// is imported from several sources, or with Gmail labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Import every copy as a separate email
+    Keep,
+    /// Import only the first copy and add the tags of the other copies to it
+    Merge,
+}
+
+impl Default for DuplicatePolicy {
+    fn default() -> Self {
+        DuplicatePolicy::Merge
+    }
+}
+
+impl DuplicatePolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DuplicatePolicy::Keep => "keep",
+            DuplicatePolicy::Merge => "merge",
+        }
+    }
+}
+
+/// The time zone in which the year, month, day, hour and weekday
+/// of the emails are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTimezone {
+    /// The time zone of the computer that imports the emails
+    Local,
+    /// The time zone of each sender. Databases from before
+    /// the time zones were kept were imported like this.
+    Sender,
+    /// A fixed offset from UTC in seconds (`0` is UTC)
+    Offset(i32),
+}
+
+impl Default for DisplayTimezone {
+    fn default() -> Self {
+        DisplayTimezone::Local
+    }
+}
+
+impl DisplayTimezone {
+    /// `datetime` in this time zone
+    pub fn convert(&self, datetime: &DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            DisplayTimezone::Local => {
+                let local = datetime.with_timezone(&Local);
+                local.with_timezone(local.offset())
+            }
+            DisplayTimezone::Sender => *datetime,
+            DisplayTimezone::Offset(seconds) => {
+                let offset =
+                    FixedOffset::east_opt(*seconds).unwrap_or_else(|| FixedOffset::east(0));
+                datetime.with_timezone(&offset)
+            }
+        }
+    }
+
+    fn to_field(self) -> Value {
+        match self {
+            DisplayTimezone::Local => "local".into(),
+            DisplayTimezone::Sender => "sender".into(),
+            DisplayTimezone::Offset(seconds) => seconds.into(),
+        }
+    }
+
+    fn from_field(field: &Value) -> Option<Self> {
+        match field {
+            Value::String(n) if n == "local" => Some(DisplayTimezone::Local),
+            Value::String(n) if n == "sender" => Some(DisplayTimezone::Sender),
+            Value::Number(n) => n.as_i64().map(|e| DisplayTimezone::Offset(e as i32)),
+            _ => None,
+        }
+    }
+}
+
+/// A folder, archive or server that emails are imported from
+#[derive(Debug, Clone)]
+pub struct Source {
     /// The path where the emails are
     pub emails_folder_path: PathBuf,
-    /// The addresses used to send emails
-    pub sender_emails: HashSet<String>,
-    /// The importer format we're using
+    /// The importer format of the emails
     pub format: FormatType,
-    /// Did the user intend to keep the database
-    /// (e.g. is the database path temporary?)
-    pub persistent: bool,
+    /// The server to import from, if the format is `Imap`
+    pub imap: Option<ImapConfig>,
 }
 
-impl Config {
-    /// Construct a config from a hashmap of field values.
-    /// For missing fields, take a reasonable default value,
-    /// in order to be somewhat backwards compatible.
-    pub fn from_fields<P: AsRef<Path>>(path: P, fields: HashMap<String, Value>) -> Result<Config> {
-        // The following fields are of version 1.0, so they should aways exist
-        let emails_folder_path_str = fields
-            .get("emails_folder_path")
+impl Source {
+    pub fn new<A: AsRef<Path>>(mails: A, format: FormatType) -> Self {
+        Source {
+            emails_folder_path: mails.as_ref().to_path_buf(),
+            format,
+            imap: None,
+        }
+    }
+
+    /// The name that is stored with each email from this source,
+    /// so that the emails can be grouped and filtered by source
+    pub fn name(&self) -> String {
+        self.emails_folder_path.display().to_string()
+    }
+
+    /// Read a source out of the values in `field`. The keys are the same
+    /// as the ones that databases from before multiple sources used for
+    /// their only source.
+    fn from_fields<'a, F>(field: F) -> Result<Source>
+    where
+        F: Fn(&str) -> Option<&'a Value>,
+    {
+        let emails_folder_path_str = field("emails_folder_path")
             .ok_or_else(|| eyre!("Missing config field emails_folder_path"))?
             .as_str()
             .ok_or_else(|| eyre!("Invalid field type for emails_folder_path"))?;
@@ -104,40 +256,148 @@ impl Config {
                 e
             )
         })?;
-        #[allow(clippy::needless_collect)]
-        let sender_emails: Vec<String> = fields
-            .get("sender_emails")
-            .map(|v| v.as_str().map(|e| e.to_string()))
-            .flatten()
-            .ok_or_else(|| eyre!("Missing config field sender_emails"))?
-            .split(',')
-            .map(|e| e.trim().to_owned())
-            .collect();
-        let format = fields
-            .get("format")
-            .map(|e| e.as_str())
-            .flatten()
+        let format = field("format")
+            .and_then(|e| e.as_str())
             .map(FormatType::from)
             .ok_or_else(|| eyre!("Missing config field format_type"))?;
+        // The IMAP fields are optional. The password is never stored.
+        let imap = field("imap_host")
+            .and_then(|e| e.as_str())
+            .map(|host| ImapConfig {
+                host: host.to_owned(),
+                port: field("imap_port")
+                    .and_then(|e| e.as_u64())
+                    .map(|e| e as u16)
+                    .unwrap_or(ImapConfig::TLS_PORT),
+                tls: field("imap_tls").and_then(|e| e.as_bool()).unwrap_or(true),
+                user: field("imap_user")
+                    .and_then(|e| e.as_str())
+                    .unwrap_or_default()
+                    .to_owned(),
+                password: String::new(),
+            });
+        Ok(Source {
+            emails_folder_path,
+            format,
+            imap,
+        })
+    }
+
+    fn to_fields(&self) -> Option<serde_json::Map<String, Value>> {
+        let mut new = serde_json::Map::new();
+        new.insert(
+            "emails_folder_path".to_owned(),
+            self.emails_folder_path.to_str()?.into(),
+        );
+        let format: String = self.format.into();
+        new.insert("format".to_owned(), format.into());
+        if let Some(imap) = &self.imap {
+            new.insert("imap_host".to_owned(), imap.host.clone().into());
+            new.insert("imap_port".to_owned(), imap.port.into());
+            new.insert("imap_tls".to_owned(), imap.tls.into());
+            new.insert("imap_user".to_owned(), imap.user.clone().into());
+        }
+        Some(new)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The path to where the database should be stored
+    pub database_path: PathBuf,
+    /// Where the emails are imported from. All of them
+    /// are imported into the same database.
+    pub sources: Vec<Source>,
+    /// The identities (addresses) of the user, used to find the emails
+    /// that were sent by them
+    pub identities: Vec<Identity>,
+    /// Did the user intend to keep the database
+    /// (e.g. is the database path temporary?)
+    pub persistent: bool,
+    /// How emails with the same `Message-ID` are imported
+    pub duplicates: DuplicatePolicy,
+    /// The time zone for the year, month, day, hour and weekday of the emails
+    pub timezone: DisplayTimezone,
+}
+
+impl Config {
+    /// Construct a config from a hashmap of field values.
+    /// For missing fields, take a reasonable default value,
+    /// in order to be somewhat backwards compatible.
+    pub fn from_fields<P: AsRef<Path>>(path: P, fields: HashMap<String, Value>) -> Result<Config> {
+        // Databases from before multiple sources only have the fields of one source
+        let sources = match fields.get("sources").and_then(|e| e.as_array()) {
+            Some(sources) => sources
+                .iter()
+                .map(|source| {
+                    let source = source
+                        .as_object()
+                        .ok_or_else(|| eyre!("Invalid field type for sources"))?;
+                    Source::from_fields(|key| source.get(key))
+                })
+                .collect::<Result<Vec<Source>>>()?,
+            None => vec![Source::from_fields(|key| fields.get(key))?],
+        };
+        // Databases from before identities only have a list of sender addresses
+        let identities = match fields.get("identities").and_then(|e| e.as_array()) {
+            Some(identities) => identities
+                .iter()
+                .map(identity_from_field)
+                .collect::<Result<Vec<Identity>>>()?,
+            None => {
+                let sender_emails: Vec<String> = fields
+                    .get("sender_emails")
+                    .map(|v| v.as_str().map(|e| e.to_string()))
+                    .flatten()
+                    .ok_or_else(|| eyre!("Missing config field sender_emails"))?
+                    .split(',')
+                    .map(|e| e.to_owned())
+                    .collect();
+                Identity::from_addresses(sender_emails)
+            }
+        };
         let persistent = fields
             .get("persistent")
             .map(|e| e.as_bool())
             .flatten()
             .ok_or_else(|| eyre!("Missing config field persistent"))?;
+        // Databases from before the duplicate detection kept all duplicates
+        let duplicates = match fields.get("duplicates").and_then(|e| e.as_str()) {
+            Some("merge") => DuplicatePolicy::Merge,
+            _ => DuplicatePolicy::Keep,
+        };
+        // Databases from before the time zones were kept used the time of the sender
+        let timezone = fields
+            .get("timezone")
+            .and_then(DisplayTimezone::from_field)
+            .unwrap_or(DisplayTimezone::Sender);
         Ok(Config {
             database_path: path.as_ref().to_path_buf(),
-            emails_folder_path,
-            sender_emails: HashSet::from_iter(sender_emails.into_iter()),
-            format,
+            sources,
+            identities,
             persistent,
+            duplicates,
+            timezone,
         })
     }
 
+    /// A config for importing the emails at `mails` in the `format`.
+    /// Each of the `sender_emails` becomes an [`Identity`].
     pub fn new<A: AsRef<Path>>(
         db: Option<A>,
         mails: A,
         sender_emails: Vec<String>,
         format: FormatType,
+    ) -> eyre::Result<Self> {
+        let source = Source::new(mails, format);
+        Config::with_sources(db, vec![source], Identity::from_addresses(sender_emails))
+    }
+
+    /// A config for importing the emails of all `sources` into one database
+    pub fn with_sources<A: AsRef<Path>>(
+        db: Option<A>,
+        sources: Vec<Source>,
+        identities: Vec<Identity>,
     ) -> eyre::Result<Self> {
         // If we don't have a database path, we use a temporary folder.
         let persistent = db.is_some();
@@ -162,10 +422,11 @@ impl Config {
         };
         Ok(Config {
             database_path,
-            emails_folder_path: mails.as_ref().to_path_buf(),
-            sender_emails: HashSet::from_iter(sender_emails.into_iter()),
-            format,
+            sources,
+            identities,
             persistent,
+            duplicates: DuplicatePolicy::default(),
+            timezone: DisplayTimezone::default(),
         })
     }
 
@@ -175,27 +436,61 @@ impl Config {
             "database_path".to_owned(),
             self.database_path.to_str()?.into(),
         );
-        new.insert(
-            "emails_folder_path".to_owned(),
-            self.emails_folder_path.to_str()?.into(),
-        );
+        let sources = self
+            .sources
+            .iter()
+            .map(|e| e.to_fields().map(Value::Object))
+            .collect::<Option<Vec<Value>>>()?;
+        // The first source is also stored the way it was before multiple sources,
+        // so that older versions can still open the database
+        if let Some(first) = sources.first().and_then(|e| e.as_object()) {
+            new.extend(first.clone());
+        }
+        new.insert("sources".to_owned(), Value::Array(sources));
         new.insert("persistent".to_owned(), self.persistent.into());
+        let identities = self
+            .identities
+            .iter()
+            .map(|identity| {
+                let mut fields = serde_json::Map::new();
+                fields.insert("name".to_owned(), identity.name.clone().into());
+                fields.insert("patterns".to_owned(), identity.patterns.clone().into());
+                Value::Object(fields)
+            })
+            .collect();
+        new.insert("identities".to_owned(), Value::Array(identities));
+        // Older versions only know a list of sender addresses
         new.insert(
             "sender_emails".to_owned(),
-            self.sender_emails
+            self.identities
                 .iter()
-                .cloned()
+                .flat_map(|e| e.patterns.iter().cloned())
                 .collect::<Vec<String>>()
                 .join(",")
                 .into(),
         );
-        let format: String = self.format.into();
-        new.insert("format".to_owned(), format.into());
+        new.insert("duplicates".to_owned(), self.duplicates.as_str().into());
+        new.insert("timezone".to_owned(), self.timezone.to_field());
 
         Some(new)
     }
 }
 
+fn identity_from_field(field: &Value) -> Result<Identity> {
+    let name = field
+        .get("name")
+        .and_then(|e| e.as_str())
+        .ok_or_else(|| eyre!("Missing identity field name"))?;
+    let patterns = field
+        .get("patterns")
+        .and_then(|e| e.as_array())
+        .ok_or_else(|| eyre!("Missing identity field patterns"))?
+        .iter()
+        .filter_map(|e| e.as_str().map(|e| e.to_owned()))
+        .collect();
+    Ok(Identity::new(name, patterns))
+}
+
 fn random_filename() -> String {
     use rand::Rng;
     let number: u32 = rand::thread_rng().gen();
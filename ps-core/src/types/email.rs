@@ -9,6 +9,9 @@ pub type Tag = String;
 pub struct EmailMeta {
     pub tags: Vec<Tag>,
     pub is_seen: bool,
+    /// The id of the conversation, if the system has one
+    /// (e.g. Gmail's `X-GM-THRID`)
+    pub thread_id: Option<String>,
 }
 
 const TAG_SEP: &str = ":|:";
@@ -20,7 +23,11 @@ impl EmailMeta {
 
     pub fn from(is_seen: bool, tag_string: &str) -> Self {
         let tags = EmailMeta::tags_from_string(tag_string);
-        EmailMeta { tags, is_seen }
+        EmailMeta {
+            tags,
+            is_seen,
+            thread_id: None,
+        }
     }
 
     pub fn tags_string(&self) -> String {
@@ -28,23 +35,139 @@ impl EmailMeta {
     }
 }
 
+/// The header that the address of a [`Recipient`] is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientKind {
+    To,
+    Cc,
+    Bcc,
+}
+
+impl RecipientKind {
+    pub fn all() -> [RecipientKind; 3] {
+        [RecipientKind::To, RecipientKind::Cc, RecipientKind::Bcc]
+    }
+
+    /// The name of the header
+    pub fn header(&self) -> &'static str {
+        match self {
+            RecipientKind::To => "To",
+            RecipientKind::Cc => "Cc",
+            RecipientKind::Bcc => "Bcc",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecipientKind::To => "to",
+            RecipientKind::Cc => "cc",
+            RecipientKind::Bcc => "bcc",
+        }
+    }
+}
+
+/// One of the addresses an email was sent to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub name: String,
+    pub address: String,
+    pub kind: RecipientKind,
+}
+
+/// A file attached to an email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The filename, empty if the attachment has none
+    pub filename: String,
+    /// The lowercased `Content-Type`, e.g. `application/pdf`
+    pub mime_type: String,
+    /// The size in bytes
+    pub size: usize,
+}
+
+/// A stable 64 bit FNV-1a hash that identifies an email across imports,
+/// see [`EmailEntry::fingerprint`]
+#[derive(Debug, Clone, Copy)]
+pub struct Fingerprint(u64);
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Fingerprint(0xcbf29ce484222325)
+    }
+}
+
+impl Fingerprint {
+    /// The fingerprint of the emails with the `Message-ID` `message_id`
+    pub fn message_id(message_id: &str) -> i64 {
+        let mut fingerprint = Fingerprint::default();
+        fingerprint.write(b"message-id\0");
+        fingerprint.write(message_id.as_bytes());
+        fingerprint.finish()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// SQLite only has signed integers
+    pub fn finish(self) -> i64 {
+        self.0 as i64
+    }
+}
+
 /// Representation of an email
 #[derive(Debug)]
 pub struct EmailEntry {
     pub path: PathBuf,
+    /// The name of the [`crate::Source`] this email was imported from
+    pub source: String,
+    /// Used to find the emails that are already in the database when
+    /// updating it. The [`Fingerprint::message_id`] if the email has a
+    /// `Message-ID`, otherwise a hash of its path and contents.
+    pub fingerprint: i64,
+    /// The `Message-ID` header without the angle brackets
+    pub message_id: Option<String>,
+    /// The ids in `References` followed by `In-Reply-To`, so that
+    /// the email this is a reply to is the last one
+    pub references: Vec<String>,
     pub sender_domain: String,
     pub sender_local_part: String,
     pub sender_name: String,
-    pub datetime: chrono::DateTime<Utc>,
+    /// When the email was sent, in the time zone of the sender
+    pub datetime: chrono::DateTime<FixedOffset>,
+    /// `datetime` in the [`crate::DisplayTimezone`] of the config.
+    /// The year, month, day, hour and weekday are taken from this.
+    pub display_datetime: chrono::DateTime<FixedOffset>,
     pub subject: String,
+    /// The size of the raw (uncompressed) email in bytes
+    pub size: usize,
     /// The amount of `to:` adresses
     pub to_count: usize,
     /// When this email was send to a group, the group name
     pub to_group: Option<String>,
     /// The first address and name in `To`, if any
     pub to_first: Option<(String, String)>,
+    /// All the addresses in `To`, `Cc` and `Bcc`
+    pub recipients: Vec<Recipient>,
     pub is_reply: bool,
+    /// The `List-Id` (or a substitute) of the mailing list this was sent to
+    pub mailing_list: Option<String>,
+    /// Was this email sent to many recipients at once
+    /// (mailing lists, newsletters, notifications)?
+    pub is_bulk: bool,
+    /// The attachments anywhere in the MIME tree
+    pub attachments: Vec<Attachment>,
+    /// The readable text of the body, for the full-text search
+    pub body: String,
     /// Was this email send from the account we're importing?
     pub is_send: bool,
+    /// The name of the [`crate::Identity`] that sent this email or,
+    /// if it wasn't sent by the user, that received it
+    pub identity: Option<String>,
+    /// If the email could only be parsed partially, the headers
+    /// that were malformed. `None` if it was parsed completely.
+    pub failed_headers: Option<Vec<String>>,
     pub meta: Option<EmailMeta>,
 }
@@ -0,0 +1,161 @@
+/// One of the identities of the user, e.g. `Work` or `Private`, with the
+/// addresses that belong to it. An address can contain `*` wildcards
+/// (e.g. `*@mycompany.com` or `me+*@gmail.com`), but no regular expressions.
+/// The case is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub patterns: Vec<String>,
+    /// The `patterns` in lowercase, as they're matched against every email
+    lowercased: Vec<Vec<char>>,
+}
+
+/// Separates the identities in the text the user entered
+const IDENTITY_SEP: char = ';';
+/// Separates the name of an identity from its addresses
+const NAME_SEP: char = ':';
+/// Separates the addresses of an identity
+const PATTERN_SEP: char = ',';
+
+impl Identity {
+    pub fn new<S: Into<String>>(name: S, patterns: Vec<String>) -> Self {
+        let lowercased = patterns.iter().map(|e| lowercase(e)).collect();
+        Identity {
+            name: name.into(),
+            patterns,
+            lowercased,
+        }
+    }
+
+    /// One identity for each of the `addresses`, named like the address.
+    /// This is also how the plain sender addresses of older versions are read.
+    pub fn from_addresses(addresses: Vec<String>) -> Vec<Identity> {
+        addresses
+            .into_iter()
+            .map(|e| e.trim().to_owned())
+            .filter(|e| !e.is_empty())
+            .map(|e| Identity::new(e.clone(), vec![e]))
+            .collect()
+    }
+
+    /// Parse the identities the user entered, e.g.
+    /// `Work: *@mycompany.com; Private: me@gmail.com, me+*@gmail.com`.
+    /// Addresses without a name are each their own identity, so that
+    /// a plain list of addresses (`a@b.com, c@d.com`) still works.
+    pub fn parse_list(text: &str) -> Vec<Identity> {
+        let mut identities = Vec::new();
+        for part in text.split(IDENTITY_SEP) {
+            let (name, patterns) = match part.split_once(NAME_SEP) {
+                Some((name, patterns)) => (Some(name.trim()), patterns),
+                None => (None, part),
+            };
+            let patterns: Vec<String> = patterns
+                .split(PATTERN_SEP)
+                .map(|e| e.trim().to_owned())
+                .filter(|e| !e.is_empty())
+                .collect();
+            match name {
+                Some(name) if !name.is_empty() && !patterns.is_empty() => {
+                    identities.push(Identity::new(name, patterns))
+                }
+                _ => identities.extend(Identity::from_addresses(patterns)),
+            }
+        }
+        identities
+    }
+
+    /// The inverse of [`Identity::parse_list`]
+    pub fn format_list(identities: &[Identity]) -> String {
+        identities
+            .iter()
+            .map(|identity| {
+                let patterns = identity.patterns.join(", ");
+                if identity.patterns.len() == 1 && identity.patterns[0] == identity.name {
+                    patterns
+                } else {
+                    format!("{}: {}", identity.name, patterns)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+
+    /// Does `address` (`local@domain`) belong to this identity?
+    pub fn matches(&self, address: &str) -> bool {
+        self.matches_lowercased(&lowercase(address))
+    }
+
+    /// The first of the `identities` that `address` belongs to
+    pub fn find<'a>(identities: &'a [Identity], address: &str) -> Option<&'a Identity> {
+        let address = lowercase(address);
+        identities.iter().find(|e| e.matches_lowercased(&address))
+    }
+
+    fn matches_lowercased(&self, address: &[char]) -> bool {
+        self.lowercased
+            .iter()
+            .any(|pattern| wildcard_matches(pattern, address))
+    }
+}
+
+fn lowercase(text: &str) -> Vec<char> {
+    text.to_lowercase().chars().collect()
+}
+
+/// Match `text` against `pattern`, where `*` matches any amount of characters.
+/// When a match fails after a `*`, we retry with the `*` consuming one
+/// more character. Only the last `*` needs to be retried.
+fn wildcard_matches(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // The position after the last `*` and where in the text it started
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            retry = Some((p, t));
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((retry_p, retry_t)) = retry {
+            p = retry_p;
+            t = retry_t + 1;
+            retry = Some((retry_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|e| *e == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let identity = Identity::new(
+            "Me",
+            vec!["*@mycompany.com".to_owned(), "me+*@gmail.com".to_owned()],
+        );
+        assert!(identity.matches("john@mycompany.com"));
+        assert!(identity.matches("John@MyCompany.com"));
+        assert!(identity.matches("me+lists@gmail.com"));
+        assert!(!identity.matches("me@gmail.com"));
+        assert!(!identity.matches("john@mycompany.com.evil.org"));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let text = "Work: *@mycompany.com; Private: me@gmail.com, me+*@gmail.com; old@web.de";
+        let identities = Identity::parse_list(text);
+        assert_eq!(identities.len(), 3);
+        assert_eq!(identities[1].name, "Private");
+        assert_eq!(identities[1].patterns.len(), 2);
+        assert_eq!(identities[2].name, "old@web.de");
+        assert_eq!(
+            Identity::parse_list(&Identity::format_list(&identities)),
+            identities
+        );
+        assert!(Identity::parse_list("").is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 mod config;
 mod email;
 mod format_type;
-pub use config::{Config, FormatType};
-pub use email::{EmailEntry, EmailMeta};
+mod identity;
+pub use config::{Config, DisplayTimezone, DuplicatePolicy, FormatType, ImapConfig, Source};
+pub use email::{Attachment, EmailEntry, EmailMeta, Fingerprint, Recipient, RecipientKind};
+pub use identity::Identity;
@@ -66,11 +66,12 @@ pub fn value_from_field<'stmt>(field: &Field, row: &Row<'stmt>) -> Result<ValueF
     // Use type safety when unpacking
     match field {
         Path | SenderDomain | SenderLocalPart | SenderName | ToGroup | ToName | ToAddress
+        | RecipientName | RecipientAddress | RecipientKind | AttachmentType | AttachmentName
         | Subject => {
             let string: String = row.get::<&str, String>(field.as_str())?;
             Ok(ValueField::string(field, &string))
         }
-        Year | Month | Day | Timestamp => {
+        Year | Month | Day | Hour | Weekday | Timestamp | AttachmentCount | AttachmentsSize => {
             return Ok(ValueField::usize(
                 field,
                 row.get::<&str, usize>(field.as_str())?,
@@ -84,7 +85,83 @@ pub fn value_from_field<'stmt>(field: &Field, row: &Row<'stmt>) -> Result<ValueF
                 tags.into_iter().map(Value::String).collect(),
             ))
         }
-        IsReply | IsSend | MetaIsSeen => {
+        Size => {
+            // Databases from before the sizes were recorded don't know them
+            let size = row
+                .get::<&str, Option<usize>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::usize(field, size))
+        }
+        Source => {
+            // Databases from before multiple sources don't have one
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        Identity => {
+            // Only the emails of the user have an identity
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        FailedHeaders => {
+            // Only the partially parsed emails have failed headers
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        MailingList => {
+            // Only the emails sent to a list have one
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        Thread | ThreadId => {
+            // The threads are reconstructed at the end of an import
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        ThreadSize | ThreadPosition => {
+            // Until then, every email is a thread of its own
+            let number = row.get::<&str, Option<usize>>(field.as_str())?.unwrap_or(1);
+            Ok(ValueField::usize(field, number))
+        }
+        Correspondent => {
+            // Sent emails without a reply have no correspondent
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        ReplyLatency => {
+            // Only the emails that got a reply have a latency
+            let value = match row.get::<&str, Option<i64>>(field.as_str())? {
+                Some(n) => Value::Number(n.into()),
+                None => Value::Null,
+            };
+            Ok(ValueField::new(field, value))
+        }
+        MetaThreadId => {
+            // Only some formats know about threads
+            let string = row
+                .get::<&str, Option<String>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::string(field, &string))
+        }
+        HasAttachment => {
+            // Databases from before the attachments were recorded don't know
+            let value = row
+                .get::<&str, Option<bool>>(field.as_str())?
+                .unwrap_or_default();
+            Ok(ValueField::bool(field, value))
+        }
+        IsReply | IsSend | IsBulk | IsAnswered | IsReplied | MetaIsSeen => {
             return Ok(ValueField::bool(
                 field,
                 row.get::<&str, bool>(field.as_str())?,
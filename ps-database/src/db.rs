@@ -1,29 +1,38 @@
-use rusqlite::{self, params, Connection, Statement};
+use rusqlite::{self, params, Connection, OptionalExtension, Statement};
 
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::{collections::HashMap, path::Path, thread::JoinHandle};
 
 use super::sql::*;
+use super::threads::{threads, ThreadMail};
 use super::{value_from_field, RowConversion};
-use ps_core::chrono::Datelike;
+use ps_core::chrono::{self, DateTime, Datelike, FixedOffset, Timelike};
 use ps_core::eyre::{self, bail, Report, Result};
 use ps_core::tracing;
 use ps_core::Value;
 use ps_core::{
     crossbeam_channel::{unbounded, Sender},
-    Config, DBMessage, DatabaseLike, DatabaseQuery, EmailEntry, OtherQuery, Query, QueryResult,
+    Config, DBMessage, DatabaseLike, DatabaseQuery, EmailEntry, EmailMeta, Fingerprint, OtherQuery,
+    Query, QueryResult,
 };
 
+/// How many messages are written per transaction during an import
+const CHECKPOINT_INTERVAL: usize = 10_000;
+
 #[derive(Debug)]
 pub struct Database {
     connection: Option<Connection>,
     path: PathBuf,
+    checkpoint_interval: usize,
 }
 
 impl Clone for Database {
     fn clone(&self) -> Self {
         // If we could open one before, we hopefully can open one again
-        Database::new(&self.path).unwrap()
+        let mut database = Database::new(&self.path).unwrap();
+        database.checkpoint_interval = self.checkpoint_interval;
+        database
     }
 }
 
@@ -70,8 +79,9 @@ impl DatabaseLike for Database {
         #[allow(unused_mut)]
         let mut connection = Connection::open(path.as_ref())?;
 
-        // Improve the insertion performance.
-        connection.pragma_update(None, "journal_mode", &"memory")?;
+        // Improve the insertion performance. The write-ahead log keeps
+        // the committed checkpoints of an import intact if the app crashes.
+        connection.pragma_update(None, "journal_mode", &"wal")?;
         connection.pragma_update(None, "synchronous", &"OFF")?;
 
         Self::create_tables(&connection)?;
@@ -84,6 +94,7 @@ impl DatabaseLike for Database {
         Ok(Database {
             connection: Some(connection),
             path: path.as_ref().into(),
+            checkpoint_interval: CHECKPOINT_INTERVAL,
         })
     }
 
@@ -104,6 +115,55 @@ impl DatabaseLike for Database {
         Ok(count)
     }
 
+    fn fingerprints(&self) -> Result<HashSet<i64>> {
+        let connection = match &self.connection {
+            Some(n) => n,
+            None => bail!("No connection to database available in query"),
+        };
+        let mut stmt = connection.prepare(QUERY_SELECT_FINGERPRINTS)?;
+        let mut fingerprints = HashSet::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            fingerprints.insert(row.get::<_, i64>(0)?);
+        }
+        let mut stmt = connection.prepare(QUERY_SELECT_MESSAGE_IDS)?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            fingerprints.insert(Fingerprint::message_id(&row.get::<_, String>(0)?));
+        }
+        Ok(fingerprints)
+    }
+
+    fn total_duplicates(&self) -> Result<usize> {
+        let connection = match &self.connection {
+            Some(n) => n,
+            None => bail!("No connection to database available in query"),
+        };
+        let mut stmt = connection.prepare(QUERY_COUNT_DUPLICATES)?;
+        let count: usize = stmt.query_row([], |q| q.get(0))?;
+        Ok(count)
+    }
+
+    fn total_partial(&self) -> Result<usize> {
+        let connection = match &self.connection {
+            Some(n) => n,
+            None => bail!("No connection to database available in query"),
+        };
+        let mut stmt = connection.prepare(QUERY_COUNT_PARTIAL)?;
+        let count: usize = stmt.query_row([], |q| q.get(0))?;
+        Ok(count)
+    }
+
+    fn total_outdated(&self) -> Result<usize> {
+        let connection = match &self.connection {
+            Some(n) => n,
+            None => bail!("No connection to database available in query"),
+        };
+        let mut stmt = connection.prepare(QUERY_COUNT_OUTDATED)?;
+        let count: usize = stmt.query_row([], |q| q.get(0))?;
+        Ok(count)
+    }
+
     fn save_config(&self, config: Config) -> Result<()> {
         let fields = config
             .into_fields()
@@ -133,14 +193,33 @@ impl DatabaseLike for Database {
         // Import can only be called *once* on a database created with `new`.
         // Therefore there should always be a value to unwrap;
         let mut connection = self.connection.take().unwrap();
+        let checkpoint_interval = self.checkpoint_interval;
         let handle = std::thread::spawn(move || {
+            connection.execute(QUERY_DELETE_ERRORS, [])?;
             let mut counter = 0;
-            {
+            let mut done = false;
+            let mut cancelled = false;
+            while !done {
+                // We commit every `checkpoint_interval` messages. If the import is
+                // cancelled or crashes, the committed mails are kept and skipped
+                // when the import is resumed by updating the database.
                 let transaction = connection.transaction()?;
                 {
-                    let mut mail_prepared = transaction.prepare(QUERY_EMAILS)?;
+                    let mut mail_prepared = InsertStatements {
+                        mail: transaction.prepare(QUERY_EMAILS)?,
+                        recipient: transaction.prepare(QUERY_RECIPIENTS)?,
+                        attachment: transaction.prepare(QUERY_ATTACHMENTS)?,
+                        body: transaction.prepare(QUERY_BODIES)?,
+                        reference: transaction.prepare(QUERY_THREAD_REFERENCES)?,
+                    };
                     let mut error_prepared = transaction.prepare(QUERY_ERRORS)?;
-                    loop {
+                    let mut merge_prepared = MergeStatements {
+                        select: transaction.prepare(QUERY_SELECT_MESSAGE_ID)?,
+                        update: transaction.prepare(QUERY_MERGE_TAGS)?,
+                        seen: transaction.prepare(QUERY_MERGE_SEEN)?,
+                        duplicate: transaction.prepare(QUERY_DUPLICATES)?,
+                    };
+                    for _ in 0..checkpoint_interval {
                         let next = match receiver.recv() {
                             Ok(n) => n,
                             Err(e) => {
@@ -153,17 +232,43 @@ impl DatabaseLike for Database {
                                 counter += 1;
                                 insert_mail(&mut mail_prepared, &mail)
                             }
+                            DBMessage::MergeMail(mail) => {
+                                match merge_mail(&mut merge_prepared, &mail) {
+                                    Ok(true) => Ok(()),
+                                    Ok(false) => {
+                                        counter += 1;
+                                        insert_mail(&mut mail_prepared, &mail)
+                                    }
+                                    Err(e) => Err(e),
+                                }
+                            }
                             DBMessage::Error(report) => insert_error(&mut error_prepared, &report),
                             DBMessage::Done => {
                                 tracing::trace!("Received DBMessage::Done");
+                                done = true;
+                                break;
+                            }
+                            DBMessage::Cancelled => {
+                                tracing::trace!("Received DBMessage::Cancelled");
+                                done = true;
+                                cancelled = true;
                                 break;
                             }
                         }?;
                     }
                 }
+                // Remember when we imported, also if the import is resumed later on
+                record_last_import(&transaction)?;
                 if let Err(e) = transaction.commit() {
                     return Err(eyre::eyre!("Transaction Error: {:?}", &e));
                 }
+                tracing::trace!("Committed {} mails", &counter);
+            }
+            // The new mails can be replies to the existing ones and vice versa,
+            // so their threads are reconstructed. If the import was cancelled, the
+            // mails have no thread until the import is resumed.
+            if !cancelled {
+                update_threads(&mut connection)?;
             }
             // In case closing the database fails, we try again until we succeed
             let mut c = connection;
@@ -182,10 +287,88 @@ impl DatabaseLike for Database {
 }
 
 impl Database {
+    /// How many emails are written per transaction during an import. If the import
+    /// is cancelled or crashes, the emails of the finished transactions are kept.
+    pub fn set_checkpoint_interval(&mut self, interval: usize) {
+        self.checkpoint_interval = interval.max(1);
+    }
+
+    /// When the emails were last imported into the database, if it was
+    /// created or updated by a version that records it
+    pub fn last_import(&self) -> Result<Option<DateTime<FixedOffset>>> {
+        let fields = self.select_config_fields()?;
+        let value = match fields.get(LAST_IMPORT_KEY).and_then(|e| e.as_str()) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        Ok(Some(DateTime::parse_from_rfc3339(value)?))
+    }
+
     fn create_tables(connection: &Connection) -> Result<()> {
         connection.execute(TBL_EMAILS, params![])?;
         connection.execute(TBL_ERRORS, params![])?;
         connection.execute(TBL_META, params![])?;
+        connection.execute(TBL_DUPLICATES, params![])?;
+        let has_recipients: usize =
+            connection.query_row(QUERY_HAS_RECIPIENTS, params![], |row| row.get(0))?;
+        connection.execute(TBL_RECIPIENTS, params![])?;
+        connection.execute(IDX_RECIPIENTS, params![])?;
+        connection.execute(TBL_ATTACHMENTS, params![])?;
+        connection.execute(IDX_ATTACHMENTS, params![])?;
+        connection.execute(TBL_BODIES, params![])?;
+        let has_thread_references: usize =
+            connection.query_row(QUERY_HAS_THREAD_REFERENCES, params![], |row| row.get(0))?;
+        connection.execute(TBL_THREAD_REFERENCES, params![])?;
+        connection.execute(IDX_THREAD_REFERENCES, params![])?;
+        Self::migrate_tables(connection)?;
+        if has_recipients == 0 {
+            connection.execute(QUERY_MIGRATE_RECIPIENTS, params![])?;
+        }
+        if has_thread_references == 0 {
+            Self::migrate_thread_references(connection)?;
+        }
+        // The indices need the migrated `message_id` and `thread_id` columns
+        connection.execute(IDX_MESSAGE_ID, params![])?;
+        connection.execute(IDX_THREAD_ID, params![])?;
+        Ok(())
+    }
+
+    /// Fill the `thread_references` table from the references of each email
+    fn migrate_thread_references(connection: &Connection) -> Result<()> {
+        let mut select = connection.prepare(QUERY_SELECT_REFERENCES)?;
+        let mut insert = connection.prepare(QUERY_THREAD_REFERENCES)?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let rowid: i64 = row.get(0)?;
+            let references: String = row.get(1)?;
+            for reference in references.split_whitespace() {
+                insert.execute(params![rowid, reference])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the columns that databases from older versions don't have yet
+    fn migrate_tables(connection: &Connection) -> Result<()> {
+        let mut stmt = connection.prepare("PRAGMA table_info(emails)")?;
+        let mut rows = stmt.query([])?;
+        let mut columns = HashSet::new();
+        while let Some(row) = rows.next()? {
+            columns.insert(row.get::<_, String>("name")?);
+        }
+        for (column, kind) in MIGRATED_COLUMNS {
+            if columns.contains(*column) {
+                continue;
+            }
+            tracing::info!("Adding missing column {}", column);
+            connection.execute(
+                &format!("ALTER TABLE emails ADD COLUMN {} {}", column, kind),
+                params![],
+            )?;
+        }
+        if !columns.contains("hour") {
+            connection.execute(QUERY_MIGRATE_LOCAL_TIME, params![])?;
+        }
         Ok(())
     }
 
@@ -215,25 +398,51 @@ impl Database {
             Some(n) => n,
             None => bail!("No connection to database available in query"),
         };
+        // Saving the config again (e.g. when updating) replaces the old values
+        let mut delete = connection.prepare(QUERY_DELETE_META)?;
         let mut stmt = connection.prepare(QUERY_INSERT_META)?;
         for (key, value) in fields {
+            delete.execute(params![key])?;
             stmt.execute(params![key, value])?;
         }
         Ok(())
     }
 }
 
-fn insert_mail(statement: &mut Statement, entry: &EmailEntry) -> Result<()> {
+fn record_last_import(connection: &Connection) -> Result<()> {
+    let last_import = Value::String(chrono::Utc::now().to_rfc3339());
+    connection.execute(QUERY_DELETE_META, params![LAST_IMPORT_KEY])?;
+    connection.execute(QUERY_INSERT_META, params![LAST_IMPORT_KEY, last_import])?;
+    Ok(())
+}
+
+struct InsertStatements<'a> {
+    mail: Statement<'a>,
+    recipient: Statement<'a>,
+    attachment: Statement<'a>,
+    body: Statement<'a>,
+    reference: Statement<'a>,
+}
+
+fn insert_mail(statements: &mut InsertStatements, entry: &EmailEntry) -> Result<()> {
     let path = entry.path.display().to_string();
-    let year = entry.datetime.date().year();
-    let month = entry.datetime.date().month();
-    let day = entry.datetime.date().day();
+    let local = &entry.display_datetime;
+    let year = local.year();
+    let month = local.month();
+    let day = local.day();
+    let hour = local.hour();
+    let weekday = local.weekday().number_from_monday();
     let timestamp = entry.datetime.timestamp();
+    let utc_offset = entry.datetime.offset().local_minus_utc();
     let e = entry;
     let to_name = e.to_first.as_ref().map(|e| &e.0);
     let to_address = e.to_first.as_ref().map(|e| &e.1);
     let meta_tags = e.meta.as_ref().map(|e| e.tags_string());
     let meta_is_seen = e.meta.as_ref().map(|e| e.is_seen);
+    let meta_thread_id = e.meta.as_ref().and_then(|e| e.thread_id.as_ref());
+    let failed_headers = e.failed_headers.as_ref().map(|e| e.join(", "));
+    let references = e.references.join(" ");
+    let attachments_size: usize = e.attachments.iter().map(|e| e.size).sum();
     let p = params![
         path,
         e.sender_domain,
@@ -251,13 +460,205 @@ fn insert_mail(statement: &mut Statement, entry: &EmailEntry) -> Result<()> {
         e.is_reply,
         e.is_send,
         meta_tags,
-        meta_is_seen
+        meta_is_seen,
+        meta_thread_id,
+        e.fingerprint,
+        e.message_id,
+        e.source,
+        e.identity,
+        failed_headers,
+        hour,
+        weekday,
+        utc_offset,
+        e.mailing_list,
+        e.is_bulk,
+        references,
+        e.attachments.len(),
+        attachments_size,
+        !e.attachments.is_empty(),
+        e.size
     ];
-    statement.execute(p)?;
+    let rowid = statements.mail.insert(p)?;
+    for recipient in &e.recipients {
+        statements.recipient.execute(params![
+            rowid,
+            recipient.name,
+            recipient.address,
+            recipient.kind.as_str()
+        ])?;
+    }
+    for attachment in &e.attachments {
+        statements.attachment.execute(params![
+            rowid,
+            attachment.filename,
+            attachment.mime_type,
+            attachment.size
+        ])?;
+    }
+    if !e.body.is_empty() {
+        statements.body.execute(params![rowid, e.body])?;
+    }
+    for reference in &e.references {
+        statements.reference.execute(params![rowid, reference])?;
+    }
     tracing::trace!("Insert Mail {}", &path);
     Ok(())
 }
 
+struct MergeStatements<'a> {
+    select: Statement<'a>,
+    update: Statement<'a>,
+    seen: Statement<'a>,
+    duplicate: Statement<'a>,
+}
+
+/// If there is a mail with the same `message_id`, add the tags of `entry`
+/// to it (and mark it as seen if `entry` was) and record `entry` as a
+/// duplicate. Returns `false` if there is no such mail, so that `entry`
+/// should be inserted instead.
+fn merge_mail(statements: &mut MergeStatements, entry: &EmailEntry) -> Result<bool> {
+    let message_id = match &entry.message_id {
+        Some(n) => n,
+        None => return Ok(false),
+    };
+    let existing = statements
+        .select
+        .query_row(params![message_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<bool>>(2)?,
+            ))
+        })
+        .optional()?;
+    let (rowid, tags, is_seen) = match existing {
+        Some(n) => n,
+        None => return Ok(false),
+    };
+
+    let new_is_seen = entry.meta.as_ref().map(|e| e.is_seen).unwrap_or(false);
+    if new_is_seen && is_seen != Some(true) {
+        statements.seen.execute(params![rowid])?;
+    }
+
+    let mut tags = tags
+        .filter(|e| !e.is_empty())
+        .map(|e| EmailMeta::tags_from_string(&e))
+        .unwrap_or_default();
+    let new_tags = entry
+        .meta
+        .as_ref()
+        .map(|e| e.tags.as_slice())
+        .unwrap_or(&[]);
+    if new_tags.iter().any(|e| !tags.contains(e)) {
+        for tag in new_tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let tags_string = EmailMeta {
+            tags,
+            ..Default::default()
+        }
+        .tags_string();
+        statements.update.execute(params![tags_string, rowid])?;
+    }
+
+    let path = entry.path.display().to_string();
+    statements
+        .duplicate
+        .execute(params![message_id, path, entry.fingerprint])?;
+    tracing::trace!("Merged Mail {}", &path);
+    Ok(true)
+}
+
+/// Reconstruct the threads of the mails that are in no thread yet, together
+/// with the existing threads that they join, so that an update doesn't have
+/// to go through all the mails. Each thread contains all the mails that
+/// refer to each other, so the other threads stay the same.
+fn update_threads(connection: &mut Connection) -> Result<()> {
+    let mut mails = select_thread_mails(connection, QUERY_SELECT_UNTHREADED_MAILS, params![])?;
+    if mails.is_empty() {
+        return Ok(());
+    }
+    // On the first import, there are no threads to join
+    let has_threads: bool = connection.query_row(QUERY_HAS_THREADS, params![], |row| row.get(0))?;
+    let mut thread_ids: HashSet<String> = HashSet::new();
+    if has_threads {
+        let mut keys: HashSet<&str> = HashSet::new();
+        for mail in &mails {
+            keys.extend(mail.message_id.as_deref());
+            keys.extend(mail.references.iter().map(|e| e.as_str()));
+        }
+        let mut stmt = connection.prepare(QUERY_SELECT_RELATED_THREADS)?;
+        for key in keys {
+            let mut rows = stmt.query(params![key])?;
+            while let Some(row) = rows.next()? {
+                thread_ids.insert(row.get(0)?);
+            }
+        }
+    }
+    for thread_id in &thread_ids {
+        let thread =
+            select_thread_mails(connection, QUERY_SELECT_THREAD_MAILS, params![thread_id])?;
+        mails.extend(thread);
+    }
+    // In the order of the import, like when all mails are threaded at once
+    mails.sort_by_key(|e| e.rowid);
+
+    let infos = threads(&mails);
+    let transaction = connection.transaction()?;
+    {
+        let mut stmt = transaction.prepare(QUERY_UPDATE_THREAD)?;
+        for (mail, info) in mails.iter().zip(infos) {
+            stmt.execute(params![
+                info.id,
+                info.subject,
+                info.size,
+                info.position,
+                info.is_answered,
+                info.reply_latency.is_some(),
+                info.reply_latency,
+                info.correspondent,
+                mail.rowid
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    tracing::trace!(
+        "Updated the threads of {} mails in {} existing threads",
+        mails.len(),
+        thread_ids.len()
+    );
+    Ok(())
+}
+
+fn select_thread_mails<P: rusqlite::Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<ThreadMail>> {
+    let mut mails = Vec::new();
+    let mut stmt = connection.prepare(query)?;
+    let mut rows = stmt.query(params)?;
+    while let Some(row) = rows.next()? {
+        let references = row.get::<_, Option<String>>(2)?.unwrap_or_default();
+        mails.push(ThreadMail {
+            rowid: row.get(0)?,
+            message_id: row.get(1)?,
+            references: references
+                .split_whitespace()
+                .map(|e| e.to_owned())
+                .collect(),
+            timestamp: row.get(3)?,
+            subject: row.get(4)?,
+            sender: row.get(5)?,
+            is_send: row.get(6)?,
+        });
+    }
+    Ok(mails)
+}
+
 fn insert_error(statement: &mut Statement, message: &Report) -> Result<()> {
     statement.execute(params![message.to_string()])?;
     tracing::trace!("Insert Error {}", message);
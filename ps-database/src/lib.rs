@@ -5,6 +5,7 @@
 mod conversion;
 mod db;
 mod sql;
+mod threads;
 
 pub use conversion::{value_from_field, RowConversion};
 pub use db::Database;
@@ -16,7 +16,31 @@ CREATE TABLE IF NOT EXISTS emails (
   is_reply BOOL,
   is_send BOOL,
   meta_tags TEXT NULL,
-  meta_is_seen BOOL NULL
+  meta_is_seen BOOL NULL,
+  meta_thread_id TEXT NULL,
+  fingerprint INTEGER NULL,
+  message_id TEXT NULL,
+  source TEXT NULL,
+  identity TEXT NULL,
+  failed_headers TEXT NULL,
+  hour INTEGER NULL,
+  weekday INTEGER NULL,
+  utc_offset INTEGER NULL,
+  mailing_list TEXT NULL,
+  is_bulk BOOL NOT NULL DEFAULT 0,
+  thread_references TEXT NULL,
+  thread_id TEXT NULL,
+  thread TEXT NULL,
+  thread_size INTEGER NULL,
+  thread_position INTEGER NULL,
+  is_answered BOOL NOT NULL DEFAULT 0,
+  is_replied BOOL NOT NULL DEFAULT 0,
+  reply_latency INTEGER NULL,
+  correspondent TEXT NULL,
+  attachment_count INTEGER NOT NULL DEFAULT 0,
+  attachments_size INTEGER NOT NULL DEFAULT 0,
+  has_attachment BOOL NULL,
+  size INTEGER NULL
 );"#;
 
 pub const QUERY_EMAILS: &str = r#"
@@ -26,7 +50,14 @@ INSERT INTO emails
         year, month, day, timestamp, subject,
         to_count, to_group, to_name, to_address,
         is_reply, is_send,
-        meta_tags, meta_is_seen
+        meta_tags, meta_is_seen, meta_thread_id,
+        fingerprint, message_id, source,
+        identity, failed_headers,
+        hour, weekday, utc_offset,
+        mailing_list, is_bulk,
+        thread_references,
+        attachment_count, attachments_size, has_attachment,
+        size
     )
 VALUES
     (
@@ -34,10 +65,116 @@ VALUES
         ?, ?, ?, ?, ?,
         ?, ?, ?, ?,
         ?, ?,
-        ?, ?
+        ?, ?, ?,
+        ?, ?, ?,
+        ?, ?,
+        ?, ?, ?,
+        ?, ?,
+        ?,
+        ?, ?, ?,
+        ?
     )
 "#;
 
+/// All the addresses in `To`, `Cc` and `Bcc`. `email_id` is the `rowid`
+/// of the email. The columns are named like the recipient `Field`s.
+pub const TBL_RECIPIENTS: &str = r#"
+CREATE TABLE IF NOT EXISTS recipients (
+  email_id INTEGER NOT NULL,
+  recipient_name TEXT NOT NULL,
+  recipient_address TEXT NOT NULL,
+  recipient_kind TEXT NOT NULL
+);"#;
+
+pub const IDX_RECIPIENTS: &str = r#"
+CREATE INDEX IF NOT EXISTS recipients_email_id ON recipients (email_id);"#;
+
+pub const QUERY_RECIPIENTS: &str = r#"
+INSERT INTO recipients
+    (email_id, recipient_name, recipient_address, recipient_kind)
+VALUES
+    (?, ?, ?, ?)
+"#;
+
+pub const QUERY_HAS_RECIPIENTS: &str = r#"
+SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'recipients'
+"#;
+
+/// Databases from before the recipients table only know the first `To` address
+pub const QUERY_MIGRATE_RECIPIENTS: &str = r#"
+INSERT INTO recipients
+    (email_id, recipient_name, recipient_address, recipient_kind)
+SELECT rowid, IFNULL(to_name, ''), to_address, 'to' FROM emails WHERE to_address IS NOT NULL
+"#;
+
+/// The files attached to the emails. `email_id` is the `rowid` of
+/// the email. The columns are named like the attachment `Field`s.
+pub const TBL_ATTACHMENTS: &str = r#"
+CREATE TABLE IF NOT EXISTS attachments (
+  email_id INTEGER NOT NULL,
+  attachment_name TEXT NOT NULL,
+  attachment_type TEXT NOT NULL,
+  attachment_size INTEGER NOT NULL
+);"#;
+
+pub const IDX_ATTACHMENTS: &str = r#"
+CREATE INDEX IF NOT EXISTS attachments_email_id ON attachments (email_id);"#;
+
+pub const QUERY_ATTACHMENTS: &str = r#"
+INSERT INTO attachments
+    (email_id, attachment_name, attachment_type, attachment_size)
+VALUES
+    (?, ?, ?, ?)
+"#;
+
+/// The text of the emails for the full-text search. The `rowid` of a body
+/// is the `rowid` of its email. The table is contentless, the text is only
+/// indexed and not stored, as it is never displayed.
+pub const TBL_BODIES: &str = r#"
+CREATE VIRTUAL TABLE IF NOT EXISTS bodies USING fts5(body, content='');"#;
+
+pub const QUERY_BODIES: &str = r#"
+INSERT INTO bodies (rowid, body) VALUES (?, ?)
+"#;
+
+pub const IDX_MESSAGE_ID: &str = r#"
+CREATE INDEX IF NOT EXISTS emails_message_id ON emails (message_id);"#;
+
+pub const QUERY_SELECT_MESSAGE_ID: &str = r#"
+SELECT rowid, meta_tags, meta_is_seen FROM emails WHERE message_id = ? LIMIT 1
+"#;
+
+pub const QUERY_MERGE_TAGS: &str = r#"
+UPDATE emails SET meta_tags = ? WHERE rowid = ?
+"#;
+
+/// A mail counts as seen if any of its copies was seen
+pub const QUERY_MERGE_SEEN: &str = r#"
+UPDATE emails SET meta_is_seen = TRUE WHERE rowid = ?
+"#;
+
+/// The copies of emails that were merged into the email with the same `message_id`
+pub const TBL_DUPLICATES: &str = r#"
+CREATE TABLE IF NOT EXISTS duplicates (
+  message_id TEXT NOT NULL,
+  path TEXT NOT NULL,
+  fingerprint INTEGER NOT NULL
+);"#;
+
+pub const QUERY_DUPLICATES: &str = r#"
+INSERT INTO duplicates
+    (message_id, path, fingerprint)
+VALUES
+    (?, ?, ?)
+"#;
+
+/// The merged copies, plus the copies that were imported as separate emails
+pub const QUERY_COUNT_DUPLICATES: &str = r#"
+SELECT
+    (SELECT count(*) FROM duplicates) +
+    (SELECT count(message_id) - count(DISTINCT message_id) FROM emails)
+"#;
+
 pub const TBL_ERRORS: &str = r#"
 CREATE TABLE IF NOT EXISTS errors (
   message TEXT NOT NULL
@@ -50,12 +187,24 @@ VALUES
     (?)
 "#;
 
+/// Each import reports the emails it couldn't parse again
+pub const QUERY_DELETE_ERRORS: &str = r#"
+DELETE FROM errors
+"#;
+
 pub const TBL_META: &str = r#"
 CREATE TABLE IF NOT EXISTS meta (
     key TEXT NOT NULL,
     value TEXT NOT NULL
 );"#;
 
+/// The `meta` key for the time of the last (finished or checkpointed) import
+pub const LAST_IMPORT_KEY: &str = "last_import";
+
+pub const QUERY_DELETE_META: &str = r#"
+DELETE FROM meta WHERE key = ?
+"#;
+
 pub const QUERY_INSERT_META: &str = r#"
 INSERT INTO meta
     (key, value)
@@ -69,3 +218,142 @@ SELECT key, value FROM meta"#;
 pub const QUERY_COUNT_MAILS: &str = r#"
 SELECT count(path) FROM emails
 "#;
+
+/// The emails that could only be parsed partially
+pub const QUERY_COUNT_PARTIAL: &str = r#"
+SELECT count(path) FROM emails WHERE failed_headers IS NOT NULL
+"#;
+
+/// The emails that were imported before the attachments and texts were recorded
+pub const QUERY_COUNT_OUTDATED: &str = r#"
+SELECT count(path) FROM emails WHERE has_attachment IS NULL
+"#;
+
+pub const QUERY_SELECT_FINGERPRINTS: &str = r#"
+SELECT fingerprint FROM emails WHERE fingerprint IS NOT NULL
+UNION
+SELECT fingerprint FROM duplicates
+"#;
+
+/// The emails are identified by their `Message-ID`, also in
+/// databases from versions that fingerprinted the path and contents
+pub const QUERY_SELECT_MESSAGE_IDS: &str = r#"
+SELECT message_id FROM emails WHERE message_id IS NOT NULL
+UNION
+SELECT message_id FROM duplicates
+"#;
+
+/// The ids in the `References` and `In-Reply-To` of the emails, one per row, so
+/// that the emails referring to a new email can be found. `email_id` is the `rowid`.
+pub const TBL_THREAD_REFERENCES: &str = r#"
+CREATE TABLE IF NOT EXISTS thread_references (
+  email_id INTEGER NOT NULL,
+  reference TEXT NOT NULL
+);"#;
+
+pub const IDX_THREAD_REFERENCES: &str = r#"
+CREATE INDEX IF NOT EXISTS thread_references_reference ON thread_references (reference);"#;
+
+pub const QUERY_THREAD_REFERENCES: &str = r#"
+INSERT INTO thread_references
+    (email_id, reference)
+VALUES
+    (?, ?)
+"#;
+
+pub const QUERY_HAS_THREAD_REFERENCES: &str = r#"
+SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'thread_references'
+"#;
+
+/// Databases from before the `thread_references` table only have the references
+/// as one string, which is split up to fill the table
+pub const QUERY_SELECT_REFERENCES: &str = r#"
+SELECT rowid, thread_references FROM emails WHERE thread_references IS NOT NULL
+"#;
+
+pub const IDX_THREAD_ID: &str = r#"
+CREATE INDEX IF NOT EXISTS emails_thread_id ON emails (thread_id);"#;
+
+/// What the threading needs to know about the emails that are not in a thread yet.
+/// These are the ones imported since the last update (or by a cancelled import).
+pub const QUERY_SELECT_UNTHREADED_MAILS: &str = r#"
+SELECT
+    rowid, message_id, thread_references, timestamp, subject,
+    sender_local_part || '@' || sender_domain, is_send
+FROM emails
+WHERE thread_id IS NULL
+"#;
+
+pub const QUERY_HAS_THREADS: &str = r#"
+SELECT EXISTS (SELECT 1 FROM emails WHERE thread_id IS NOT NULL)
+"#;
+
+/// The threads an email with the `Message-ID` or reference `?1` belongs to:
+/// the one of the email with that id, the ones of the emails referring to it,
+/// and the one that is named after it because that email was missing
+pub const QUERY_SELECT_RELATED_THREADS: &str = r#"
+SELECT thread_id FROM emails WHERE message_id = ?1 AND thread_id IS NOT NULL
+UNION
+SELECT emails.thread_id FROM thread_references
+JOIN emails ON emails.rowid = thread_references.email_id
+WHERE thread_references.reference = ?1 AND emails.thread_id IS NOT NULL
+UNION
+SELECT thread_id FROM emails WHERE thread_id = ?1
+"#;
+
+/// What the threading needs to know about the emails in a thread
+pub const QUERY_SELECT_THREAD_MAILS: &str = r#"
+SELECT
+    rowid, message_id, thread_references, timestamp, subject,
+    sender_local_part || '@' || sender_domain, is_send
+FROM emails
+WHERE thread_id = ?
+"#;
+
+/// The `thread` is the subject of the first email in the thread
+pub const QUERY_UPDATE_THREAD: &str = r#"
+UPDATE emails SET
+    thread_id = ?, thread = ?, thread_size = ?, thread_position = ?, is_answered = ?,
+    is_replied = ?, reply_latency = ?, correspondent = ?
+WHERE rowid = ?
+"#;
+
+/// Columns that were added after the first release. Databases created
+/// before are migrated by adding them.
+pub const MIGRATED_COLUMNS: &[(&str, &str)] = &[
+    ("meta_thread_id", "TEXT NULL"),
+    ("fingerprint", "INTEGER NULL"),
+    ("message_id", "TEXT NULL"),
+    ("source", "TEXT NULL"),
+    ("identity", "TEXT NULL"),
+    ("failed_headers", "TEXT NULL"),
+    ("hour", "INTEGER NULL"),
+    ("weekday", "INTEGER NULL"),
+    ("utc_offset", "INTEGER NULL"),
+    ("mailing_list", "TEXT NULL"),
+    ("is_bulk", "BOOL NOT NULL DEFAULT 0"),
+    ("thread_references", "TEXT NULL"),
+    ("thread_id", "TEXT NULL"),
+    ("thread", "TEXT NULL"),
+    ("thread_size", "INTEGER NULL"),
+    ("thread_position", "INTEGER NULL"),
+    ("is_answered", "BOOL NOT NULL DEFAULT 0"),
+    ("is_replied", "BOOL NOT NULL DEFAULT 0"),
+    ("reply_latency", "INTEGER NULL"),
+    ("correspondent", "TEXT NULL"),
+    ("attachment_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("attachments_size", "INTEGER NOT NULL DEFAULT 0"),
+    // Unknown (`NULL`) for the emails that were imported before
+    ("has_attachment", "BOOL NULL"),
+    // Unknown (`NULL`) for the emails that were imported before
+    ("size", "INTEGER NULL"),
+];
+
+/// Databases from before the time zones were kept stored the time of the sender
+/// as the timestamp, so the hour and weekday (`1` is Monday) can be computed from it
+pub const QUERY_MIGRATE_LOCAL_TIME: &str = r#"
+UPDATE emails SET
+    hour = CAST(strftime('%H', timestamp, 'unixepoch') AS INTEGER),
+    weekday = (CAST(strftime('%w', timestamp, 'unixepoch') AS INTEGER) + 6) % 7 + 1
+WHERE hour IS NULL
+"#;
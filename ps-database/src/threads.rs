@@ -0,0 +1,299 @@
+//! Reconstruction of conversation threads, following the algorithm
+//! by Jamie Zawinski (<https://www.jwz.org/doc/threading.html>).
+//!
+//! Each email refers to its parents with `References` and `In-Reply-To`.
+//! Emails that are referenced but missing (e.g. because they were deleted)
+//! are kept as empty containers, so that their replies stay in one thread.
+//! Unlike the original, emails are not grouped by subject, as that merges
+//! unrelated mails like "Hello" or "Question".
+
+use std::collections::HashMap;
+
+/// What the threading needs to know about an email
+#[derive(Debug, Clone)]
+pub struct ThreadMail {
+    pub rowid: i64,
+    pub message_id: Option<String>,
+    /// The `References` followed by `In-Reply-To`, the direct parent last
+    pub references: Vec<String>,
+    pub timestamp: i64,
+    pub subject: String,
+    /// The address of the sender
+    pub sender: String,
+    pub is_send: bool,
+}
+
+/// The thread of an email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    /// The `Message-ID` of the first email of the thread
+    pub id: String,
+    /// The subject of the oldest email in the thread
+    pub subject: String,
+    /// The amount of emails in the thread
+    pub size: usize,
+    /// The position of the email in the thread by date, starting at `1`
+    pub position: usize,
+    /// Did the user reply to this email?
+    pub is_answered: bool,
+    /// The seconds until the first reply from the other side: from the
+    /// user for received emails, from someone else for sent emails
+    pub reply_latency: Option<i64>,
+    /// Who the user talks to: the sender of received emails, and
+    /// whoever replied first to sent emails
+    pub correspondent: Option<String>,
+}
+
+#[derive(Default)]
+struct Container {
+    /// The index of the email in this container, if we have it
+    mail: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Threader {
+    containers: Vec<Container>,
+    ids: HashMap<String, usize>,
+    /// The key of each container, which is its `Message-ID`
+    keys: Vec<String>,
+}
+
+impl Threader {
+    fn container(&mut self, key: &str) -> usize {
+        if let Some(index) = self.ids.get(key) {
+            return *index;
+        }
+        let index = self.containers.len();
+        self.containers.push(Container::default());
+        self.keys.push(key.to_owned());
+        self.ids.insert(key.to_owned(), index);
+        index
+    }
+
+    /// Is `ancestor` the same as `index` or one of its parents?
+    fn is_ancestor(&self, ancestor: usize, index: usize) -> bool {
+        let mut current = Some(index);
+        while let Some(n) = current {
+            if n == ancestor {
+                return true;
+            }
+            current = self.containers[n].parent;
+        }
+        false
+    }
+
+    fn set_parent(&mut self, child: usize, parent: usize) {
+        if let Some(old) = self.containers[child].parent.take() {
+            self.containers[old].children.retain(|e| *e != child);
+        }
+        self.containers[child].parent = Some(parent);
+        self.containers[parent].children.push(child);
+    }
+
+    fn add(&mut self, index: usize, mail: &ThreadMail) {
+        // Emails without an id, or with the id of an email that we
+        // already have, get a key that can't be referenced
+        let key = match &mail.message_id {
+            Some(n)
+                if self
+                    .ids
+                    .get(n)
+                    .map_or(true, |e| self.containers[*e].mail.is_none()) =>
+            {
+                n.clone()
+            }
+            _ => format!("#{}", mail.rowid),
+        };
+        let container = self.container(&key);
+        self.containers[container].mail = Some(index);
+
+        // Each reference is the parent of the next one, unless we know
+        // better already or that would create a loop
+        let mut previous: Option<usize> = None;
+        for reference in &mail.references {
+            let current = self.container(reference);
+            if let Some(parent) = previous {
+                if self.containers[current].parent.is_none() && !self.is_ancestor(current, parent) {
+                    self.set_parent(current, parent);
+                }
+            }
+            previous = Some(current);
+        }
+
+        // The last reference is the parent of this email
+        if let Some(parent) = previous {
+            if !self.is_ancestor(container, parent) {
+                self.set_parent(container, parent);
+            }
+        }
+    }
+
+    /// The emails in the tree below `index`
+    fn collect(&self, index: usize, mails: &mut Vec<usize>) {
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            if let Some(mail) = self.containers[current].mail {
+                mails.push(mail);
+            }
+            stack.extend(&self.containers[current].children);
+        }
+    }
+
+    /// The replies to the email in `index`. Missing emails in between are skipped.
+    fn replies(&self, index: usize) -> Vec<usize> {
+        let mut replies = Vec::new();
+        let mut stack = self.containers[index].children.clone();
+        while let Some(current) = stack.pop() {
+            match self.containers[current].mail {
+                Some(mail) => replies.push(mail),
+                None => stack.extend(&self.containers[current].children),
+            }
+        }
+        replies
+    }
+}
+
+/// Find the thread of each of the `mails`. The result is in the same order.
+pub fn threads(mails: &[ThreadMail]) -> Vec<ThreadInfo> {
+    let mut threader = Threader::default();
+    for (index, mail) in mails.iter().enumerate() {
+        threader.add(index, mail);
+    }
+
+    let mut infos: Vec<Option<ThreadInfo>> = vec![None; mails.len()];
+    let mut thread: Vec<usize> = Vec::new();
+    for root in 0..threader.containers.len() {
+        if threader.containers[root].parent.is_some() {
+            continue;
+        }
+        thread.clear();
+        threader.collect(root, &mut thread);
+        if thread.is_empty() {
+            continue;
+        }
+        thread.sort_by_key(|e| (mails[*e].timestamp, mails[*e].rowid));
+        // The subjects keep the whitespace after `Subject:`
+        let subject = mails[thread[0]].subject.trim().to_owned();
+        for (position, mail) in thread.iter().enumerate() {
+            infos[*mail] = Some(ThreadInfo {
+                id: threader.keys[root].clone(),
+                subject: subject.clone(),
+                size: thread.len(),
+                position: position + 1,
+                is_answered: false,
+                reply_latency: None,
+                correspondent: None,
+            });
+        }
+    }
+    for (index, container) in threader.containers.iter().enumerate() {
+        let mail = match container.mail {
+            Some(n) => n,
+            None => continue,
+        };
+        let replies = threader.replies(index);
+        let first_reply = replies
+            .iter()
+            .filter(|e| mails[**e].is_send != mails[mail].is_send)
+            .min_by_key(|e| (mails[**e].timestamp, mails[**e].rowid));
+        if let Some(info) = infos[mail].as_mut() {
+            info.is_answered = replies.iter().any(|e| mails[*e].is_send);
+            // Wrong clocks can make a reply older than the email
+            info.reply_latency =
+                first_reply.map(|e| (mails[*e].timestamp - mails[mail].timestamp).max(0));
+            info.correspondent = match mails[mail].is_send {
+                true => first_reply.map(|e| mails[*e].sender.clone()),
+                false => Some(mails[mail].sender.clone()),
+            };
+        }
+    }
+    // Every email is in a container without a loop, so it is below a root
+    infos
+        .into_iter()
+        .map(|e| e.expect("Every email is in a thread"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail(rowid: i64, id: Option<&str>, references: &[&str], is_send: bool) -> ThreadMail {
+        ThreadMail {
+            rowid,
+            message_id: id.map(|e| e.to_owned()),
+            references: references.iter().map(|e| e.to_string()).collect(),
+            timestamp: rowid * 60,
+            subject: format!("Mail {}", rowid),
+            sender: match is_send {
+                true => "me@example.com".to_owned(),
+                false => format!("{}@example.com", rowid),
+            },
+            is_send,
+        }
+    }
+
+    #[test]
+    fn test_threads() {
+        let mails = vec![
+            // A reply that arrived before the mail it replies to
+            mail(3, Some("c"), &["a", "b"], false),
+            mail(1, Some("a"), &[], false),
+            mail(2, Some("b"), &["a"], true),
+            // The parent of this one is missing
+            mail(4, Some("e"), &["d"], false),
+            mail(5, Some("f"), &["d", "e"], true),
+            mail(6, None, &[], false),
+        ];
+        let infos = threads(&mails);
+        let summary: Vec<(&str, usize, usize, bool)> = infos
+            .iter()
+            .map(|e| (e.id.as_str(), e.size, e.position, e.is_answered))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", 3, 3, false),
+                ("a", 3, 1, true),
+                ("a", 3, 2, false),
+                ("d", 2, 1, true),
+                ("d", 2, 2, false),
+                ("#6", 1, 1, false),
+            ]
+        );
+        assert_eq!(infos[0].subject, "Mail 1");
+
+        // I answered the first mail after a minute, the answer to
+        // my mail came another minute later
+        let latencies: Vec<(Option<i64>, Option<&str>)> = infos
+            .iter()
+            .map(|e| (e.reply_latency, e.correspondent.as_deref()))
+            .collect();
+        assert_eq!(
+            latencies,
+            vec![
+                (None, Some("3@example.com")),
+                (Some(60), Some("1@example.com")),
+                (Some(60), Some("3@example.com")),
+                (Some(60), Some("4@example.com")),
+                (None, None),
+                (None, Some("6@example.com")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loops_and_duplicates() {
+        let mails = vec![
+            mail(1, Some("a"), &["b"], false),
+            mail(2, Some("b"), &["a"], false),
+            mail(3, Some("a"), &[], false),
+        ];
+        let infos = threads(&mails);
+        assert_eq!(infos[0].id, infos[1].id);
+        assert_eq!(infos[0].size, 2);
+        assert_eq!(infos[2].id, "#3");
+    }
+}
@@ -12,7 +12,7 @@ use super::Textures;
 use super::{StateUIAction, StateUIVariant};
 use ps_core::{
     message_adapter::{Adapter, State},
-    Config, DatabaseLike, FormatType,
+    CancelToken, Config, DatabaseLike,
 };
 
 pub struct ImporterUI {
@@ -23,6 +23,8 @@ pub struct ImporterUI {
     /// The handle to the adapter thread
     /// As handle.join takes `self` it has to be optional
     handle: Option<JoinHandle<Result<()>>>,
+    /// Used to cancel the import
+    cancel: CancelToken,
     /// The animation divisions
     animation_divisions: usize,
     /// time counter
@@ -39,6 +41,8 @@ pub struct ImporterUI {
     progress_divisions: usize,
     /// we're done importing
     pub done_importing: bool,
+    /// The import was cancelled
+    pub cancelled: bool,
     /// Total amount of mails we imported
     pub total_mails: usize,
     /// Any errors during importing
@@ -69,27 +73,14 @@ impl ImporterUI {
 
         // The adapter that controls the syncing
         let adapter = Adapter::new();
+        let cancel = CancelToken::default();
 
-        // Could not figure out how to build this properly
-        // with dynamic dispatch. (to abstract away the match)
-        // Will try again when I'm online.
         // On Wasm, we just do nothing. Wasm is just a demo and
         // the importer will never be run.
-
         #[cfg(not(target_arch = "wasm32"))]
-        let handle = match config.format {
-            FormatType::AppleMail => {
-                let importer = ps_importer::applemail_importer(config);
-                adapter.process(database, importer)?
-            }
-            FormatType::GmailVault => {
-                let importer = ps_importer::gmail_importer(config);
-                adapter.process(database, importer)?
-            }
-            FormatType::Mbox => {
-                let importer = ps_importer::mbox_importer(config);
-                adapter.process(database, importer)?
-            }
+        let handle = {
+            let importer = ps_importer::importer(config);
+            adapter.process(database, importer, cancel.clone())?
         };
 
         #[cfg(target_arch = "wasm32")]
@@ -99,6 +90,7 @@ impl ImporterUI {
             config: cloned_config,
             adapter,
             handle: Some(handle),
+            cancel,
             animation_divisions,
             timer: 0.0,
             offset_counter: 0,
@@ -106,6 +98,7 @@ impl ImporterUI {
             progress_blocks,
             progress_divisions,
             done_importing: false,
+            cancelled: false,
             total_mails: 0,
             importer_error: None,
             missing_permissions: false,
@@ -125,6 +118,11 @@ impl StateUIVariant for ImporterUI {
                 report,
                 config: self.config.clone(),
             },
+            // The mails that were imported until then stay in the database,
+            // updating it later on resumes the import
+            (_, true) if self.cancelled => StateUIAction::Close {
+                config: self.config.clone(),
+            },
             (_, true) => StateUIAction::ImportDone {
                 config: self.config.clone(),
                 total: self.total_mails,
@@ -142,7 +140,7 @@ impl ImporterUI {
 
         let available = ui.available_size();
 
-        let (label, progress, writing, done, written) = match self.handle_adapter() {
+        let (label, progress, writing, done, cancelled, written) = match self.handle_adapter() {
             Ok(state) => {
                 #[cfg(target_os = "macos")]
                 if state.missing_permissions {
@@ -155,13 +153,14 @@ impl ImporterUI {
                     writing,
                     written,
                     done,
+                    cancelled,
                     ..
                 } = state;
-                (label, progress, writing, done, written)
+                (label, progress, writing, done, cancelled, written)
             }
             Err(e) => {
                 // Generate a response signifying we're done - as there was an error
-                let response = (format!("Error {}", &e), 1.0, false, true, 0);
+                let response = (format!("Error {}", &e), 1.0, false, true, false, 0);
                 self.importer_error = Some(e);
                 response
             }
@@ -171,12 +170,13 @@ impl ImporterUI {
             self.importer_error = Some(error);
         }
 
-        if done && !self.missing_permissions {
+        if (done || cancelled) && !self.missing_permissions {
             // if we're done, the join handle should not lock
             if let Some(handle) = self.handle.take() {
                 self.importer_error = handle.join().ok().map(|e| e.err()).flatten();
             }
             self.done_importing = true;
+            self.cancelled = cancelled;
             self.total_mails = written;
         }
 
@@ -194,7 +194,7 @@ impl ImporterUI {
             .draw_background(ui, available);
         }
 
-        let desired_height = 370.0 - (220.0 * growth) as f32;
+        let desired_height = 400.0 - (220.0 * growth) as f32;
         let desired_size = egui::vec2(330.0, desired_height);
 
         let paint_rect = Rect::from_min_size(
@@ -256,6 +256,18 @@ impl ImporterUI {
                 ui.add_space(20.0);
             }
             ui.small(label);
+            ui.add_space(10.0);
+            let cancelling = self.cancel.is_cancelled();
+            ui.add_enabled_ui(!cancelling, |ui| {
+                let text = if cancelling {
+                    "Cancelling..."
+                } else {
+                    "Cancel"
+                };
+                if ui.button(text).clicked() {
+                    self.cancel.cancel();
+                }
+            });
         })
         .response
     }
@@ -297,6 +309,7 @@ struct InternalAdapterState {
     writing: bool,
     written: usize,
     done: bool,
+    cancelled: bool,
     #[cfg(target_os = "macos")]
     missing_permissions: bool,
 }
@@ -306,14 +319,25 @@ impl ImporterUI {
     fn handle_adapter(&mut self) -> Result<InternalAdapterState> {
         let (mut label, progress, writing) = {
             let write = self.adapter.write_count()?;
-            if write.count > 0 {
+            let read = self.adapter.read_count()?;
+            if write.count > 0 && write.total > 0 {
                 (
                     format!("\rParsing emails {}/{}...", write.count, write.total),
                     (write.count as f32 / write.total as f32),
                     true,
                 )
+            } else if write.count > 0 {
+                // The emails are parsed while they're read, so until the format
+                // can estimate the total we only know how far reading is
+                (
+                    format!(
+                        "Reading emails {}/{}, parsed {}...",
+                        read.count, read.total, write.count
+                    ),
+                    (read.count as f32 / read.total as f32),
+                    false,
+                )
             } else {
-                let read = self.adapter.read_count()?;
                 (
                     format!("Reading emails {}/{}...", read.count, read.total),
                     (read.count as f32 / read.total as f32),
@@ -324,6 +348,7 @@ impl ImporterUI {
 
         let State {
             done,
+            cancelled,
             finishing,
             written,
             #[cfg(target_os = "macos")]
@@ -339,6 +364,7 @@ impl ImporterUI {
             writing,
             written,
             done,
+            cancelled,
             #[cfg(target_os = "macos")]
             missing_permissions,
         })
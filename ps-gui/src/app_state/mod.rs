@@ -3,6 +3,7 @@ mod import;
 mod main;
 mod startup;
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 pub use super::textures::Textures;
@@ -13,18 +14,28 @@ pub use main::{MainUI, UIState};
 use ps_core::eyre::Report;
 pub use startup::StartupUI;
 
-use ps_core::{Config, DatabaseLike, FormatType};
+use ps_core::{Config, DatabaseLike, DisplayTimezone, Identity, Source};
 
 pub enum StateUIAction {
     CreateDatabase {
         database_path: Option<PathBuf>,
-        emails_folder_path: PathBuf,
-        sender_emails: Vec<String>,
-        format: FormatType,
+        /// The folders (or servers) to import into the database
+        sources: Vec<Source>,
+        /// The addresses of the user, to find the mails they sent
+        identities: Vec<Identity>,
+        /// The time zone for the dates of the mails
+        timezone: DisplayTimezone,
     },
     OpenDatabase {
         database_path: PathBuf,
     },
+    /// Import the mails that are new since the database was created
+    UpdateDatabase {
+        database_path: PathBuf,
+        /// The passwords of the servers that were entered in the form, by the
+        /// name of their source, as they're not stored in the database
+        imap_passwords: HashMap<String, String>,
+    },
     ImportDone {
         config: Config,
         total: usize,
@@ -72,20 +83,20 @@ impl StateUI {
         match response {
             StateUIAction::CreateDatabase {
                 database_path,
-                emails_folder_path,
-                sender_emails,
-                format,
+                sources,
+                identities,
+                timezone,
             } => {
-                *self = self.create_database::<Database>(
-                    database_path,
-                    emails_folder_path,
-                    sender_emails,
-                    format,
-                )
+                *self =
+                    self.create_database::<Database>(database_path, sources, identities, timezone)
             }
             StateUIAction::OpenDatabase { database_path } => {
                 *self = self.open_database::<Database>(database_path)
             }
+            StateUIAction::UpdateDatabase {
+                database_path,
+                imap_passwords,
+            } => *self = self.update_database::<Database>(database_path, imap_passwords),
             StateUIAction::ImportDone { config, total } => {
                 *self = match main::MainUI::new::<Database>(config.clone(), total) {
                     Ok(n) => StateUI::Main(n),
@@ -117,12 +128,12 @@ impl StateUI {
     pub fn create_database<Database: DatabaseLike>(
         &self,
         database_path: Option<PathBuf>,
-        emails_folder_path: PathBuf,
-        sender_emails: Vec<String>,
-        format: FormatType,
+        sources: Vec<Source>,
+        identities: Vec<Identity>,
+        timezone: DisplayTimezone,
     ) -> StateUI {
-        let config = match Config::new(database_path, emails_folder_path, sender_emails, format) {
-            Ok(n) => n,
+        let config = match Config::with_sources(database_path, sources, identities) {
+            Ok(n) => Config { timezone, ..n },
             Err(e) => {
                 return StateUI::Error(error::ErrorUI::new(e, None));
             }
@@ -153,6 +164,32 @@ impl StateUI {
         }
     }
 
+    pub fn update_database<Database: DatabaseLike>(
+        &self,
+        database_path: PathBuf,
+        imap_passwords: HashMap<String, String>,
+    ) -> StateUI {
+        let mut config = match Database::config(&database_path) {
+            Ok(config) => config,
+            Err(report) => return StateUI::Error(error::ErrorUI::new(report, None)),
+        };
+        for source in config.sources.iter_mut() {
+            if let (Some(imap), Some(password)) =
+                (source.imap.as_mut(), imap_passwords.get(&source.name()))
+            {
+                imap.password = password.clone();
+            }
+        }
+
+        // The importer skips the mails that are already in the database
+        let database = match Database::new(&config.database_path) {
+            Ok(database) => database,
+            Err(report) => return StateUI::Error(error::ErrorUI::new(report, Some(config))),
+        };
+
+        self.importer_with_config(config, database)
+    }
+
     fn importer_with_config<Database: DatabaseLike>(
         &self,
         config: Config,
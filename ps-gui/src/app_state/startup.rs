@@ -9,21 +9,34 @@ use super::super::platform::platform_colors;
 use super::super::widgets::background::{shadow_background, AnimatedBackground};
 use super::Textures;
 use super::{StateUIAction, StateUIVariant};
-use ps_core::{Config, FormatType};
+use ps_core::crossbeam_channel::{Receiver, TryRecvError};
+use ps_core::{Config, DisplayTimezone, FormatType, Identity, ImapConfig, Source};
 
 #[derive(Default)]
 pub struct StartupUI {
+    /// The sources that were added before the one in the form.
+    /// All of them are imported into the same database.
+    sources: Vec<Source>,
     /// Which importer format are we using
     format: FormatType,
+    /// Why the format can't be imported, e.g. a program it needs is missing
+    format_error: Option<String>,
     /// Where are the emails located
     email_folder: Option<PathBuf>,
+    /// The formats that were detected in `email_folder`, once
+    /// the detection (which runs in the background) is done
+    detection: Option<Receiver<Vec<(FormatType, f32)>>>,
+    /// The server to import from if the format is `Imap`
+    imap: ImapConfig,
     /// Should we keep them in memory,
     /// or save them to disk, to this location
     database_path: Option<PathBuf>,
     /// Should we save to disk as a flag
     save_to_disk: bool,
-    /// The email address of the user
+    /// The email addresses (identities) of the user
     email_address: Option<String>,
+    /// The time zone for the year, month, day, hour and weekday of the mails
+    timezone: DisplayTimezone,
     /// time counter
     timer: f64,
     /// recursive offset counter
@@ -36,9 +49,8 @@ pub struct StartupUI {
 
 impl StartupUI {
     pub fn from_config(config: Config) -> Self {
-        let emails = if !config.sender_emails.is_empty() {
-            let mails: Vec<String> = config.sender_emails.iter().map(|e| e.to_owned()).collect();
-            Some(mails.join(", "))
+        let emails = if !config.identities.is_empty() {
+            Some(Identity::format_list(&config.identities))
         } else {
             None
         };
@@ -48,14 +60,22 @@ impl StartupUI {
             true => (true, Some(config.database_path)),
             false => (false, None),
         };
-        Self {
-            format: config.format,
-            email_folder: Some(config.emails_folder_path),
+        // The last source is shown in the form, the others above it
+        let mut sources = config.sources;
+        let current = sources.pop();
+        let mut startup = Self {
+            sources,
+            format: current.as_ref().map(|e| e.format).unwrap_or_default(),
+            email_folder: current.as_ref().map(|e| e.emails_folder_path.clone()),
+            imap: current.and_then(|e| e.imap).unwrap_or_default(),
             database_path,
             save_to_disk,
             email_address: emails,
+            timezone: config.timezone,
             ..Default::default()
-        }
+        };
+        startup.check_format();
+        startup
     }
 }
 
@@ -78,6 +98,7 @@ impl StateUIVariant for StartupUI {
 impl StartupUI {
     /// Separated to have a less stuff happening
     fn ui(&mut self, ui: &mut egui::Ui) -> Response {
+        self.poll_detection();
         let available = ui.available_size();
 
         AnimatedBackground {
@@ -94,7 +115,13 @@ impl StartupUI {
         // `ui.allocate_exact_size`
         // `ui.allocate_with_layout`
         // and variations. This, at least, worked.
-        let desired_size = egui::vec2(450.0, 370.0);
+        let desired_size = match self.format {
+            // The server settings need more space
+            FormatType::Imap => egui::vec2(450.0, 575.0),
+            _ => egui::vec2(450.0, 475.0),
+        };
+        // And so does every additional source
+        let desired_size = desired_size + egui::vec2(0.0, 27.0 * self.sources.len() as f32);
 
         let paint_rect = Rect::from_min_size(
             Pos2 {
@@ -133,6 +160,8 @@ impl StartupUI {
             egui::Grid::new("filter_grid")
                 .spacing(vec2(15.0, 12.0))
                 .show(ui, |ui| {
+                    self.added_sources(ui);
+
                     ui.add(
                         egui::widgets::Label::new("Choose Import Format:")
                             .text_color(colors.text_primary)
@@ -143,28 +172,62 @@ impl StartupUI {
                     self.format_selection(ui, center.width() * 0.7);
                     ui.end_row();
 
-                    ui.add(
-                        egui::widgets::Label::new("Email Folder:")
-                            .text_color(platform_colors().text_primary)
-                            .text_style(TextStyle::Body),
-                    );
-                    ui.end_row();
+                    if let Some(ref e) = self.format_error {
+                        ui.colored_label(Color32::from_rgb(255, 0, 0), e);
+                        ui.end_row();
+                    }
 
-                    ui.horizontal(|ui| {
-                        if ui.button("Browse...").clicked() {
-                            self.open_email_folder_dialog()
-                        }
-                        if self.format == FormatType::AppleMail && ui.button("or Mail.app default folder").clicked(){
+                    if self.detection.is_some() {
+                        ui.add(
+                            egui::widgets::Label::new("Looking for the format of the mails...")
+                                .text_color(colors.text_secondary)
+                                .text_style(TextStyle::Small),
+                        );
+                        ui.end_row();
+                    }
+
+                    if self.format == FormatType::Imap {
+                        self.imap_settings(ui);
+                    } else {
+                        ui.add(
+                            egui::widgets::Label::new("Email Folder:")
+                                .text_color(platform_colors().text_primary)
+                                .text_style(TextStyle::Body),
+                        );
+                        ui.end_row();
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Browse...").clicked() {
+                                self.open_email_folder_dialog()
+                            }
+                            if self.format.supports_archives() && ui.button("or Archive...").clicked() {
+                                self.open_archive_dialog()
+                            }
+                            if self.format == FormatType::AppleMail && ui.button("or Mail.app default folder").clicked(){
 
-                            self.set_default_path();
+                                self.set_default_path();
+                            }
+                            if self.format == FormatType::Thunderbird && ui.button("or default Thunderbird profile").clicked(){
+                                self.set_default_path();
+                            }
+                            if self.format == FormatType::Notmuch && ui.button("or mail root from notmuch config")
+                                .on_hover_text("The database.path in ~/.notmuch-config\n(or $NOTMUCH_CONFIG), ~/mail without one")
+                                .clicked(){
+                                self.set_default_path();
+                            }
+                        });
+                        ui.end_row();
+                        if let Some(n) = self.email_folder.as_ref() {
+                            let label = egui::widgets::Label::new(format!("{}", n.display()))
+                                 .text_color(hyperlink_color);
+                            ui.add(label)
+                                .on_hover_text(format!("{}", self.email_folder.as_ref().unwrap().display()));
                         }
-                    });
-                    ui.end_row();
-                    if let Some(n) = self.email_folder.as_ref() {
-                        let label = egui::widgets::Label::new(format!("{}", n.display()))
-                             .text_color(hyperlink_color);
-                        ui.add(label)
-                            .on_hover_text(format!("{}", self.email_folder.as_ref().unwrap().display()));
+                        ui.end_row();
+                    }
+
+                    if ui.button("Add Another Source").on_hover_text("Import the mails of several folders\nor servers into one database").clicked() {
+                        self.action_add_source();
                     }
                     ui.end_row();
 
@@ -179,7 +242,7 @@ impl StartupUI {
                     }
 
                     ui.small_button("?")
-                        .on_hover_text("Multiple addresses can be\nseparated by comma (,)");
+                        .on_hover_text("Multiple addresses can be\nseparated by comma (,)\n\nUse * as a wildcard: *@company.com\n\nName them and separate them by ;\nto split the mails by identity:\nWork: *@company.com; Private: me@gmail.com");
                     ui.end_row();
 
                     ui.add(
@@ -189,6 +252,9 @@ impl StartupUI {
                     );
                     ui.end_row();
 
+                    self.timezone_selection(ui);
+                    ui.end_row();
+
                     ui.checkbox(&mut self.save_to_disk, "Save Imported Output Database?");
                     ui.small_button("?").on_hover_text(
                         "Save the database generated\nduring import. It can be opened\nwith the \"Open Database\" \nbutton below",
@@ -214,11 +280,12 @@ impl StartupUI {
                     let button_size2: Vec2 = ((center.width() / 2.0) - 25.0, 25.0).into();
                     ui.horizontal(|ui| {
                         let enabled = {
-                                // if we have an email folder,
+                                // if we have an email folder (or server),
                                 // and - if we want to save to disk -
                                 // if we have a database path
-                                self.email_folder.is_some() &&
-                                (self.save_to_disk == self.database_path.is_some())
+                                (self.email_folder.is_some() || self.format == FormatType::Imap || !self.sources.is_empty()) &&
+                                (self.save_to_disk == self.database_path.is_some()) &&
+                                self.format_error.is_none()
                         };
                         ui.add_enabled_ui(enabled, |ui| {
                             let response = ui.add_sized(
@@ -235,6 +302,12 @@ impl StartupUI {
                         }
                     });
                     ui.end_row();
+                    let response = ui.add_sized(button_size2, egui::Button::new("Or Update Database"))
+                        .on_hover_text("Add the new mails to an existing database");
+                    if response.clicked() {
+                        self.action_update_database();
+                    }
+                    ui.end_row();
                     if let Some(ref e) = self.error_message {
                         let r = Color32::from_rgb(255, 0, 0);
                         ui.colored_label(r, e);
@@ -245,6 +318,66 @@ impl StartupUI {
         response.response
     }
 
+    /// The sources that were added before, with a button to remove them
+    fn added_sources(&mut self, ui: &mut egui::Ui) {
+        let colors = platform_colors();
+        let mut removed = None;
+        for (index, source) in self.sources.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("x").on_hover_text("Remove").clicked() {
+                    removed = Some(index);
+                }
+                ui.add(
+                    egui::widgets::Label::new(format!(
+                        "{}: {}",
+                        source.format.name(),
+                        source.name()
+                    ))
+                    .text_color(colors.text_secondary),
+                );
+            });
+            ui.end_row();
+        }
+        if let Some(index) = removed {
+            self.sources.remove(index);
+        }
+    }
+
+    fn imap_settings(&mut self, ui: &mut egui::Ui) {
+        let colors = platform_colors();
+        ui.add(
+            egui::widgets::Label::new("IMAP Server:")
+                .text_color(colors.text_primary)
+                .text_style(TextStyle::Body),
+        );
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.imap.host).hint_text("imap.example.org"));
+            ui.add(egui::DragValue::new(&mut self.imap.port).prefix("Port: "));
+            if ui.checkbox(&mut self.imap.tls, "TLS").changed() {
+                self.imap.port = match self.imap.tls {
+                    true => ImapConfig::TLS_PORT,
+                    false => 143,
+                };
+            }
+        });
+        ui.end_row();
+
+        ui.add(egui::TextEdit::singleline(&mut self.imap.user).hint_text("User"));
+        ui.end_row();
+
+        ui.add(
+            egui::TextEdit::singleline(&mut self.imap.password)
+                .password(true)
+                .hint_text("Password or App Token"),
+        );
+        ui.small_button("?").on_hover_text(
+            "The password is only used for the import.\nIt is not saved in the database",
+        );
+        ui.end_row();
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn set_default_path(&mut self) {
         self.email_folder = ps_importer::default_path(&self.format);
@@ -252,27 +385,89 @@ impl StartupUI {
 
     #[cfg(target_arch = "wasm32")]
     fn set_default_path(&mut self) {}
+
+    /// Outlook files can only be read if `readpst` is installed,
+    /// which is checked once the format is selected.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn check_format(&mut self) {
+        self.format_error = ps_importer::check_requirements(&self.format)
+            .err()
+            .map(|e| e.to_string());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn check_format(&mut self) {}
+
+    /// Can `format` be imported, i.e. is everything it needs installed?
+    #[cfg(not(target_arch = "wasm32"))]
+    fn is_usable(format: &FormatType) -> bool {
+        ps_importer::check_requirements(format).is_ok()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn is_usable(_format: &FormatType) -> bool {
+        true
+    }
 }
 
 impl StartupUI {
-    fn action_start(&mut self) {
+    /// The source that is configured in the form
+    fn current_source(&self) -> Result<Source, String> {
+        if self.format == FormatType::Imap {
+            if self.imap.host.is_empty() || self.imap.user.is_empty() {
+                return Err("Please enter the server and user".into());
+            }
+            // There's no folder for a server, so we use its address instead
+            let address = format!(
+                "imap://{}@{}:{}",
+                self.imap.user, self.imap.host, self.imap.port
+            );
+            return Ok(Source {
+                imap: Some(self.imap.clone()),
+                ..Source::new(address, self.format)
+            });
+        }
+
         let email = match &self.email_folder {
-            Some(n) => n.clone(),
-            _ => return,
+            Some(n) => n,
+            None => return Err("Please select an email folder".into()),
         };
+        if !email.exists() {
+            return Err("Email folder doesn't exist".into());
+        }
+        Ok(Source::new(email, self.format))
+    }
 
-        // Split by comma, remove whitespace
-        let emails: Vec<String> = self
-            .email_address
-            .iter()
-            .map(|e| e.split(',').map(|e| e.trim().to_string()).collect())
-            .collect();
+    fn action_add_source(&mut self) {
+        match self.current_source() {
+            Ok(source) => {
+                self.sources.push(source);
+                self.email_folder = None;
+                self.detection = None;
+                self.error_message = None;
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
 
-        if !email.exists() {
-            self.error_message = Some("Email folder doesn't exist".into());
-            return;
+    fn action_start(&mut self) {
+        let mut sources = self.sources.clone();
+        match self.current_source() {
+            Ok(source) => sources.push(source),
+            // The form can be left empty if sources were added before
+            Err(_) if self.email_folder.is_none() && !sources.is_empty() => (),
+            Err(e) => {
+                self.error_message = Some(e);
+                return;
+            }
         }
 
+        let identities = self
+            .email_address
+            .as_deref()
+            .map(Identity::parse_list)
+            .unwrap_or_default();
+
         if self.save_to_disk && self.database_path.is_none() {
             self.error_message = Some("Please select a database folder".into());
             return;
@@ -280,9 +475,9 @@ impl StartupUI {
 
         self.action = Some(StateUIAction::CreateDatabase {
             database_path: self.database_path.clone(),
-            emails_folder_path: email,
-            sender_emails: emails,
-            format: self.format,
+            sources,
+            identities,
+            timezone: self.timezone,
         });
     }
 
@@ -296,6 +491,27 @@ impl StartupUI {
         });
     }
 
+    fn action_update_database(&mut self) {
+        let path = match self.open_database_dialog() {
+            Some(n) => n,
+            None => return,
+        };
+        // The IMAP passwords are not stored, so we use the ones of the
+        // servers that were entered (or added) in the form
+        let current = self.current_source().ok();
+        let imap_passwords = self
+            .sources
+            .iter()
+            .chain(current.iter())
+            .filter_map(|source| Some((source.name(), source.imap.as_ref()?.password.clone())))
+            .filter(|(_, password)| !password.is_empty())
+            .collect();
+        self.action = Some(StateUIAction::UpdateDatabase {
+            database_path: path,
+            imap_passwords,
+        });
+    }
+
     fn format_selection(&mut self, ui: &mut egui::Ui, width: f32) {
         let mut selected = self.format;
         egui::ComboBox::from_id_source("mailbox_type_combobox")
@@ -303,10 +519,63 @@ impl StartupUI {
             .selected_text(selected.name())
             .show_ui(ui, |ui| {
                 for format in FormatType::all_cases() {
+                    // Notmuch can only be imported if it was built with libnotmuch
+                    if !cfg!(feature = "notmuch") && format == FormatType::Notmuch {
+                        continue;
+                    }
                     ui.selectable_value(&mut selected, format, format.name());
                 }
             });
-        self.format = selected;
+        if selected != self.format {
+            self.format = selected;
+            // The format the user chose wins over the one we detect
+            self.detection = None;
+            self.check_format();
+        }
+    }
+
+    /// The time zone of the computer, of each sender or a fixed offset from UTC
+    fn timezone_selection(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Time Zone:");
+            let name = match self.timezone {
+                DisplayTimezone::Local => "This Computer",
+                DisplayTimezone::Sender => "Each Sender",
+                DisplayTimezone::Offset(_) => "Fixed Offset",
+            };
+            let offset = match self.timezone {
+                DisplayTimezone::Offset(n) => DisplayTimezone::Offset(n),
+                _ => DisplayTimezone::Offset(0),
+            };
+            egui::ComboBox::from_id_source("timezone_combobox")
+                .selected_text(name)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.timezone,
+                        DisplayTimezone::Local,
+                        "This Computer",
+                    );
+                    ui.selectable_value(&mut self.timezone, DisplayTimezone::Sender, "Each Sender");
+                    ui.selectable_value(&mut self.timezone, offset, "Fixed Offset");
+                });
+            if let DisplayTimezone::Offset(seconds) = &mut self.timezone {
+                // Edited in hours, in steps of 15 minutes
+                let mut hours = *seconds as f32 / 3600.0;
+                let response = ui.add(
+                    egui::DragValue::new(&mut hours)
+                        .speed(0.25)
+                        .clamp_range(-12.0..=14.0)
+                        .prefix("UTC ")
+                        .suffix(" h"),
+                );
+                if response.changed() {
+                    *seconds = ((hours * 4.0).round() * 900.0) as i32;
+                }
+            }
+        });
+        ui.small_button("?").on_hover_text(
+            "The year, month, day, hour and weekday\nof the mails are in this time zone",
+        );
     }
 
     #[cfg(target_arch = "wasm32")]
@@ -330,9 +599,70 @@ impl StartupUI {
             Some(path) => path,
             None => return,
         };
+        self.select_email_folder(path);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn open_archive_dialog(&mut self) {}
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_archive_dialog(&mut self) {
+        let fallback = shellexpand::tilde("~/").to_string();
+
+        let filename = match tinyfiledialogs::open_file_dialog(
+            "Select archive",
+            &fallback,
+            Some((
+                &[
+                    "*.zip", "*.tar", "*.tar.gz", "*.tgz", "*.tar.xz", "*.txz", "*.gz", "*.xz",
+                ],
+                "Archives",
+            )),
+        ) {
+            Some(result) => PathBuf::from_str(&result).ok(),
+            None => return,
+        };
+
+        let path = match filename {
+            Some(path) => path,
+            None => return,
+        };
+        self.select_email_folder(path);
+    }
+
+    /// Use the folder (or archive) at `path` and preselect the format
+    /// that we detected in there, so that users don't end up with an
+    /// empty import because they picked the wrong format.
+    /// The detection looks into up to thousands of files (or reads through
+    /// an archive), so it runs in the background.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn select_email_folder(&mut self, path: PathBuf) {
+        let (sender, receiver) = ps_core::crossbeam_channel::bounded(1);
+        let detect_path = path.clone();
+        std::thread::spawn(move || {
+            // Fails if another folder was selected in the meantime
+            let _ = sender.send(ps_importer::detect_format(&detect_path));
+        });
+        self.detection = Some(receiver);
         self.email_folder = Some(path);
     }
 
+    /// Preselect the detected format once the detection is done. Formats that
+    /// can't be imported (e.g. notmuch without `libnotmuch`) are skipped in favour
+    /// of the next one (e.g. the Maildir the notmuch database indexes).
+    fn poll_detection(&mut self) {
+        let formats = match self.detection.as_ref().map(|e| e.try_recv()) {
+            None | Some(Err(TryRecvError::Empty)) => return,
+            Some(Err(TryRecvError::Disconnected)) => Vec::new(),
+            Some(Ok(n)) => n,
+        };
+        self.detection = None;
+        if let Some((format, _)) = formats.iter().find(|(e, _)| Self::is_usable(e)) {
+            self.format = *format;
+            self.check_format();
+        }
+    }
+
     #[cfg(target_arch = "wasm32")]
     fn save_database_dialog(&mut self) {}
 
@@ -1,4 +1,3 @@
-use chrono::prelude::*;
 use eframe::egui::{self, Widget};
 use ps_core::eyre::Report;
 use ps_core::{model::items, model::Engine, Field, QueryRow};
@@ -64,11 +63,14 @@ impl<'a> Widget for MailPanel<'a> {
                         Some(n) => n,
                         None => return "".to_owned(),
                     };
-                    if let Some(value) = sample[&Field::Timestamp].value().as_i64() {
-                        let dt = Utc.timestamp(value, 0);
-                        dt.format("%Y-%m-%d").to_string()
-                    } else {
-                        String::new()
+                    // The date in the time zone of the config
+                    let date = [Field::Year, Field::Month, Field::Day]
+                        .map(|field| sample[&field].value().as_i64());
+                    match date {
+                        [Some(year), Some(month), Some(day)] => {
+                            format!("{:04}-{:02}-{:02}", year, month, day)
+                        }
+                        _ => String::new(),
                     }
                 })
                 .column("Subject", 400.0, |sample| {
@@ -69,6 +69,37 @@ impl<'a> Widget for NavigationBar<'a> {
                 self.total_mails.to_formatted_string(&Locale::en)
             )));
 
+            let duplicates = self.engine.duplicates();
+            if duplicates > 0 {
+                ui.add(Label::new(format!(
+                    "({} Duplicates)",
+                    duplicates.to_formatted_string(&Locale::en)
+                )))
+                .on_hover_text("Mails with the same Message-ID as another mail");
+            }
+
+            let partial = self.engine.partial();
+            if partial > 0 {
+                ui.add(Label::new(format!(
+                    "({} Partially Parsed)",
+                    partial.to_formatted_string(&Locale::en)
+                )))
+                .on_hover_text(
+                    "Mails with malformed headers.\nGroup by \"Failed headers\" to see which",
+                );
+            }
+
+            let outdated = self.engine.outdated();
+            if outdated > 0 {
+                ui.add(Label::new(format!(
+                    "({} From an Older Version)",
+                    outdated.to_formatted_string(&Locale::en)
+                )))
+                .on_hover_text(
+                    "Mails imported by an older version of Postsack. Their attachments are unknown\nand their text can't be searched. Import them into a new database to add these",
+                );
+            }
+
             // This is a hack to get right-alignment.
             // we can't size the button, we can only size text. We will size text
             // and then use ~that for these buttons
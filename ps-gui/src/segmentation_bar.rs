@@ -1,6 +1,7 @@
 use eframe::egui::{self, Widget};
 use ps_core::eyre::Report;
 use ps_core::model::{segmentations, Engine};
+use ps_core::Metric;
 
 pub struct SegmentationBar<'a> {
     engine: &'a mut Engine,
@@ -51,6 +52,21 @@ impl<'a> Widget for SegmentationBar<'a> {
             if has_back && ui.button("\u{2716}").clicked() {
                 self.engine.pop();
             }
+
+            ui.separator();
+            ui.label("Weigh By:");
+            let metrics = Metric::all();
+            let current = segmentations::metric(self.engine);
+            let mut selected = metrics.iter().position(|e| *e == current).unwrap_or(0);
+            let response = egui::ComboBox::from_id_source("metric").show_index(
+                ui,
+                &mut selected,
+                metrics.len(),
+                |i| metrics[i].name().to_string(),
+            );
+            if response.changed() {
+                *self.error = segmentations::set_metric(self.engine, metrics[selected]).err();
+            }
         })
         .response
     }
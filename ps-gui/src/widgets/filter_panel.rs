@@ -17,7 +17,14 @@ pub struct FilterState {
     is_send: Option<bool>,
     is_reply: Option<bool>,
     is_seen: Option<bool>,
+    /// Yes: Only mailing lists and newsletters, No: Only personal mails, None: Any
+    is_bulk: Option<bool>,
+    /// Yes: Only mails the user replied to, No: Only unanswered mails, None: Any
+    is_answered: Option<bool>,
+    has_attachment: Option<bool>,
     subject_contains: Option<String>,
+    /// Words that have to be in the text of the mails
+    text_contains: Option<String>,
     tags_contains: Option<String>,
 }
 
@@ -41,6 +48,15 @@ impl FilterState {
         if let Some(val) = self.is_reply {
             filters.push(Filter::Is(ValueField::bool(&Field::IsReply, val)));
         }
+        if let Some(val) = self.is_bulk {
+            filters.push(Filter::Is(ValueField::bool(&Field::IsBulk, val)));
+        }
+        if let Some(val) = self.is_answered {
+            filters.push(Filter::Is(ValueField::bool(&Field::IsAnswered, val)));
+        }
+        if let Some(val) = self.has_attachment {
+            filters.push(Filter::Is(ValueField::bool(&Field::HasAttachment, val)));
+        }
         // FIXME: The system currently doesn't allow searching for multiple tags
         // (e.g. (x like tag1 or x like tag2))
         // this would require a `Filter::Expression` that is just added verbatim
@@ -56,6 +72,9 @@ impl FilterState {
                 n.clone(),
             )));
         }
+        if let Some(n) = &self.text_contains {
+            filters.push(Filter::FullText(n.clone()));
+        }
         *error = segmentations::set_filters(engine, &filters).err();
     }
 
@@ -63,7 +82,11 @@ impl FilterState {
         self.is_send = None;
         self.is_reply = None;
         self.is_seen = None;
+        self.is_bulk = None;
+        self.is_answered = None;
+        self.has_attachment = None;
         self.subject_contains = None;
+        self.text_contains = None;
         self.tags_contains = None;
     }
 }
@@ -147,6 +170,12 @@ impl FilterPanel<'_> {
 
                                 input_block(ui, "Subject", &mut state.subject_contains);
                                 ui.end_row();
+
+                                input_block(ui, "Contents", &mut state.text_contains)
+                                    .on_hover_text(
+                                        "Words in the text of the mails. The text of IMAP mails is not downloaded,\nso they can only be found by their subject",
+                                    );
+                                ui.end_row();
                             });
                         ui.end_row();
 
@@ -176,6 +205,30 @@ impl FilterPanel<'_> {
                         );
                         ui.end_row();
 
+                        radio_group(
+                            ui,
+                            "Mailing Lists",
+                            &["Only Lists", "No Lists", "All"],
+                            &mut state.is_bulk,
+                        );
+                        ui.end_row();
+
+                        radio_group(
+                            ui,
+                            "Answered by Me",
+                            &["Yes", "No", "All"],
+                            &mut state.is_answered,
+                        );
+                        ui.end_row();
+
+                        radio_group(
+                            ui,
+                            "Attachments",
+                            &["With Attachments", "Without Attachments", "All"],
+                            &mut state.has_attachment,
+                        );
+                        ui.end_row();
+
                         if engine.format_has_seen() {
                             radio_group(ui, "Only Seen", &["Yes", "No", "All"], &mut state.is_seen);
                             ui.end_row();
@@ -213,14 +266,16 @@ fn radio_group(ui: &mut egui::Ui, title: &str, names: &[&str; 3], value: &mut Op
     *value = output_value;
 }
 
-fn input_block(ui: &mut egui::Ui, title: &str, value: &mut Option<String>) {
+/// Returns the `Response` of the label
+fn input_block(ui: &mut egui::Ui, title: &str, value: &mut Option<String>) -> Response {
     let mut text_value = value.clone().unwrap_or_else(|| "".to_string());
-    ui.label(title);
+    let label = ui.label(title);
     ui.text_edit_singleline(&mut text_value);
     match text_value.as_str() {
         "" => *value = None,
         _ => *value = Some(text_value),
     }
+    label
 }
 
 fn input_tags(
@@ -4,6 +4,7 @@ use eframe::egui::{self, epaint::Galley, Color32, Pos2, Rect, Rgba, Stroke, Text
 use num_format::{Locale, ToFormattedString};
 use ps_core::eyre::Report;
 use ps_core::model::{self, segmentations, Engine, Segment};
+use ps_core::Metric;
 
 use super::super::platform::platform_colors;
 
@@ -38,6 +39,12 @@ impl<'a> Widget for Rectangles<'a> {
         };
 
         let active = segmentations::can_aggregate_more(self.engine);
+        let metric = self
+            .engine
+            .segmentations()
+            .last()
+            .map(|e| e.metric)
+            .unwrap_or_default();
 
         let colors = platform_colors();
 
@@ -46,14 +53,25 @@ impl<'a> Widget for Rectangles<'a> {
         for (index, item) in items.iter().enumerate() {
             let item_response = ui.put(
                 convert_rect_back(item.layout_rect()),
-                rectangle(item, active, colors.content_background, index, total),
+                rectangle(
+                    item,
+                    metric,
+                    active,
+                    colors.content_background,
+                    index,
+                    total,
+                ),
             );
             if item_response.clicked() && active {
                 *self.error = self.engine.push(item.clone()).err();
                 response.mark_changed();
             }
             if item_response.hovered() {
-                hovered = Some(format!("{}: #{}", item.field.to_string(), item.count));
+                let weight = match metric {
+                    Metric::Count => format!("#{}", item.weight),
+                    Metric::Size => format_size(item.weight),
+                };
+                hovered = Some(format!("{}: {}", item.field.to_string(), weight));
             }
         }
 
@@ -99,6 +117,7 @@ impl<'a> Widget for Rectangles<'a> {
 fn rectangle_ui(
     ui: &mut egui::Ui,
     segment: &Segment,
+    metric: Metric,
     active: bool,
     stroke_color: Color32,
     position: usize,
@@ -156,7 +175,10 @@ fn rectangle_ui(
         }
     }
     {
-        let text = segment.count.to_formatted_string(&Locale::en);
+        let text = match metric {
+            Metric::Count => segment.weight.to_formatted_string(&Locale::en),
+            Metric::Size => format_size(segment.weight),
+        };
         let galley = painter.layout_no_wrap(text, TextStyle::Small, Rgba::BLACK.into());
         if let Some(center) = align_bottom(&galley, &mut center, 5.0) {
             painter.galley(center, galley);
@@ -167,12 +189,30 @@ fn rectangle_ui(
 
 fn rectangle(
     segment: &Segment,
+    metric: Metric,
     active: bool,
     stroke_color: Color32,
     position: usize,
     total: usize,
 ) -> impl egui::Widget + '_ {
-    move |ui: &mut egui::Ui| rectangle_ui(ui, segment, active, stroke_color, position, total)
+    move |ui: &mut egui::Ui| {
+        rectangle_ui(ui, segment, metric, active, stroke_color, position, total)
+    }
+}
+
+/// A size in bytes with the largest unit that fits, e.g. `12.3 MB`
+fn format_size(bytes: usize) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
 }
 
 // Can't implement into / from as the trait is in another
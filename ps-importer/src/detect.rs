@@ -0,0 +1,243 @@
+//! Guess the format of the mails in a folder (or archive) by looking for the
+//! files and folders that are typical for each format: `.emlx` files for Apple
+//! Mail, `.eml` files with a `.meta` file for GMVault, `cur` / `new` / `tmp`
+//! folders for Maildir, files starting with `From ` for mbox and so on.
+//!
+//! Only a limited number of entries is inspected, so that this is fast enough
+//! to run whenever the user picks a folder.
+
+use walkdir::WalkDir;
+
+use ps_core::tracing;
+use ps_core::FormatType;
+
+use super::formats::shared::archive::{archive_members, is_archive};
+
+use std::io::Read;
+use std::path::Path;
+
+/// How deep we look into the folder. Apple Mail nests the `.emlx` files in
+/// `V8/<account>/INBOX.mbox/<uuid>/Data/0/1/Messages`
+const MAX_DEPTH: usize = 10;
+
+/// How many files and folders we look at at most
+const MAX_ENTRIES: usize = 10_000;
+
+/// The folders that contain the actual mails in a Maildir
+const MAILDIR_FOLDERS: &[&str] = &["cur", "new", "tmp"];
+
+/// The markers we found for each of the formats
+#[derive(Debug, Default)]
+struct Markers {
+    /// `.emlx` files
+    emlx: usize,
+    /// `.eml` files with a GMVault `.meta` file next to them
+    gmvault: usize,
+    /// Maildir folders with a `cur` folder
+    maildir: usize,
+    /// The `.notmuch` database folder
+    notmuch: usize,
+    /// `.pst` and `.ost` files
+    outlook: usize,
+    /// Thunderbird's `.msf` index files and the `prefs.js` of a profile
+    thunderbird: usize,
+    /// Files that end in `.mbox` and look like an mbox
+    mbox: usize,
+    /// Files without an extension that start with `From `.
+    /// These are mbox files, but Thunderbird stores its folders like that, too.
+    unnamed_mbox: usize,
+}
+
+/// Guess the format of the mails at `path`.
+/// Returns the formats for which we found markers, together with a confidence
+/// between `0.0` and `1.0` (the share of the markers that point to the format),
+/// the most likely format first. If nothing was found, the result is empty.
+pub fn detect_format<P: AsRef<Path>>(path: P) -> Vec<(FormatType, f32)> {
+    let path = path.as_ref();
+    let mut markers = Markers::default();
+    if is_archive(path) {
+        inspect_archive(path, &mut markers);
+    } else if path.is_file() {
+        inspect_file(path, &mut markers);
+    } else {
+        inspect_folder(path, &mut markers);
+    }
+    tracing::trace!("Markers for {}: {:?}", path.display(), &markers);
+    markers.confidences()
+}
+
+fn inspect_folder(root: &Path, markers: &mut Markers) {
+    if root.join(".notmuch").is_dir() {
+        markers.notmuch += 1;
+    }
+    if root.join("prefs.js").is_file() {
+        markers.thunderbird += 1;
+    }
+    for entry in WalkDir::new(root)
+        .max_depth(MAX_DEPTH)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".notmuch")
+        .take(MAX_ENTRIES)
+    {
+        let entry = match entry {
+            Ok(n) => n,
+            Err(e) => {
+                tracing::info!("Could not read folder: {}", e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if path.file_name().map(|e| e == "cur").unwrap_or(false) {
+                markers.maildir += 1;
+            }
+            continue;
+        }
+        inspect_file(path, markers);
+    }
+}
+
+fn inspect_file(path: &Path, markers: &mut Markers) {
+    let name = match path.file_name().and_then(|e| e.to_str()) {
+        Some(n) => n.to_lowercase(),
+        None => return,
+    };
+    if name.ends_with(".emlx") {
+        markers.emlx += 1;
+    } else if name.ends_with(".pst") || name.ends_with(".ost") {
+        markers.outlook += 1;
+    } else if name.ends_with(".msf") {
+        markers.thunderbird += 1;
+    } else if let Some(stem) = original_stem(path, &name) {
+        // GMVault stores the labels in a `.meta` file next to the mail
+        if path.with_file_name(format!("{}.meta", stem)).is_file() {
+            markers.gmvault += 1;
+        }
+    } else if name.contains(".mbox") {
+        if starts_with_from(path) {
+            markers.mbox += 1;
+        }
+    } else if !name.contains('.') && !is_in_maildir(path) && starts_with_from(path) {
+        markers.unnamed_mbox += 1;
+    }
+}
+
+/// We can't look into the files in an archive cheaply, so we only go by
+/// the names of the members.
+fn inspect_archive(archive: &Path, markers: &mut Markers) {
+    let members = match archive_members(archive, MAX_ENTRIES) {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!("Could not read archive at {}: {}", archive.display(), e);
+            return;
+        }
+    };
+    let has_meta = members
+        .iter()
+        .any(|e| e.extension().map(|e| e == "meta").unwrap_or(false));
+    for member in members {
+        let name = match member.file_name().and_then(|e| e.to_str()) {
+            Some(n) => n.to_lowercase(),
+            None => continue,
+        };
+        if is_in_maildir(&member) {
+            markers.maildir += 1;
+        } else if has_meta && (name.ends_with(".eml") || name.ends_with(".eml.gz")) {
+            markers.gmvault += 1;
+        } else if name.contains(".mbox") {
+            markers.mbox += 1;
+        }
+    }
+}
+
+/// The file name of an `.eml` or `.eml.gz` file without the extension.
+/// `name` is the lowercased file name.
+fn original_stem<'a>(path: &'a Path, name: &str) -> Option<&'a str> {
+    let extension = [".eml.gz", ".eml"]
+        .into_iter()
+        .find(|e| name.ends_with(e))?;
+    let original = path.file_name()?.to_str()?;
+    original.get(..original.len() - extension.len())
+}
+
+/// Is this a mail in the `cur`, `new` or `tmp` folder of a Maildir?
+fn is_in_maildir(path: &Path) -> bool {
+    path.parent()
+        .and_then(|e| e.file_name())
+        .and_then(|e| e.to_str())
+        .map(|e| MAILDIR_FOLDERS.contains(&e))
+        .unwrap_or(false)
+}
+
+/// Does the file at `path` start with an mbox `From ` line?
+/// Some mbox files have whitespace before the first line, so we skip that.
+fn starts_with_from(path: &Path) -> bool {
+    let mut buffer = Vec::with_capacity(512);
+    let file = match std::fs::File::open(path) {
+        Ok(n) => n,
+        Err(_) => return false,
+    };
+    if file.take(512).read_to_end(&mut buffer).is_err() {
+        return false;
+    }
+    let start = buffer
+        .iter()
+        .position(|e| !e.is_ascii_whitespace())
+        .unwrap_or(buffer.len());
+    buffer[start..].starts_with(b"From ")
+}
+
+impl Markers {
+    /// The markers that point to each format
+    fn counts(&self) -> Vec<(FormatType, usize)> {
+        let is_thunderbird = self.thunderbird > 0;
+        let is_notmuch = self.notmuch > 0;
+        vec![
+            (FormatType::AppleMail, self.emlx),
+            (FormatType::GmailVault, self.gmvault),
+            (FormatType::Outlook, self.outlook),
+            // The mails of a notmuch database are usually in a Maildir,
+            // so they count for both formats
+            (FormatType::Maildir, self.maildir),
+            (
+                FormatType::Notmuch,
+                if is_notmuch {
+                    self.notmuch + self.maildir
+                } else {
+                    0
+                },
+            ),
+            // Without any `.msf` files, mbox files without an extension
+            // are more likely to be plain mboxes
+            (
+                FormatType::Thunderbird,
+                if is_thunderbird {
+                    self.thunderbird + self.unnamed_mbox
+                } else {
+                    0
+                },
+            ),
+            (
+                FormatType::Mbox,
+                if is_thunderbird {
+                    self.mbox
+                } else {
+                    self.mbox + self.unnamed_mbox
+                },
+            ),
+        ]
+    }
+
+    fn confidences(&self) -> Vec<(FormatType, f32)> {
+        let counts: Vec<(FormatType, usize)> =
+            self.counts().into_iter().filter(|(_, n)| *n > 0).collect();
+        let total: usize = counts.iter().map(|(_, n)| n).sum();
+        let mut result: Vec<(FormatType, f32)> = counts
+            .into_iter()
+            .map(|(format, n)| (format, n as f32 / total as f32))
+            .collect();
+        // `sort_by` is stable, so equal confidences keep the order above
+        result.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        result
+    }
+}
@@ -5,17 +5,22 @@
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use super::super::shared::filesystem::emails_in;
+use super::super::shared::filesystem::{count_files, emails_in};
+use super::super::EmailSender;
 use ps_core::eyre::{eyre, Result};
 use ps_core::tracing;
-use ps_core::{Config, Message, MessageSender};
+use ps_core::{Message, MessageSender, Source};
 
 use super::mail::Mail;
 use std::path::PathBuf;
 
-pub fn read_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>> {
+pub fn read_emails(
+    source: &Source,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
     // on macOS, we might need permission for the `Library` folder...
-    match std::fs::read_dir(&config.emails_folder_path) {
+    match std::fs::read_dir(&source.emails_folder_path) {
         Ok(_) => (),
         Err(e) => match e.kind() {
             #[cfg(target_os = "macos")]
@@ -26,7 +31,7 @@ pub fn read_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>>
                 }
                 // We should return early now, otherwise the code below will send a different
                 // error
-                return Ok(Vec::new());
+                return Ok(());
             }
             _ => {
                 if let Err(e) = sender.send(Message::Error(eyre!("Error: {:?}", &e))) {
@@ -39,8 +44,9 @@ pub fn read_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>>
     // As `walkdir` does not support `par_iter` (see https://www.reddit.com/r/rust/comments/6eif7r/walkdir_users_we_need_you/)
     // - -we first collect all folders,
     // then all sub-folders in those ending in mboxending in .mbox and then iterate over them in paralell
-    let folders: Vec<PathBuf> = WalkDir::new(&config.emails_folder_path)
+    let folders: Vec<PathBuf> = WalkDir::new(&source.emails_folder_path)
         .into_iter()
+        .take_while(|_| !emails.is_cancelled())
         .filter_map(|e| match e {
             Ok(n)
                 if n.path().is_dir()
@@ -64,10 +70,14 @@ pub fn read_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>>
         })
         .collect();
     sender.send(Message::ReadTotal(folders.len()))?;
-    let mails: Vec<Mail> = folders
+    emails.estimate(folders.iter().map(|e| count_files(e, &[".emlx"])).sum());
+    folders
         .into_par_iter()
-        .filter_map(
-            |path| match emails_in(path.clone(), sender.clone(), Mail::new) {
+        .filter_map(|path| {
+            if emails.is_cancelled() {
+                return None;
+            }
+            match emails_in(path.clone(), sender.clone(), Mail::new) {
                 Ok(n) => Some(n),
                 Err(e) => {
                     tracing::error!("{} {:?}", path.display(), &e);
@@ -80,9 +90,9 @@ pub fn read_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>>
                     }
                     None
                 }
-            },
-        )
+            }
+        })
         .flatten()
-        .collect();
-    Ok(mails)
+        .try_for_each(|mail| emails.send(mail))?;
+    Ok(())
 }
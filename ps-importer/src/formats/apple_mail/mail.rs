@@ -64,6 +64,7 @@ impl ParseableEmail for Mail {
         let meta = EmailMeta {
             tags,
             is_seen: self.is_seen,
+            thread_id: None,
         };
         Ok(Some(meta))
     }
@@ -3,8 +3,8 @@ mod mail;
 
 use std::{path::PathBuf, str::FromStr};
 
-use super::{ImporterFormat, Result};
-use ps_core::{Config, MessageSender};
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::{MessageSender, Source};
 
 #[derive(Default)]
 pub struct AppleMail {}
@@ -17,7 +17,12 @@ impl ImporterFormat for AppleMail {
         Some(PathBuf::from_str(&path.to_string()).unwrap())
     }
 
-    fn emails(&self, config: &Config, sender: MessageSender) -> Result<Vec<Self::Item>> {
-        filesystem::read_emails(config, sender)
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        filesystem::read_emails(source, sender, emails)
     }
 }
@@ -30,6 +30,7 @@ impl From<Meta> for EmailMeta {
         EmailMeta {
             tags: meta.labels,
             is_seen,
+            thread_id: None,
         }
     }
 }
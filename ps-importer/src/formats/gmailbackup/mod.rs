@@ -1,10 +1,21 @@
 mod meta;
 mod raw_email;
 
-use super::shared::filesystem::{emails_in, folders_in};
-use super::{Config, ImporterFormat, MessageSender, Result};
+use flate2::read::GzDecoder;
+use ps_core::tracing;
+
+use super::shared::archive::{is_archive, progress_total, read_archive};
+use super::shared::filesystem::{count_files, emails_in, folders_in};
+use super::{EmailSender, ImporterFormat, Message, MessageSender, Result, Source};
 use raw_email::RawEmailEntry;
 
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The mails in a GMVault export, next to their `.meta` files
+const MAIL_SUFFIXES: &[&str] = &[".eml", ".eml.gz"];
+
 #[derive(Default)]
 pub struct Gmail {}
 
@@ -15,9 +26,84 @@ impl ImporterFormat for Gmail {
         None
     }
 
-    fn emails(&self, config: &Config, sender: MessageSender) -> Result<Vec<Self::Item>> {
-        folders_in(&config.emails_folder_path, sender, |path, sender| {
-            emails_in(path, sender, RawEmailEntry::new)
-        })
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        if is_archive(&source.emails_folder_path) {
+            return archived_emails(&source.emails_folder_path, sender, emails);
+        }
+        if let Ok(entries) = std::fs::read_dir(&source.emails_folder_path) {
+            emails.estimate(
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| count_files(e.path(), MAIL_SUFFIXES))
+                    .sum(),
+            );
+        }
+        folders_in(
+            &source.emails_folder_path,
+            sender,
+            emails,
+            |path, sender| emails_in(path, sender, RawEmailEntry::new),
+        )
     }
 }
+
+/// Read the mails in a zipped (or tarred) GMVault export
+fn archived_emails(
+    archive: &Path,
+    sender: MessageSender,
+    emails: EmailSender<RawEmailEntry>,
+) -> Result<()> {
+    // The `.meta` files can come before or after their mails in the archive.
+    // They are small, so they are collected by their path without the extension
+    // in a first pass. The mails are sent on one by one in a second pass.
+    sender.send(Message::ReadTotal(2 * progress_total(archive)))?;
+    let mut metas: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+    let mut total = 0;
+    read_archive(archive, &sender, |path, member| {
+        emails.check()?;
+        let name = file_name(&path).unwrap_or_default();
+        if let Some(stem) = name.strip_suffix(".meta") {
+            let mut data = Vec::new();
+            member.read_to_end(&mut data)?;
+            metas.insert(path.with_file_name(stem), data);
+        } else if MAIL_SUFFIXES.iter().any(|e| name.ends_with(e)) {
+            total += 1;
+        }
+        Ok(())
+    })?;
+    emails.estimate(total);
+    read_archive(archive, &sender, |path, member| {
+        emails.check()?;
+        let name = match file_name(&path) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        let mut eml = Vec::new();
+        let stem = if let Some(stem) = name.strip_suffix(".eml.gz") {
+            if let Err(e) = GzDecoder::new(member).read_to_end(&mut eml) {
+                tracing::error!("Could not decompress {}: {}", path.display(), e);
+                return Ok(());
+            }
+            stem
+        } else if let Some(stem) = name.strip_suffix(".eml") {
+            member.read_to_end(&mut eml)?;
+            stem
+        } else {
+            return Ok(());
+        };
+        let meta = metas.remove(&path.with_file_name(stem));
+        if let Some(entry) = RawEmailEntry::archived(archive.join(&path), eml, meta) {
+            emails.send(entry)?;
+        }
+        Ok(())
+    })
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name().and_then(|e| e.to_str()).map(str::to_owned)
+}
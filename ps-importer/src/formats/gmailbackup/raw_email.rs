@@ -19,8 +19,15 @@ pub struct RawEmailEntry {
     eml_path: PathBuf,
     gmail_meta_path: Option<PathBuf>,
     is_compressed: bool,
-    #[allow(unused)]
-    size: u64,
+    /// Mails that were read out of an archive already have their contents
+    archived: Option<Archived>,
+}
+
+/// The contents of a mail and its meta file from an archive
+#[derive(Debug)]
+struct Archived {
+    eml: Vec<u8>,
+    meta: Option<Vec<u8>>,
 }
 
 impl RawEmailEntry {
@@ -29,7 +36,9 @@ impl RawEmailEntry {
     }
 
     pub fn read(&self) -> Result<Vec<u8>> {
-        if self.is_compressed {
+        if let Some(archived) = &self.archived {
+            Ok(archived.eml.clone())
+        } else if self.is_compressed {
             let reader = std::fs::File::open(&self.eml_path)?;
             let mut decoder = GzDecoder::new(reader);
             let mut buffer = Vec::new();
@@ -41,10 +50,16 @@ impl RawEmailEntry {
     }
 
     pub fn has_gmail_meta(&self) -> bool {
-        self.gmail_meta_path.is_some()
+        match &self.archived {
+            Some(archived) => archived.meta.is_some(),
+            None => self.gmail_meta_path.is_some(),
+        }
     }
 
     pub fn read_gmail_meta(&self) -> Option<Result<Vec<u8>>> {
+        if let Some(archived) = &self.archived {
+            return archived.meta.clone().map(Ok);
+        }
         // Just using map here returns a `&Option` whereas we want `Option`
         #[allow(clippy::manual_map)]
         match &self.gmail_meta_path {
@@ -68,8 +83,6 @@ impl RawEmailEntry {
         let folder_name = path.parent()?.file_name()?.to_str()?.to_owned();
         let eml_path = path.to_path_buf();
 
-        let file_metadata = path.metadata().ok()?;
-
         // Build a meta path
         let meta_path = path
             .parent()?
@@ -92,7 +105,22 @@ impl RawEmailEntry {
             eml_path,
             gmail_meta_path,
             is_compressed,
-            size: file_metadata.len(),
+            archived: None,
+        })
+    }
+
+    /// A mail that was read out of an archive. `path` is the path of the mail within
+    /// the archive, `eml` the (uncompressed) mail and `meta` the contents of the
+    /// `.meta` file, if there was one.
+    pub(super) fn archived(path: PathBuf, eml: Vec<u8>, meta: Option<Vec<u8>>) -> Option<Self> {
+        let folder_name = path.parent()?.file_name()?.to_str()?.to_owned();
+        tracing::trace!("Archived Email {} {}", path.display(), meta.is_some());
+        Some(RawEmailEntry {
+            folder_name,
+            eml_path: path,
+            gmail_meta_path: None,
+            is_compressed: false,
+            archived: Some(Archived { eml, meta }),
         })
     }
 }
@@ -102,7 +130,10 @@ impl ParseableEmail for RawEmailEntry {
         Ok(())
     }
     fn message(&self) -> Result<Cow<'_, [u8]>> {
-        Ok(Cow::Owned(self.read()?))
+        match &self.archived {
+            Some(archived) => Ok(Cow::Borrowed(archived.eml.as_slice())),
+            None => Ok(Cow::Owned(self.read()?)),
+        }
     }
 
     fn path(&self) -> &Path {
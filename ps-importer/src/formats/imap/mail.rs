@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use ps_core::eyre::Result;
+use ps_core::{Attachment, EmailMeta};
+
+use super::super::shared::parse::ParseableEmail;
+
+pub struct Mail {
+    /// A virtual path that identifies the mail on the server,
+    /// e.g. `imap://user@host/INBOX/12`
+    path: PathBuf,
+    // This is the `\Seen` flag
+    is_seen: bool,
+    // The mailbox name
+    tags: Vec<String>,
+    // The header section of the mail
+    content: Vec<u8>,
+    // The `RFC822.SIZE` of the whole mail
+    size: Option<usize>,
+    // The attachments in the `BODYSTRUCTURE`
+    attachments: Option<Vec<Attachment>>,
+}
+
+impl Mail {
+    pub fn new(
+        path: PathBuf,
+        mailbox: String,
+        is_seen: bool,
+        content: Vec<u8>,
+        size: Option<usize>,
+        attachments: Option<Vec<Attachment>>,
+    ) -> Self {
+        Self {
+            path,
+            is_seen,
+            tags: vec![mailbox],
+            content,
+            size,
+            attachments,
+        }
+    }
+}
+
+impl ParseableEmail for Mail {
+    fn prepare(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn message(&self) -> Result<Cow<'_, [u8]>> {
+        Ok(self.content.as_slice().into())
+    }
+    fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    fn meta(&self) -> Result<Option<EmailMeta>> {
+        Ok(Some(EmailMeta {
+            tags: self.tags.clone(),
+            is_seen: self.is_seen,
+            thread_id: None,
+        }))
+    }
+    fn size(&self) -> Option<usize> {
+        self.size
+    }
+    fn attachments(&self) -> Option<Vec<Attachment>> {
+        self.attachments.clone()
+    }
+}
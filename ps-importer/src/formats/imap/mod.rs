@@ -0,0 +1,40 @@
+//! Import the mails directly from an IMAP server, for users that don't
+//! have a local copy of their mails. We only fetch the headers of each
+//! mail (`BODY.PEEK[HEADER]`) and the `FLAGS` as we don't need the
+//! bodies and `PEEK` doesn't mark the mails as seen. The size and the
+//! attachments come from the `RFC822.SIZE` and the `BODYSTRUCTURE`.
+
+mod mail;
+mod session;
+mod structure;
+
+use std::path::PathBuf;
+
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::eyre::eyre;
+use ps_core::{MessageSender, Source};
+
+#[derive(Default)]
+pub struct Imap {}
+
+impl ImporterFormat for Imap {
+    type Item = mail::Mail;
+
+    /// There's no local path for a server
+    fn default_path() -> Option<PathBuf> {
+        None
+    }
+
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        let imap = source
+            .imap
+            .as_ref()
+            .ok_or_else(|| eyre!("Missing IMAP server configuration"))?;
+        session::read_emails(imap, sender, emails)
+    }
+}
@@ -0,0 +1,204 @@
+use imap::types::{Flag, NameAttribute};
+use native_tls::TlsConnector;
+
+use ps_core::eyre::{eyre, Result};
+use ps_core::tracing;
+use ps_core::{ImapConfig, Message, MessageSender};
+
+use super::super::shared::database::Cancelled;
+use super::super::EmailSender;
+use super::mail::Mail;
+use super::structure::attachments;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+/// How many mails we fetch per `FETCH` command. Large mailboxes would
+/// otherwise result in one huge response.
+const BATCH_SIZE: u32 = 1000;
+
+/// We only need the headers, the flags, the size of the whole mail and the
+/// structure of its parts. `PEEK` doesn't set `\Seen`. The `UID` stays the same
+/// when other mails are deleted, so it is part of the path in order to recognize
+/// mails when updating.
+const FETCH_QUERY: &str = "(UID FLAGS RFC822.SIZE BODYSTRUCTURE BODY.PEEK[HEADER])";
+
+pub fn read_emails(
+    config: &ImapConfig,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let address = (config.host.as_str(), config.port);
+    if config.tls {
+        let connector = TlsConnector::builder().build()?;
+        let client = imap::connect(address, &config.host, &connector)?;
+        read_session(client, config, sender, emails)
+    } else {
+        let stream = TcpStream::connect(address)?;
+        let mut client = imap::Client::new(stream);
+        client.read_greeting()?;
+        read_session(client, config, sender, emails)
+    }
+}
+
+fn read_session<T: Read + Write>(
+    client: imap::Client<T>,
+    config: &ImapConfig,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let mut session = client
+        .login(&config.user, &config.password)
+        .map_err(|(e, _)| eyre!("Could not log in as {}: {}", config.user, e))?;
+
+    let names = session.list(Some(""), Some("*"))?;
+    // Mailboxes such as `[Gmail]` only exist to group other mailboxes
+    let mailboxes: Vec<(String, Option<String>)> = names
+        .iter()
+        .filter(|e| !e.attributes().contains(&NameAttribute::NoSelect))
+        .map(|e| (e.name().to_owned(), e.delimiter().map(|e| e.to_owned())))
+        .collect();
+    sender.send(Message::ReadTotal(mailboxes.len()))?;
+
+    for (mailbox, delimiter) in mailboxes {
+        emails.check()?;
+        match read_mailbox(
+            &mut session,
+            config,
+            &mailbox,
+            delimiter.as_deref(),
+            &emails,
+        ) {
+            Ok(_) => (),
+            // The other mailboxes can't be sent either
+            Err(e) if e.is::<Cancelled>() => return Err(e),
+            Err(e) => {
+                tracing::error!("{} {:?}", &mailbox, &e);
+                if let Err(e) = sender.send(Message::Error(eyre!(
+                    "Could not read mails in {}: {:?}",
+                    &mailbox,
+                    e
+                ))) {
+                    tracing::error!("Error sending error {}", e);
+                }
+            }
+        }
+        sender.send(Message::ReadOne)?;
+    }
+
+    if let Err(e) = session.logout() {
+        tracing::error!("Could not log out: {}", e);
+    }
+    Ok(())
+}
+
+fn read_mailbox<T: Read + Write>(
+    session: &mut imap::Session<T>,
+    config: &ImapConfig,
+    mailbox: &str,
+    delimiter: Option<&str>,
+    emails: &EmailSender<Mail>,
+) -> Result<()> {
+    // `EXAMINE` opens the mailbox read-only
+    let total = session.examine(mailbox)?.exists;
+    tracing::trace!("Mailbox {} has {} mails", mailbox, total);
+    emails.estimate(total as usize);
+    let name = decode_mailbox_name(mailbox);
+    let tag = mailbox_tag(&name, delimiter);
+
+    let mut start = 1;
+    while start <= total {
+        emails.check()?;
+        let end = (start + BATCH_SIZE - 1).min(total);
+        let fetches = session.fetch(format!("{}:{}", start, end), FETCH_QUERY)?;
+        for fetch in fetches.iter() {
+            let header = match fetch.header() {
+                Some(n) => n.to_vec(),
+                None => {
+                    tracing::error!("Missing header for {} in {}", fetch.message, mailbox);
+                    continue;
+                }
+            };
+            let is_seen = fetch.flags().contains(&Flag::Seen);
+            let id = fetch.uid.unwrap_or(fetch.message);
+            let path = PathBuf::from(format!(
+                "imap://{}@{}/{}/{}",
+                config.user, config.host, name, id
+            ));
+            let size = fetch.size.map(|e| e as usize);
+            let attachments = fetch.bodystructure().map(attachments);
+            emails.send(Mail::new(
+                path,
+                tag.clone(),
+                is_seen,
+                header,
+                size,
+                attachments,
+            ))?;
+        }
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// Servers use different hierarchy delimiters (e.g. `INBOX.Archive` or
+/// `INBOX/Archive`). We always use `/` for the tags.
+fn mailbox_tag(mailbox: &str, delimiter: Option<&str>) -> String {
+    match delimiter {
+        Some(n) if n != "/" => mailbox.replace(n, "/"),
+        _ => mailbox.to_owned(),
+    }
+}
+
+/// Mailbox names with other than printable ASCII characters are sent in
+/// modified UTF-7 (RFC 3501), e.g. `Entw&APw-rfe` for `Entwürfe`. The
+/// characters between `&` and `-` are UTF-16 in base64, with `,` instead
+/// of `/`, and `&-` is a plain `&`. Broken names are kept as they are.
+fn decode_mailbox_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let encoded = &rest[start + 1..];
+        let end = match encoded.find('-') {
+            Some(n) => n,
+            None => return name.to_owned(),
+        };
+        if end == 0 {
+            result.push('&');
+        } else {
+            let bytes = match base64::decode_config(
+                encoded[..end].replace(',', "/"),
+                base64::STANDARD_NO_PAD,
+            ) {
+                Ok(n) if n.len() % 2 == 0 => n,
+                _ => return name.to_owned(),
+            };
+            let units = bytes.chunks(2).map(|e| u16::from_be_bytes([e[0], e[1]]));
+            match char::decode_utf16(units).collect::<Result<String, _>>() {
+                Ok(n) => result.push_str(&n),
+                Err(_) => return name.to_owned(),
+            }
+        }
+        rest = &encoded[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mailbox_name() {
+        assert_eq!(decode_mailbox_name("INBOX.Archive"), "INBOX.Archive");
+        assert_eq!(decode_mailbox_name("Entw&APw-rfe"), "Entw\u{fc}rfe");
+        assert_eq!(decode_mailbox_name("Tom &- Jerry"), "Tom & Jerry");
+        assert_eq!(
+            decode_mailbox_name("&ZeVnLIqe-/&U,BTFw-"),
+            "\u{65e5}\u{672c}\u{8a9e}/\u{53f0}\u{5317}"
+        );
+        assert_eq!(decode_mailbox_name("Broken &AP"), "Broken &AP");
+    }
+}
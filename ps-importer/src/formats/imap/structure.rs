@@ -0,0 +1,87 @@
+//! The attachments of an IMAP mail, read from its `BODYSTRUCTURE`
+//! as only the headers of the mail are downloaded.
+
+use imap_proto::types::{BodyContentCommon, BodyContentSinglePart, BodyStructure, ContentEncoding};
+use ps_core::Attachment;
+
+use super::super::shared::mime::attachment;
+
+/// Nested multiparts deeper than this are ignored
+const MAX_DEPTH: usize = 20;
+
+/// The attachments in the MIME tree described by `structure`,
+/// the same that `mime::attachments` finds in the contents
+pub fn attachments(structure: &BodyStructure) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    walk(structure, 0, &mut attachments);
+    attachments
+}
+
+fn walk(structure: &BodyStructure, depth: usize, attachments: &mut Vec<Attachment>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let (common, other) = match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for body in bodies {
+                walk(body, depth + 1, attachments);
+            }
+            return;
+        }
+        BodyStructure::Message {
+            common,
+            other,
+            body,
+            ..
+        } => {
+            // Forwarded emails can have attachments of their own
+            if !is_attachment(common) {
+                walk(body, depth + 1, attachments);
+                return;
+            }
+            (common, other)
+        }
+        BodyStructure::Basic { common, other, .. } => (common, other),
+        BodyStructure::Text { common, other, .. } => (common, other),
+    };
+    let content_type = header_value(
+        &format!("{}/{}", common.ty.ty, common.ty.subtype),
+        &common.ty.params,
+    );
+    let disposition = common
+        .disposition
+        .as_ref()
+        .map(|e| header_value(e.ty, &e.params))
+        .unwrap_or_default();
+    if let Some(attachment) = attachment(&content_type, &disposition, || decoded_size(other)) {
+        attachments.push(attachment);
+    }
+}
+
+fn is_attachment(common: &BodyContentCommon) -> bool {
+    common
+        .disposition
+        .as_ref()
+        .map(|e| e.ty.eq_ignore_ascii_case("attachment"))
+        .unwrap_or(false)
+}
+
+/// The server has already split the parameters, they're joined again
+/// in order to be read like the header values of other formats
+fn header_value(value: &str, params: &Option<Vec<(&str, &str)>>) -> String {
+    let mut header = value.to_owned();
+    for (key, value) in params.iter().flatten() {
+        header.push_str(&format!("; {}=\"{}\"", key, value));
+    }
+    header
+}
+
+/// Base64 has 3 bytes in every 4 characters, and a
+/// line break after every 76 of them
+fn decoded_size(part: &BodyContentSinglePart) -> usize {
+    let octets = part.octets as usize;
+    match part.transfer_encoding {
+        ContentEncoding::Base64 => octets * 76 / 78 * 3 / 4,
+        _ => octets,
+    }
+}
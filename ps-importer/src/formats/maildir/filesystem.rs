@@ -0,0 +1,213 @@
+//! A Maildir consists of a root folder with the `cur`, `new` and `tmp`
+//! sub folders. Additional mailboxes are either stored as Maildir++
+//! folders (e.g. `.Work.Projects`) next to them, or (e.g. with
+//! mbsync's `SubFolders Verbatim`) as nested folders (`Work/Projects`).
+//! We walk the whole tree, collect every `cur`, `new` and `tmp` folder
+//! and read the mails in there in paralell.
+
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use super::super::shared::archive::{archive_members, is_archive, progress_total, read_archive};
+use super::super::shared::filesystem::{count_files, emails_in};
+use super::super::EmailSender;
+use ps_core::eyre::{bail, eyre, Result};
+use ps_core::tracing;
+use ps_core::{Message, MessageSender, Source};
+
+use super::mail::Mail;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The folders that contain the actual mails in a Maildir
+const MAIL_FOLDERS: &[&str] = &["cur", "new", "tmp"];
+
+/// The tag we use for the mails in the root of the Maildir
+const INBOX_TAG: &str = "INBOX";
+
+/// Separates the levels of a Maildir++ folder name (`.Work.Projects`)
+const MAILDIR_PP_SEPARATOR: char = '.';
+
+pub fn read_emails(
+    source: &Source,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let root = &source.emails_folder_path;
+    if !root.exists() {
+        bail!("Folder {} does not exist", &root.display());
+    }
+    if is_archive(root) {
+        return read_archived_emails(root, sender, emails);
+    }
+
+    let folders: Vec<(PathBuf, String)> = WalkDir::new(root)
+        .into_iter()
+        .take_while(|_| !emails.is_cancelled())
+        .filter_map(|e| match e {
+            Ok(n) if n.path().is_dir() && is_mail_folder(n.path()) => {
+                tracing::trace!("Found folder {}", n.path().display());
+                let tag = folder_tag(root, n.path().parent()?);
+                Some((n.path().to_path_buf(), tag))
+            }
+            Err(e) => {
+                tracing::info!("Could not read folder: {}", e);
+                if let Err(e) = sender.send(Message::Error(eyre!("Could not read folder: {:?}", e)))
+                {
+                    tracing::error!("Error sending error {}", e);
+                }
+                None
+            }
+            _ => None,
+        })
+        .collect();
+    sender.send(Message::ReadTotal(folders.len()))?;
+    // Every file in a mail folder is a mail
+    emails.estimate(folders.iter().map(|(path, _)| count_files(path, &[])).sum());
+    folders
+        .into_par_iter()
+        .filter_map(|(path, tag)| {
+            if emails.is_cancelled() {
+                return None;
+            }
+            let is_new = path.file_name().map(|e| e == "new").unwrap_or(false);
+            match emails_in(path.clone(), sender.clone(), move |p| {
+                Mail::new(p, &tag, is_new)
+            }) {
+                Ok(n) => Some(n),
+                Err(e) => {
+                    tracing::error!("{} {:?}", path.display(), &e);
+                    if let Err(e) = sender.send(Message::Error(eyre!(
+                        "Could read mails in {}: {:?}",
+                        path.display(),
+                        e
+                    ))) {
+                        tracing::error!("Error sending error {}", e);
+                    }
+                    None
+                }
+            }
+        })
+        .flatten()
+        .try_for_each(|mail| emails.send(mail))?;
+    Ok(())
+}
+
+/// Read the mails in the Maildir folders within the archive at `archive`
+/// (e.g. a `.tar.gz` of `~/Maildir`).
+fn read_archived_emails(
+    archive: &Path,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    // We can only build the tags once we know all folders. So the paths
+    // are listed first, without keeping the contents of the members.
+    let folders: Vec<PathBuf> = archive_members(archive, usize::MAX)?
+        .iter()
+        .filter_map(|path| mail_folder(path))
+        .collect();
+
+    // The root of the Maildir is the folder all other folders are in
+    let mut root = folders.first().cloned().unwrap_or_default();
+    for folder in &folders {
+        while !folder.starts_with(&root) {
+            root.pop();
+        }
+    }
+
+    sender.send(Message::ReadTotal(progress_total(archive)))?;
+    // There's one entry in `folders` for each mail
+    emails.estimate(folders.len());
+    read_archive(archive, &sender, |path, member| {
+        emails.check()?;
+        let folder = match mail_folder(&path) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        let is_new = path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|e| e == "new")
+            .unwrap_or(false);
+        let tag = folder_tag(&root, &folder);
+        let mut data = Vec::new();
+        member.read_to_end(&mut data)?;
+        if let Some(mail) = Mail::archived(archive.join(path), &tag, is_new, data) {
+            emails.send(mail)?;
+        }
+        Ok(())
+    })
+}
+
+/// The Maildir folder of a mail at `path` within an archive, i.e. the
+/// folder of its `cur`, `new` or `tmp` folder. `None` if it isn't in one.
+fn mail_folder(path: &Path) -> Option<PathBuf> {
+    let mail_folder = path.parent()?;
+    let name = mail_folder.file_name()?.to_str()?;
+    if !MAIL_FOLDERS.contains(&name) {
+        return None;
+    }
+    Some(
+        mail_folder
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    )
+}
+
+/// Is this a `cur`, `new` or `tmp` folder within a Maildir?
+fn is_mail_folder(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|e| e.to_str()) {
+        Some(n) => n,
+        None => return false,
+    };
+    if !MAIL_FOLDERS.contains(&name) {
+        return false;
+    }
+    // A Maildir always has a `cur` folder. This prevents us from picking up
+    // random folders that just happen to be called `new` or `tmp`.
+    path.parent()
+        .map(|p| p.join("cur").is_dir())
+        .unwrap_or(false)
+}
+
+/// Build a tag out of the Maildir folder relative to the root.
+/// Both Maildir++ (`.Work.Projects`) and nested folders (`Work/Projects`)
+/// result in the tag `Work/Projects`. The names of nested folders are
+/// kept as they are, even if they contain a dot (`Lists/rust.users`).
+fn folder_tag(root: &Path, folder: &Path) -> String {
+    let relative = match folder.strip_prefix(root) {
+        Ok(n) => n,
+        Err(_) => return INBOX_TAG.to_owned(),
+    };
+    let components: Vec<&str> = relative
+        .iter()
+        .filter_map(|e| e.to_str())
+        .flat_map(|e| match e.strip_prefix('.') {
+            Some(name) => name.split(MAILDIR_PP_SEPARATOR).collect(),
+            None => vec![e],
+        })
+        .filter(|e| !e.is_empty())
+        .collect();
+    if components.is_empty() {
+        INBOX_TAG.to_owned()
+    } else {
+        components.join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_tag() {
+        let root = Path::new("Maildir");
+        let tag = |folder: &str| folder_tag(root, &root.join(folder));
+        assert_eq!(tag(""), INBOX_TAG);
+        assert_eq!(tag(".Work.Projects"), "Work/Projects");
+        assert_eq!(tag("Work/Projects"), "Work/Projects");
+        assert_eq!(tag("Lists/rust.users"), "Lists/rust.users");
+        assert_eq!(tag("Lists/.rust.users"), "Lists/rust/users");
+    }
+}
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use ps_core::eyre::Result;
+use ps_core::EmailMeta;
+
+use super::super::shared::parse::ParseableEmail;
+
+/// The Maildir flags and the tags we map them to.
+/// `S` (seen) is handled separately as it maps to `EmailMeta::is_seen`
+const FLAG_TAGS: &[(char, &str)] = &[
+    ('R', "Replied"),
+    ('F', "Flagged"),
+    ('T', "Trashed"),
+    ('D', "Draft"),
+    ('P', "Passed"),
+];
+
+pub struct Mail {
+    path: PathBuf,
+    // This is parsed out of the filename flags
+    is_seen: bool,
+    // The folder tag, followed by the tags parsed out of the flags
+    tags: Vec<String>,
+    // Maildata
+    data: Vec<u8>,
+}
+
+impl Mail {
+    pub fn new<P: AsRef<Path>>(path: P, folder_tag: &str, is_new: bool) -> Option<Self> {
+        let path = path.as_ref();
+        let name = path.file_name()?.to_str()?;
+        // Skip hidden files such as `.DS_Store` or mbsync's `.uidvalidity`
+        if name.starts_with('.') {
+            return None;
+        }
+        let flags = flags(name);
+        let mut tags = vec![folder_tag.to_owned()];
+        tags.extend(
+            FLAG_TAGS
+                .iter()
+                .filter(|(flag, _)| flags.contains(*flag))
+                .map(|(_, tag)| tag.to_string()),
+        );
+        Some(Self {
+            path: path.to_path_buf(),
+            // Mails in `new` have not been seen by any client yet
+            is_seen: !is_new && flags.contains('S'),
+            tags,
+            data: Vec::new(),
+        })
+    }
+
+    /// A mail that was read out of an archive, `data` are its contents
+    pub fn archived<P: AsRef<Path>>(
+        path: P,
+        folder_tag: &str,
+        is_new: bool,
+        data: Vec<u8>,
+    ) -> Option<Self> {
+        Self::new(path, folder_tag, is_new).map(|mail| Self { data, ..mail })
+    }
+}
+
+/// Return the flags part of a Maildir filename.
+/// The info part is separated with `:2,` (or with `;2,` / `!2,` on
+/// platforms where `:` is not allowed in filenames).
+fn flags(name: &str) -> &str {
+    name.rfind(|c: char| c == ':' || c == ';' || c == '!')
+        .map(|index| &name[(index + 1)..])
+        .and_then(|info| info.strip_prefix("2,"))
+        .unwrap_or("")
+}
+
+impl ParseableEmail for Mail {
+    fn prepare(&mut self) -> Result<()> {
+        // Archived mails already have their data
+        if self.data.is_empty() {
+            self.data = std::fs::read(self.path.as_path())?;
+        }
+        Ok(())
+    }
+    fn message(&self) -> Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(self.data.as_slice()))
+    }
+    fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    fn meta(&self) -> Result<Option<EmailMeta>> {
+        Ok(Some(EmailMeta {
+            tags: self.tags.clone(),
+            is_seen: self.is_seen,
+            thread_id: None,
+        }))
+    }
+}
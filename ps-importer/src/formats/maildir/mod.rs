@@ -0,0 +1,28 @@
+mod filesystem;
+mod mail;
+
+use std::{path::PathBuf, str::FromStr};
+
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::{MessageSender, Source};
+
+#[derive(Default)]
+pub struct Maildir {}
+
+impl ImporterFormat for Maildir {
+    type Item = mail::Mail;
+
+    fn default_path() -> Option<PathBuf> {
+        let path = shellexpand::tilde("~/Maildir");
+        Some(PathBuf::from_str(&path.to_string()).unwrap())
+    }
+
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        filesystem::read_emails(source, sender, emails)
+    }
+}
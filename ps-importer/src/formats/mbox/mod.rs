@@ -1,42 +1,42 @@
+mod reader;
+mod takeout;
+
 use ps_core::eyre::eyre;
 use ps_core::tracing;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
-use super::{Config, ImporterFormat, Message, MessageSender, Result};
+use super::{EmailSender, ImporterFormat, Message, MessageSender, Result, Source};
 
+use super::shared::archive::{is_archive, progress_total, read_archive};
+use super::shared::database::Cancelled;
 use super::shared::parse::ParseableEmail;
 use ps_core::EmailMeta;
+use reader::read_mbox_stream;
+pub(super) use reader::{estimate_mails, read_mbox_file, MboxMail};
 
 use std::borrow::Cow;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 pub struct Mail {
-    path: PathBuf,
-    /// For now, we go with a very simple implementation:
-    /// Each mal will have a heap-allocated vec of the corresponding
-    /// bytes in the mbox.
-    /// This wastes a lot of allocations and shows the limits of our current abstraction.
-    /// It would be better to just save the headers and ignore the rest.
-    content: Vec<u8>,
+    /// The path of the mbox file, shared by all mails in it
+    path: Arc<PathBuf>,
+    /// The mail within the (memory-mapped) mbox file
+    mail: MboxMail,
 }
 
 #[derive(Default)]
 pub struct Mbox;
 
 /// The inner parsing code
-fn inner_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>> {
-    // find all files ending in .mbox
-    let mboxes: Vec<PathBuf> = WalkDir::new(&config.emails_folder_path)
+fn inner_emails(source: &Source, sender: MessageSender, emails: EmailSender<Mail>) -> Result<()> {
+    // find all files ending in .mbox (or archives that contain them)
+    let mboxes: Vec<PathBuf> = WalkDir::new(&source.emails_folder_path)
         .into_iter()
+        .take_while(|_| !emails.is_cancelled())
         .filter_map(|e| match e {
-            Ok(n)
-                if n.path().is_file()
-                    && n.path()
-                        .to_str()
-                        .map(|e| e.contains(".mbox"))
-                        .unwrap_or(false) =>
-            {
+            Ok(n) if n.path().is_file() && (is_mbox(n.path()) || is_archive(n.path())) => {
                 tracing::trace!("Found mbox file {}", n.path().display());
                 Some(n.path().to_path_buf())
             }
@@ -51,42 +51,87 @@ fn inner_emails(config: &Config, sender: MessageSender) -> Result<Vec<Mail>> {
             _ => None,
         })
         .collect();
+    let (archives, mboxes): (Vec<PathBuf>, Vec<PathBuf>) =
+        mboxes.into_iter().partition(|e| is_archive(e));
+
+    // Reading the archives is the slow part, so the progress is theirs
+    if !archives.is_empty() {
+        let total = archives.iter().map(|e| progress_total(e)).sum();
+        sender.send(Message::ReadTotal(total))?;
+    }
 
-    let mails: Vec<Mail> = mboxes
+    emails.estimate(estimate_mails(&mboxes));
+    mboxes
         .into_par_iter()
         .filter_map(|mbox_file| {
-            let mbox = match mbox_reader::MboxFile::from_file(&mbox_file) {
-                Ok(n) => n,
-                Err(e) => {
-                    tracing::error!(
-                        "Could not open mbox file at {}: {}",
-                        &mbox_file.display(),
-                        e
-                    );
-                    return None;
-                }
-            };
-            let inner_mails: Vec<Mail> = mbox
-                .iter()
-                .filter_map(|e| {
-                    let content = match e.message() {
-                        Some(n) => n,
-                        None => {
-                            tracing::error!("Could not parse mail at offset {}", e.offset());
-                            return None;
-                        }
-                    };
-                    Some(Mail {
-                        path: mbox_file.clone(),
-                        content: content.to_owned(),
-                    })
+            if emails.is_cancelled() {
+                return None;
+            }
+            let path = Arc::new(mbox_file.clone());
+            read_mbox_file(&mbox_file, |mail| {
+                Some(Mail {
+                    path: path.clone(),
+                    mail,
                 })
-                .collect();
-            Some(inner_mails)
+            })
         })
         .flatten()
-        .collect();
-    Ok(mails)
+        .try_for_each(|mail| emails.send(mail))?;
+
+    for archive in archives {
+        emails.check()?;
+        read_mbox_archive(&archive, &sender, &emails)?;
+    }
+    Ok(())
+}
+
+fn is_mbox(path: &Path) -> bool {
+    path.to_str().map(|e| e.contains(".mbox")).unwrap_or(false)
+}
+
+/// Read the mbox files within the archive at `archive` (e.g. a zipped Takeout
+/// export or an `INBOX.mbox.gz`). The path of each mail is the path of the mbox
+/// within the archive, e.g. `takeout.zip/Takeout/Mail/Inbox.mbox`.
+/// As compressed files can't be memory-mapped, each mbox in the archive is
+/// split into mails while it is decompressed.
+/// A broken archive is reported and skipped, only a failure to send the
+/// mails (once the import is cancelled) is returned.
+fn read_mbox_archive(
+    archive: &Path,
+    sender: &MessageSender,
+    emails: &EmailSender<Mail>,
+) -> Result<()> {
+    let mut disconnected = false;
+    let result = read_archive(archive, sender, |member, reader| {
+        if !is_mbox(&member) {
+            return Ok(());
+        }
+        let path = Arc::new(archive.join(member));
+        read_mbox_stream(reader, |mail| {
+            let mail = Mail {
+                path: path.clone(),
+                mail,
+            };
+            disconnected = emails.send(mail).is_err();
+            match disconnected {
+                true => Err(eyre!("The import was stopped")),
+                false => Ok(()),
+            }
+        })
+    });
+    match result {
+        Err(_) if disconnected => Err(Cancelled.into()),
+        Err(e) => {
+            tracing::error!("Could not read archive at {}: {}", archive.display(), e);
+            sender.send(Message::Error(eyre!(
+                "Could not read archive at {}: {}",
+                archive.display(),
+                e
+            )))?;
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
 }
 
 impl ImporterFormat for Mbox {
@@ -96,8 +141,13 @@ impl ImporterFormat for Mbox {
         None
     }
 
-    fn emails(&self, config: &Config, sender: MessageSender) -> Result<Vec<Self::Item>> {
-        inner_emails(config, sender)
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        inner_emails(source, sender, emails)
     }
 }
 
@@ -106,18 +156,23 @@ impl ParseableEmail for Mail {
         Ok(())
     }
     fn message(&self) -> Result<Cow<'_, [u8]>> {
-        Ok(self.content.as_slice().into())
+        Ok(self.mail.data().into())
     }
     fn path(&self) -> &Path {
         self.path.as_path()
     }
     fn meta(&self) -> Result<Option<EmailMeta>> {
+        // A Google Takeout export has the labels in each mail
+        if let Some(meta) = takeout::takeout_meta(self.mail.data()) {
+            return Ok(Some(meta));
+        }
         // The filename is a tag, e.g. `INBOX.mbox`, `WORK.mbox`
         if let Some(prefix) = self.path.file_stem() {
             if let Some(s) = prefix.to_str().map(|s| s.to_owned()) {
                 return Ok(Some(EmailMeta {
                     tags: vec![s],
                     is_seen: false,
+                    thread_id: None,
                 }));
             }
         }
@@ -0,0 +1,256 @@
+//! Reading mbox files without copying the mails.
+//!
+//! The files are memory-mapped and we only keep the range of each mail
+//! within the file. The mails are read (and parsed) when the importer asks
+//! for them in `ParseableEmail::message`, so the operating system only
+//! has to keep the pages in memory that are currently being parsed,
+//! regardless of the size of the mbox.
+//!
+//! Mbox files within (compressed) archives can't be mapped. They are split
+//! into mails while they're decompressed, so only one mail at a time
+//! has to be kept in memory.
+
+use memmap2::Mmap;
+use ps_core::eyre::Result;
+use ps_core::tracing;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::{Deref, Range};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A rough average size of a mail (including its attachments), to estimate
+/// how many mails an mbox file has before splitting it
+const AVERAGE_MAIL_SIZE: u64 = 50 * 1024;
+
+/// The contents of an mbox file
+pub enum MboxContent {
+    /// A memory-mapped mbox file
+    Mapped(Mmap),
+    /// A single mail that was read out of a stream
+    Memory(Vec<u8>),
+}
+
+impl Deref for MboxContent {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MboxContent::Mapped(n) => n,
+            MboxContent::Memory(n) => n,
+        }
+    }
+}
+
+/// A mail within an mbox. It shares the contents with the other mails
+/// in the same mbox, unless it was read out of a stream.
+pub struct MboxMail {
+    content: Arc<MboxContent>,
+    range: Range<usize>,
+}
+
+impl MboxMail {
+    fn memory(data: Vec<u8>) -> Self {
+        let range = 0..data.len();
+        MboxMail {
+            content: Arc::new(MboxContent::Memory(data)),
+            range,
+        }
+    }
+
+    /// The data of the mail, without the `From ` separator line
+    pub fn data(&self) -> &[u8] {
+        &self.content[self.range.clone()]
+    }
+}
+
+/// Estimate the amount of mails in the mbox files out of their size
+pub fn estimate_mails<P: AsRef<Path>>(mbox_files: impl IntoIterator<Item = P>) -> usize {
+    let size: u64 = mbox_files
+        .into_iter()
+        .filter_map(|e| e.as_ref().metadata().ok())
+        .map(|e| e.len())
+        .sum();
+    (size / AVERAGE_MAIL_SIZE) as usize
+}
+
+/// Open the mbox file at `mbox_file` and call `make` for each mail in there.
+/// Returns `None` if the file could not be opened.
+pub fn read_mbox_file<O, F>(mbox_file: &Path, make: F) -> Option<Vec<O>>
+where
+    F: Fn(MboxMail) -> Option<O>,
+{
+    let map = File::open(mbox_file).and_then(|file| {
+        // Empty files can't be mapped
+        if file.metadata()?.len() == 0 {
+            return Ok(None);
+        }
+        // Safety: The file could be changed by another process while we're reading it.
+        // In that case we'd read garbage mails, but there's no way to prevent that.
+        unsafe { Mmap::map(&file) }.map(Some)
+    });
+    match map {
+        Ok(Some(n)) => Some(read_mbox(MboxContent::Mapped(n), make)),
+        Ok(None) => Some(Vec::new()),
+        Err(e) => {
+            tracing::error!(
+                "Could not open mbox file at {}: {}",
+                &mbox_file.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Call `make` for each mail in `content`.
+fn read_mbox<O, F>(content: MboxContent, make: F) -> Vec<O>
+where
+    F: Fn(MboxMail) -> Option<O>,
+{
+    let ranges = mail_ranges(&content);
+    let content = Arc::new(content);
+    ranges
+        .into_iter()
+        .filter_map(|range| {
+            make(MboxMail {
+                content: content.clone(),
+                range,
+            })
+        })
+        .collect()
+}
+
+/// Split the mbox that is read from `reader` into mails while it is read,
+/// and call `action` for each of them. Stops at the first error.
+pub fn read_mbox_stream<R: Read, F>(reader: R, mut action: F) -> Result<()>
+where
+    F: FnMut(MboxMail) -> Result<()>,
+{
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    // Anything before the first `From ` line is not a mail
+    let mut mail: Option<Vec<u8>> = None;
+    let mut after_blank = true;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if is_separator(&line, after_blank) {
+            if let Some(data) = mail.replace(Vec::new()) {
+                action(MboxMail::memory(data))?;
+            }
+        } else if let Some(data) = mail.as_mut() {
+            data.extend_from_slice(&line);
+        }
+        after_blank = is_blank(&line);
+    }
+    match mail {
+        Some(data) => action(MboxMail::memory(data)),
+        None => Ok(()),
+    }
+}
+
+/// Find the mails in the contents of an mbox file.
+/// Each mail starts with a `From ` line, which is not part of the mail.
+/// See [`is_separator`] for which `From ` lines separate the mails.
+fn mail_ranges(content: &[u8]) -> Vec<Range<usize>> {
+    // The `(start, end)` of each `From ` line
+    let mut separators = Vec::new();
+    let mut offset = 0;
+    let mut after_blank = true;
+    while offset < content.len() {
+        let end = content[offset..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .map(|e| offset + e + 1)
+            .unwrap_or_else(|| content.len());
+        if is_separator(&content[offset..end], after_blank) {
+            separators.push((offset, end));
+        }
+        after_blank = is_blank(&content[offset..end]);
+        offset = end;
+    }
+    separators
+        .iter()
+        .enumerate()
+        .map(|(index, (_, start))| {
+            let end = separators
+                .get(index + 1)
+                .map(|(next, _)| *next)
+                .unwrap_or_else(|| content.len());
+            *start..end
+        })
+        .collect()
+}
+
+/// Is `line` the `From ` line that starts a mail? As mailers don't always
+/// escape `From ` in the body (as `>From `), it has to be at the start of the
+/// file or follow a blank line (`after_blank`). `git format-patch` leaves out
+/// the blank line, so a `From ` line with a complete date is a separator, too.
+fn is_separator(line: &[u8], after_blank: bool) -> bool {
+    line.starts_with(b"From ") && (after_blank || has_date(line))
+}
+
+/// Is `line` empty (apart from the line ending or other whitespace)?
+/// Whitespace before the first `From ` line is skipped the same way.
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|c| c.is_ascii_whitespace())
+}
+
+/// Does the `From ` line end in a date like `Mon Sep 17 00:00:00 2001`
+/// after the sender? Only the time and the year after it are checked.
+fn has_date(line: &[u8]) -> bool {
+    let line = String::from_utf8_lossy(line);
+    let words: Vec<&str> = line.split_whitespace().skip(2).collect();
+    let is_number = |word: &str, length: std::ops::RangeInclusive<usize>| {
+        length.contains(&word.len()) && word.bytes().all(|c| c.is_ascii_digit())
+    };
+    words.windows(2).any(|e| {
+        let time: Vec<&str> = e[0].split(':').collect();
+        (2..=3).contains(&time.len())
+            && time.iter().all(|e| is_number(e, 1..=2))
+            && is_number(e[1], 4..=4)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mail_ranges() {
+        let content = b"\nFrom a@b Mon Jan  4 10:00:00 2021\nSubject: One\n\n>From the body\nFrom here on\n\nFrom c@d Mon Jan  4 11:00:00 2021\nSubject: Two\n\nBody\nFrom e@f Mon Jan  4 12:00:00 2021\nSubject: Three\n";
+        let mails: Vec<&[u8]> = mail_ranges(content)
+            .into_iter()
+            .map(|range| &content[range])
+            .collect();
+        assert_eq!(mails.len(), 3);
+        // The unescaped `From ` in the body doesn't follow a blank line
+        assert_eq!(
+            mails[0],
+            &b"Subject: One\n\n>From the body\nFrom here on\n\n"[..]
+        );
+        // Without a blank line, only a `From ` line with a date is a separator
+        assert_eq!(mails[1], &b"Subject: Two\n\nBody\n"[..]);
+        assert_eq!(mails[2], &b"Subject: Three\n"[..]);
+    }
+
+    #[test]
+    fn test_read_mbox_stream() {
+        let content = b"\nFrom a@b Mon Jan  4 10:00:00 2021\nSubject: One\n\n>From the body\nFrom here on\n\nFrom c@d Mon Jan  4 11:00:00 2021\nSubject: Two\n\nBody\nFrom e@f Mon Jan  4 12:00:00 2021\nSubject: Three\n";
+        let mut mails = Vec::new();
+        read_mbox_stream(&content[..], |mail| {
+            mails.push(mail.data().to_vec());
+            Ok(())
+        })
+        .unwrap();
+        let expected: Vec<Vec<u8>> = mail_ranges(content)
+            .into_iter()
+            .map(|range| content[range].to_vec())
+            .collect();
+        assert_eq!(mails, expected);
+    }
+}
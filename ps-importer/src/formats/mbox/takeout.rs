@@ -0,0 +1,74 @@
+//! Gmail's Takeout export is one large mbox file. Each mail in there
+//! carries the Gmail labels in a `X-Gmail-Labels` header and the id of
+//! the conversation in a `X-GM-THRID` header.
+
+use ps_core::EmailMeta;
+
+use super::super::shared::headers::header_value;
+
+/// Gmail sets this label on mails that have not been read
+const LABEL_UNREAD: &str = "Unread";
+
+/// Gmail sets this label on mails that have been read
+const LABEL_OPENED: &str = "Opened";
+
+/// Returns the meta information if the mail was exported via
+/// Google Takeout, otherwise `None`.
+pub fn takeout_meta(content: &[u8]) -> Option<EmailMeta> {
+    let labels = header_value(content, "X-Gmail-Labels")?;
+    let labels = split_labels(&labels);
+    // Not all exports contain `Opened` so we only rely on `Unread`
+    let is_seen = !labels.iter().any(|e| e == LABEL_UNREAD);
+    let tags = labels
+        .into_iter()
+        .filter(|e| e != LABEL_UNREAD && e != LABEL_OPENED)
+        .collect();
+    let thread_id = header_value(content, "X-GM-THRID");
+    Some(EmailMeta {
+        tags,
+        is_seen,
+        thread_id,
+    })
+}
+
+/// The labels are separated by a comma. Labels containing
+/// a comma are quoted.
+fn split_labels(labels: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in labels.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => result.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    result.push(current);
+    result
+        .into_iter()
+        .map(|e| e.trim().to_owned())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_takeout_meta() {
+        let content = b"X-GM-THRID: 1700000000000000001\r\nX-Gmail-Labels: Archived,\"Work, Old\",Category Updates,Opened\r\nFrom: a@b.c\r\n\r\nBody";
+        let meta = takeout_meta(content).expect("Expected Meta");
+        assert_eq!(meta.tags, vec!["Archived", "Work, Old", "Category Updates"]);
+        assert!(meta.is_seen);
+        assert_eq!(meta.thread_id.as_deref(), Some("1700000000000000001"));
+
+        let content = b"X-Gmail-Labels: Inbox,Unread\r\n\r\nBody";
+        let meta = takeout_meta(content).expect("Expected Meta");
+        assert_eq!(meta.tags, vec!["Inbox"]);
+        assert!(!meta.is_seen);
+
+        assert!(takeout_meta(b"From: a@b.c\r\n\r\nBody").is_none());
+    }
+}
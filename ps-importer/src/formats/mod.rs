@@ -1,30 +1,138 @@
+use std::marker::PhantomData;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 pub use ps_core::eyre::Result;
+use ps_core::{tracing, CancelToken};
 
 mod apple_mail;
 mod gmailbackup;
+mod imap;
+mod maildir;
 mod mbox;
+mod notmuch;
+mod outlook;
 pub mod shared;
+mod thunderbird;
 
+pub use self::imap::Imap;
+pub use self::notmuch::Notmuch;
 pub use apple_mail::AppleMail;
 pub use gmailbackup::Gmail;
+pub use maildir::Maildir;
 pub use mbox::Mbox;
+pub use outlook::Outlook;
+pub use thunderbird::Thunderbird;
 
+use shared::database::{Cancelled, SourceEmail};
 use shared::parse::ParseableEmail;
 
-pub use ps_core::{Config, Message, MessageReceiver, MessageSender};
+pub use ps_core::{Message, MessageReceiver, MessageSender, Source};
+
+/// The channel the formats send their emails into. It is bounded, so that
+/// reading pauses if parsing and writing can't keep up.
+/// Each email is sent together with the name of the source it was read from.
+pub struct EmailSender<Item> {
+    sender: ps_core::crossbeam_channel::Sender<SourceEmail>,
+    progress: MessageSender,
+    source: Arc<String>,
+    cancel: CancelToken,
+    /// The emails sent by all sources of the import so far
+    sent: Arc<AtomicUsize>,
+    /// The emails sent by the sources before this one
+    previous: usize,
+    /// The estimated amount of emails in this source. See [`EmailSender::estimate`]
+    estimated: AtomicUsize,
+    item: PhantomData<fn(Item)>,
+}
+
+impl<Item: ParseableEmail + 'static> EmailSender<Item> {
+    pub fn new(
+        sender: ps_core::crossbeam_channel::Sender<SourceEmail>,
+        progress: MessageSender,
+        source: String,
+        cancel: CancelToken,
+        sent: Arc<AtomicUsize>,
+    ) -> Self {
+        let previous = sent.load(Ordering::Relaxed);
+        EmailSender {
+            sender,
+            progress,
+            source: Arc::new(source),
+            cancel,
+            sent,
+            previous,
+            estimated: AtomicUsize::new(0),
+            item: PhantomData,
+        }
+    }
+
+    /// Formats check this between folders, files or mailboxes, so that they
+    /// also stop while they're busy without sending anything
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fails once the import is cancelled
+    pub fn check(&self) -> std::result::Result<(), Cancelled> {
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Fails once the import is cancelled, as the receiver is dropped then
+    pub fn send(&self, mail: Item) -> std::result::Result<(), Cancelled> {
+        self.check()?;
+        self.sender
+            .send(SourceEmail {
+                source: self.source.clone(),
+                mail: Box::new(mail),
+            })
+            .map_err(|_| Cancelled)?;
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Formats call this once they know (roughly) how many emails there are,
+    /// e.g. after collecting the files or opening a mailbox. Multiple calls add up.
+    /// The estimate is sent as [`Message::WriteTotal`], so that the progress of
+    /// writing can be shown while reading. The exact total follows at the end.
+    pub fn estimate(&self, emails: usize) {
+        let estimated = self.estimated.fetch_add(emails, Ordering::Relaxed) + emails;
+        let sent = self.sent.load(Ordering::Relaxed) - self.previous;
+        let total = self.previous + estimated.max(sent);
+        if let Err(e) = self.progress.send(Message::WriteTotal(total)) {
+            tracing::error!("Error sending: {}", e);
+        }
+    }
+}
 
 /// This is implemented by the various formats
 /// to define how they return email data.
 pub trait ImporterFormat: Send + Sync {
-    type Item: ParseableEmail;
+    type Item: ParseableEmail + 'static;
 
     /// The default location path where the data for this format resides
     /// on system. If there is none (such as for mbox) return `None`
     fn default_path() -> Option<PathBuf>;
 
-    /// Return all the emails in this format.
-    /// Use the sneder to give progress updates via the `ReadProgress` case.
-    fn emails(&self, config: &Config, sender: MessageSender) -> Result<Vec<Self::Item>>;
+    /// Fails if a program that this format needs to read the emails
+    /// is not installed, so that this can be shown before the import starts.
+    fn requirements() -> Result<()> {
+        Ok(())
+    }
+
+    /// Read all the emails in `source` and send them into `emails`
+    /// as soon as they're found, so they can be parsed while reading continues.
+    /// Use the sender to give progress updates via the `ReadProgress` case, and
+    /// [`EmailSender::estimate`] once the amount of emails is roughly known.
+    /// Stop early if [`EmailSender::is_cancelled`], also while not sending.
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()>;
 }
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+/// The mail root is the `path` in the `[database]` section of the
+/// notmuch config (`$NOTMUCH_CONFIG` or `~/.notmuch-config`).
+/// If there is none, notmuch defaults to `~/mail`.
+pub fn default_path() -> Option<PathBuf> {
+    let home = PathBuf::from(shellexpand::tilde("~").to_string());
+    let config = std::env::var("NOTMUCH_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| home.join(".notmuch-config"));
+    std::fs::read_to_string(config)
+        .ok()
+        .and_then(|content| database_path(&content, &home))
+        .or_else(|| Some(home.join("mail")))
+}
+
+/// Parse the database path out of the contents of a notmuch config.
+/// Relative paths are relative to the home folder.
+fn database_path(content: &str, home: &Path) -> Option<PathBuf> {
+    let mut section = "";
+    for line in content.lines().map(|e| e.trim()) {
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|e| e.strip_suffix(']')) {
+            section = name.trim();
+            continue;
+        }
+        if section != "database" {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((key, value)) if key.trim() == "path" && !value.trim().is_empty() => {
+                let path = shellexpand::tilde(value.trim()).to_string();
+                return Some(home.join(path));
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_path() {
+        let home = Path::new("/home/user");
+        let content =
+            "[user]\nname=User\npath=/wrong\n\n[database]\n# path=/commented\npath = /data/mail\n";
+        assert_eq!(
+            database_path(content, home),
+            Some(PathBuf::from("/data/mail"))
+        );
+        let content = "[database]\npath=Mail\n";
+        assert_eq!(
+            database_path(content, home),
+            Some(PathBuf::from("/home/user/Mail"))
+        );
+        assert_eq!(database_path("[user]\nname=User\n", home), None);
+    }
+}
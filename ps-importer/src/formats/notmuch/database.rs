@@ -0,0 +1,50 @@
+use ::notmuch::{Database, DatabaseMode};
+
+use ps_core::eyre::{bail, eyre, Result};
+use ps_core::tracing;
+use ps_core::{Message, MessageSender, Source};
+
+use super::super::EmailSender;
+use super::mail::Mail;
+
+pub fn read_emails(
+    source: &Source,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let root = &source.emails_folder_path;
+    if !root.exists() {
+        bail!("Path {} does not exist", &root.display());
+    }
+    let database = Database::open(root, DatabaseMode::ReadOnly).map_err(|e| {
+        eyre!(
+            "Could not open notmuch database in {}: {}",
+            root.display(),
+            e
+        )
+    })?;
+
+    // `*` matches every message in the database
+    let query = database
+        .create_query("*")
+        .map_err(|e| eyre!("Could not query notmuch database: {}", e))?;
+    let total = query
+        .count_messages()
+        .map_err(|e| eyre!("Could not count notmuch messages: {}", e))?;
+    sender.send(Message::ReadTotal(total as usize))?;
+    emails.estimate(total as usize);
+
+    let messages = query
+        .search_messages()
+        .map_err(|e| eyre!("Could not read notmuch messages: {}", e))?;
+    for message in messages {
+        let path = message.filename();
+        tracing::trace!("Found notmuch message {}", path.display());
+        let tags: Vec<String> = message.tags().collect();
+        emails.send(Mail::new(root, path, tags, message.thread_id().to_string()))?;
+        if let Err(e) = sender.send(Message::ReadOne) {
+            tracing::error!("Error sending: {}", e);
+        }
+    }
+    Ok(())
+}
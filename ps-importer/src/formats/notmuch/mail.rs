@@ -0,0 +1,121 @@
+// Without the `notmuch` feature, the mails are never constructed
+#![cfg_attr(not(feature = "notmuch"), allow(dead_code))]
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use ps_core::eyre::Result;
+use ps_core::EmailMeta;
+
+use super::super::shared::parse::ParseableEmail;
+
+/// The notmuch tag that marks unread mails. It is mapped to
+/// `EmailMeta::is_seen` instead of being a tag.
+const UNREAD_TAG: &str = "unread";
+
+/// The Maildir subfolders that are not part of the folder name
+const MAILDIR_FOLDERS: &[&str] = &["cur", "new", "tmp"];
+
+pub struct Mail {
+    /// The path of the message file in the mail root
+    path: PathBuf,
+    is_seen: bool,
+    // The notmuch tags and the folder of the mail
+    tags: Vec<String>,
+    // The notmuch thread id
+    thread_id: String,
+    // Maildata
+    data: Vec<u8>,
+}
+
+impl Mail {
+    /// `root` is the mail root of the notmuch database and `path` the message file in there
+    pub fn new(root: &Path, path: PathBuf, tags: Vec<String>, thread_id: String) -> Self {
+        let is_seen = !tags.iter().any(|e| e == UNREAD_TAG);
+        let mut tags: Vec<String> = tags.into_iter().filter(|e| e != UNREAD_TAG).collect();
+        if let Some(folder) = folder(root, &path).filter(|e| !tags.contains(e)) {
+            tags.push(folder);
+        }
+        Self {
+            path,
+            is_seen,
+            tags,
+            thread_id,
+            data: Vec::new(),
+        }
+    }
+}
+
+/// The folder of the message file in the mail root, as in notmuch's `folder:` search.
+/// `Archive/2021/cur/1614556800.M1P1.host:2,S` is in `Archive/2021`, mails
+/// directly in the root (or in its `cur` and `new`) are in no folder.
+fn folder(root: &Path, path: &Path) -> Option<String> {
+    let folder = path.strip_prefix(root).ok()?.parent()?;
+    let folder = match folder.file_name().and_then(|e| e.to_str()) {
+        Some(name) if MAILDIR_FOLDERS.contains(&name) => folder.parent()?,
+        _ => folder,
+    };
+    let components: Vec<&str> = folder.iter().filter_map(|e| e.to_str()).collect();
+    match components.is_empty() {
+        true => None,
+        false => Some(components.join("/")),
+    }
+}
+
+impl ParseableEmail for Mail {
+    fn prepare(&mut self) -> Result<()> {
+        self.data = std::fs::read(self.path.as_path())?;
+        Ok(())
+    }
+    fn message(&self) -> Result<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(self.data.as_slice()))
+    }
+    fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    fn meta(&self) -> Result<Option<EmailMeta>> {
+        Ok(Some(EmailMeta {
+            tags: self.tags.clone(),
+            is_seen: self.is_seen,
+            thread_id: Some(self.thread_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_and_folder() {
+        let root = Path::new("/home/user/mail");
+        let tags = vec!["inbox".to_owned(), "unread".to_owned(), "work".to_owned()];
+        let mail = Mail::new(
+            root,
+            root.join("Archive/2021/cur/1614556800.M1P1.localhost:2,S"),
+            tags,
+            "0000000000000001".to_owned(),
+        );
+        let meta = mail.meta().unwrap().unwrap();
+        assert_eq!(meta.tags, vec!["inbox", "work", "Archive/2021"]);
+        assert!(!meta.is_seen);
+        assert_eq!(meta.thread_id.as_deref(), Some("0000000000000001"));
+
+        // Mails in the root have no folder, and a folder that
+        // is also a tag is not added twice
+        let mail = Mail::new(
+            root,
+            root.join("new/1614556800.M2P1.localhost"),
+            vec!["inbox".to_owned()],
+            "0000000000000002".to_owned(),
+        );
+        let meta = mail.meta().unwrap().unwrap();
+        assert_eq!(meta.tags, vec!["inbox"]);
+        assert!(meta.is_seen);
+        assert_eq!(
+            folder(root, &root.join("inbox/cur/1614556800.M3P1.localhost")),
+            Some("inbox".to_owned())
+        );
+        assert_eq!(folder(Path::new("/elsewhere"), &root.join("a/cur/1")), None);
+    }
+}
@@ -0,0 +1,51 @@
+//! [notmuch](https://notmuchmail.org) indexes a folder of mails (usually Maildir)
+//! in a Xapian database and stores the tags there. We read the message files
+//! and the tags through `libnotmuch`, so this requires the `notmuch` feature
+//! (and the library to be installed). The folder of each mail in the mail root
+//! becomes a tag, too.
+
+mod config;
+#[cfg(feature = "notmuch")]
+mod database;
+mod mail;
+
+use std::path::PathBuf;
+
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::{MessageSender, Source};
+
+#[derive(Default)]
+pub struct Notmuch {}
+
+impl ImporterFormat for Notmuch {
+    type Item = mail::Mail;
+
+    fn default_path() -> Option<PathBuf> {
+        config::default_path()
+    }
+
+    #[cfg(not(feature = "notmuch"))]
+    fn requirements() -> Result<()> {
+        ps_core::eyre::bail!("Postsack was built without notmuch support (the `notmuch` feature)")
+    }
+
+    #[cfg(feature = "notmuch")]
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        database::read_emails(source, sender, emails)
+    }
+
+    #[cfg(not(feature = "notmuch"))]
+    fn emails(
+        &self,
+        _source: &Source,
+        _sender: MessageSender,
+        _emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        Self::requirements()
+    }
+}
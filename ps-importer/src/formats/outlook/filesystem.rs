@@ -0,0 +1,203 @@
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use ps_core::eyre::{bail, eyre, Result};
+use ps_core::tracing;
+use ps_core::{Message, MessageSender, Source};
+
+use super::super::shared::database::Cancelled;
+use super::super::EmailSender;
+use super::mail::Mail;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The `readpst` binary that converts the Outlook files
+const READPST: &str = "readpst";
+
+/// How often we check if the import was cancelled while `readpst` runs
+const READPST_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The extensions of the Outlook data files
+const EXTENSIONS: &[&str] = &["pst", "ost"];
+
+pub fn read_emails(
+    source: &Source,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let root = &source.emails_folder_path;
+    if !root.exists() {
+        bail!("Path {} does not exist", &root.display());
+    }
+
+    // Fail early with a helpful message if `readpst` is missing
+    check_readpst()?;
+
+    // The path can be a single file or a folder with multiple files
+    let files: Vec<PathBuf> = WalkDir::new(root)
+        .into_iter()
+        .take_while(|_| !emails.is_cancelled())
+        .filter_map(|e| match e {
+            Ok(n) if n.path().is_file() && is_outlook_file(n.path()) => {
+                tracing::trace!("Found Outlook file {}", n.path().display());
+                Some(n.path().to_path_buf())
+            }
+            Err(e) => {
+                tracing::info!("Could not read folder: {}", e);
+                if let Err(e) = sender.send(Message::Error(eyre!("Could not read folder: {:?}", e)))
+                {
+                    tracing::error!("Error sending error {}", e);
+                }
+                None
+            }
+            _ => None,
+        })
+        .collect();
+    sender.send(Message::ReadTotal(files.len()))?;
+
+    files.into_par_iter().try_for_each(|file| {
+        if emails.is_cancelled() {
+            return Err(Cancelled);
+        }
+        match read_outlook_file(&file, &emails) {
+            Ok(()) => (),
+            Err(e) if e.is::<Cancelled>() => return Err(Cancelled),
+            Err(e) => {
+                tracing::error!("{} {:?}", file.display(), &e);
+                if let Err(e) = sender.send(Message::Error(eyre!(
+                    "Could read mails in {}: {:?}",
+                    file.display(),
+                    e
+                ))) {
+                    tracing::error!("Error sending error {}", e);
+                }
+            }
+        }
+        if let Err(e) = sender.send(Message::ReadOne) {
+            tracing::error!("Error sending: {}", e);
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Fails if `readpst` is not installed (or not in the `PATH`)
+pub fn check_readpst() -> Result<()> {
+    if let Err(e) = Command::new(READPST).arg("-V").output() {
+        bail!(
+            "Importing Outlook files requires `{}` (libpst) to be installed: {}",
+            READPST,
+            e
+        );
+    }
+    Ok(())
+}
+
+fn is_outlook_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Convert the file into a temporary folder structure and send the mails
+/// in there one by one. They are read when they're parsed, and the folder
+/// is removed once all of them have been parsed.
+fn read_outlook_file(file: &Path, emails: &EmailSender<Mail>) -> Result<()> {
+    let output = Arc::new(TemporaryFolder::new()?);
+    convert(file, output.path(), emails)?;
+    // Converting a large file takes a while
+    emails.check()?;
+    let converted: Vec<PathBuf> = WalkDir::new(output.path())
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.into_path())
+        .collect();
+    // `readpst` writes one file per mail
+    emails.estimate(converted.len());
+    for path in converted {
+        let folder = match path.strip_prefix(output.path()).ok().and_then(Path::parent) {
+            Some(n) => n,
+            None => continue,
+        };
+        if let Some(mail) = Mail::new(file, folder, &path, &output) {
+            emails.send(mail)?;
+        }
+    }
+    Ok(())
+}
+
+/// Let `readpst` convert `file` into `output`. It is stopped
+/// if the import is cancelled in the meantime.
+fn convert(file: &Path, output: &Path, emails: &EmailSender<Mail>) -> Result<()> {
+    let mut child = Command::new(READPST)
+        // One `.eml` file per mail, in a folder structure mirroring Outlook's
+        .arg("-e")
+        // No progress output
+        .arg("-q")
+        .arg("-o")
+        .arg(output)
+        .arg(file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("Could not run {}: {}", READPST, e))?;
+    // Read the errors while it runs, so it can't block on a full pipe
+    let stderr = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        })
+    });
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if emails.is_cancelled() {
+            if let Err(e) = child.kill() {
+                tracing::error!("Could not stop {}: {}", READPST, e);
+            }
+            child.wait()?;
+            return Err(Cancelled.into());
+        }
+        std::thread::sleep(READPST_POLL_INTERVAL);
+    };
+    if !status.success() {
+        let errors = stderr.and_then(|e| e.join().ok()).unwrap_or_default();
+        bail!("{} failed: {}", READPST, errors);
+    }
+    Ok(())
+}
+
+/// The folder that `readpst` converts a file into. It is removed
+/// once it is dropped, i.e. once all the mails in it are parsed.
+pub struct TemporaryFolder(PathBuf);
+
+impl TemporaryFolder {
+    fn new() -> Result<Self> {
+        use ps_core::rand::Rng;
+        let number: u32 = ps_core::rand::thread_rng().gen();
+        let mut folder = std::env::temp_dir();
+        folder.push("postsack");
+        folder.push(format!("outlook-{}", number));
+        std::fs::create_dir_all(&folder)?;
+        Ok(TemporaryFolder(folder))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TemporaryFolder {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            tracing::error!("Could not remove {}: {}", self.0.display(), e);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ps_core::eyre::Result;
+use ps_core::EmailMeta;
+
+use super::super::shared::headers::header_value;
+use super::super::shared::parse::ParseableEmail;
+use super::filesystem::TemporaryFolder;
+
+pub struct Mail {
+    /// The path of the mail within the Outlook file,
+    /// e.g. `Outlook.pst/Inbox/12.eml`
+    path: PathBuf,
+    /// The file that `readpst` wrote, it is read in `prepare`
+    converted: PathBuf,
+    /// The converted file is removed with its folder
+    /// once the last of the mails in there is dropped
+    _folder: Arc<TemporaryFolder>,
+    // This is parsed out of the `Status` header that `readpst` writes
+    is_seen: bool,
+    // The Outlook folder names
+    tags: Vec<String>,
+    // Maildata
+    content: Vec<u8>,
+}
+
+impl Mail {
+    /// `folder` is the folder within the Outlook file and `converted` the
+    /// file that `readpst` wrote into `output`.
+    pub fn new(
+        file: &Path,
+        folder: &Path,
+        converted: &Path,
+        output: &Arc<TemporaryFolder>,
+    ) -> Option<Self> {
+        if converted.extension().map(|e| e != "eml").unwrap_or(true) {
+            return None;
+        }
+        Some(Self {
+            path: file.join(folder).join(converted.file_name()?),
+            converted: converted.to_path_buf(),
+            _folder: output.clone(),
+            is_seen: false,
+            tags: folder_tags(folder),
+            content: Vec::new(),
+        })
+    }
+}
+
+/// `readpst` writes the root folder of the Outlook file (e.g. `Outlook Data File`)
+/// as the top-level folder. We skip it and use the nested folders below as tags:
+/// `Outlook Data File/Inbox/Projects` becomes `["Inbox", "Inbox/Projects"]`.
+fn folder_tags(folder: &Path) -> Vec<String> {
+    let components: Vec<&str> = folder.iter().filter_map(|e| e.to_str()).collect();
+    let components = match components.len() {
+        0 | 1 => &components[..],
+        _ => &components[1..],
+    };
+    let mut tags = Vec::new();
+    for index in 1..=components.len() {
+        tags.push(components[..index].join("/"));
+    }
+    tags
+}
+
+impl ParseableEmail for Mail {
+    fn prepare(&mut self) -> Result<()> {
+        self.content = std::fs::read(&self.converted)?;
+        // `readpst` writes `Status: RO` for mails that have been read. This is the
+        // only place the read flag ends up in, so mails without it count as unseen.
+        self.is_seen = header_value(&self.content, "Status")
+            .map(|e| e.contains('R'))
+            .unwrap_or(false);
+        Ok(())
+    }
+    fn message(&self) -> Result<Cow<'_, [u8]>> {
+        Ok(self.content.as_slice().into())
+    }
+    fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    fn meta(&self) -> Result<Option<EmailMeta>> {
+        Ok(Some(EmailMeta {
+            tags: self.tags.clone(),
+            is_seen: self.is_seen,
+            thread_id: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_tags() {
+        let tags = folder_tags(Path::new("Outlook Data File/Inbox/Projects"));
+        assert_eq!(tags, vec!["Inbox", "Inbox/Projects"]);
+        let tags = folder_tags(Path::new("Outlook Data File"));
+        assert_eq!(tags, vec!["Outlook Data File"]);
+    }
+}
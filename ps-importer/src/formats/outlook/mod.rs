@@ -0,0 +1,42 @@
+//! Outlook stores mails in `.pst` (and, for cached Exchange accounts, `.ost`) files.
+//! We don't parse the binary format ourselves. Instead we use `readpst` from
+//! [libpst](https://www.five-ten-sg.com/libpst/) to convert each file into a folder
+//! structure with one `.eml` per mail and read those.
+
+mod filesystem;
+mod mail;
+
+use std::path::PathBuf;
+
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::{MessageSender, Source};
+
+#[derive(Default)]
+pub struct Outlook {}
+
+impl ImporterFormat for Outlook {
+    type Item = mail::Mail;
+
+    fn default_path() -> Option<PathBuf> {
+        #[cfg(target_os = "windows")]
+        return Some(PathBuf::from(
+            shellexpand::tilde("~/Documents/Outlook Files").to_string(),
+        ));
+
+        #[cfg(not(target_os = "windows"))]
+        return None;
+    }
+
+    fn requirements() -> Result<()> {
+        filesystem::check_readpst()
+    }
+
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        filesystem::read_emails(source, sender, emails)
+    }
+}
@@ -0,0 +1,226 @@
+//! Read the files in `.zip`, `.tar`, `.tar.gz` and `.tar.xz` archives (or a
+//! single `.gz` / `.xz` compressed file such as `INBOX.mbox.gz`) without
+//! unpacking them to disk first. The members are decompressed while they're
+//! read, one after another, and handed on as a stream. Large exports
+//! therefore need neither scratch space nor the memory to hold them.
+
+use flate2::read::GzDecoder;
+use xz2::read::XzDecoder;
+
+use ps_core::eyre::{bail, Result};
+use ps_core::tracing;
+use ps_core::{Message, MessageSender};
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The amount of compressed bytes that are read between two `Message::ReadOne`
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// The archive types we can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    Gz,
+    Xz,
+}
+
+impl Kind {
+    fn from_path(path: &Path) -> Option<Kind> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        // The order is important, `.tar.gz` also ends with `.gz`
+        let kind = if name.ends_with(".zip") {
+            Kind::Zip
+        } else if name.ends_with(".tar") {
+            Kind::Tar
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Kind::TarGz
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Kind::TarXz
+        } else if name.ends_with(".gz") {
+            Kind::Gz
+        } else if name.ends_with(".xz") {
+            Kind::Xz
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+}
+
+/// Is this a (compressed) archive that we can read the members of?
+/// Note that `.eml.gz` files are single mails and not archives.
+pub fn is_archive(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let is_eml = path
+        .to_str()
+        .map(|e| e.to_lowercase().ends_with(".eml.gz"))
+        .unwrap_or(false);
+    !is_eml && Kind::from_path(path).is_some()
+}
+
+/// The amount of `Message::ReadOne` that [`read_archive`] sends while it reads
+/// the archive at `archive`, one for each `PROGRESS_STEP` of compressed bytes
+pub fn progress_total(archive: &Path) -> usize {
+    let size = archive.metadata().map(|e| e.len()).unwrap_or(0);
+    ((size + PROGRESS_STEP - 1) / PROGRESS_STEP) as usize
+}
+
+/// Call `action` with the path within the archive and a reader for the
+/// (decompressed) contents of every file in the archive at `archive`.
+/// For a single compressed file, the path is the file name without the
+/// compression extension (e.g. `INBOX.mbox` for `INBOX.mbox.gz`).
+/// The progress is sent to `sender` as described in [`progress_total`].
+/// Stops at the first error that `action` returns.
+pub fn read_archive<F>(archive: &Path, sender: &MessageSender, mut action: F) -> Result<()>
+where
+    F: FnMut(PathBuf, &mut dyn Read) -> Result<()>,
+{
+    let kind = match Kind::from_path(archive) {
+        Some(n) => n,
+        None => bail!("{} is not a supported archive", archive.display()),
+    };
+    tracing::trace!("Reading {:?} archive {}", kind, archive.display());
+    let file = Progress::new(File::open(archive)?, sender.clone());
+    match kind {
+        Kind::Zip => read_zip(file, action),
+        Kind::Tar => read_tar(file, action),
+        Kind::TarGz => read_tar(GzDecoder::new(file), action),
+        Kind::TarXz => read_tar(XzDecoder::new(file), action),
+        Kind::Gz => action(name(archive), &mut GzDecoder::new(file)),
+        Kind::Xz => action(name(archive), &mut XzDecoder::new(file)),
+    }
+}
+
+/// The path of the single file in a compressed file
+fn name(archive: &Path) -> PathBuf {
+    archive.file_stem().map(PathBuf::from).unwrap_or_default()
+}
+
+/// Counts the bytes read from the archive file and
+/// sends a `Message::ReadOne` for every `PROGRESS_STEP`
+struct Progress<R> {
+    inner: R,
+    sender: MessageSender,
+    read: u64,
+    steps: u64,
+}
+
+impl<R> Progress<R> {
+    fn new(inner: R, sender: MessageSender) -> Self {
+        Progress {
+            inner,
+            sender,
+            read: 0,
+            steps: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.read += count as u64;
+        while self.steps < self.read / PROGRESS_STEP {
+            self.steps += 1;
+            if let Err(e) = self.sender.send(Message::ReadOne) {
+                tracing::error!("Channel Failure: {:?}", &e);
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Zip archives are read by seeking to their members. Only
+/// the bytes that are actually read count as progress.
+impl<R: Seek> Seek for Progress<R> {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+/// The paths of (at most `limit`) files in the archive at `archive`, without
+/// reading their contents. Used to guess the format of an archive.
+pub fn archive_members(archive: &Path, limit: usize) -> Result<Vec<PathBuf>> {
+    let kind = match Kind::from_path(archive) {
+        Some(n) => n,
+        None => bail!("{} is not a supported archive", archive.display()),
+    };
+    let file = File::open(archive)?;
+    match kind {
+        Kind::Zip => {
+            let archive = zip::ZipArchive::new(file)?;
+            Ok(archive
+                .file_names()
+                .filter(|e| !e.ends_with('/'))
+                .take(limit)
+                .map(PathBuf::from)
+                .collect())
+        }
+        Kind::Tar => tar_members(file, limit),
+        Kind::TarGz => tar_members(GzDecoder::new(file), limit),
+        Kind::TarXz => tar_members(XzDecoder::new(file), limit),
+        Kind::Gz | Kind::Xz => Ok(archive.file_stem().map(PathBuf::from).into_iter().collect()),
+    }
+}
+
+fn tar_members<R: Read>(reader: R, limit: usize) -> Result<Vec<PathBuf>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for member in archive.entries()? {
+        let member = member?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        members.push(member.path()?.to_path_buf());
+        if members.len() >= limit {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+fn read_zip<R: Read + Seek, F>(file: R, mut action: F) -> Result<()>
+where
+    F: FnMut(PathBuf, &mut dyn Read) -> Result<()>,
+{
+    let mut archive = zip::ZipArchive::new(file)?;
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index)?;
+        if member.is_dir() {
+            continue;
+        }
+        // Ignore members with invalid paths such as `../../etc`
+        let path = match member.enclosed_name() {
+            Some(n) => n.to_path_buf(),
+            None => {
+                tracing::error!("Invalid path in archive: {}", member.name());
+                continue;
+            }
+        };
+        action(path, &mut member)?;
+    }
+    Ok(())
+}
+
+fn read_tar<R: Read, F>(reader: R, mut action: F) -> Result<()>
+where
+    F: FnMut(PathBuf, &mut dyn Read) -> Result<()>,
+{
+    let mut archive = tar::Archive::new(reader);
+    for member in archive.entries()? {
+        let mut member = member?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        let path = member.path()?.to_path_buf();
+        action(path, &mut member)?;
+    }
+    Ok(())
+}
@@ -1,56 +1,180 @@
 use super::parse::{parse_email, ParseableEmail};
-use ps_core::{Config, DBMessage, DatabaseLike, Message, MessageSender};
+use ps_core::{
+    CancelToken, Config, DBMessage, DatabaseLike, DuplicatePolicy, Message, MessageSender,
+};
 
+use ps_core::crossbeam_channel::{Receiver, RecvTimeoutError};
 use ps_core::eyre::{self, bail, Result};
 use ps_core::tracing;
 use rayon::prelude::*;
 
-pub fn into_database<Mail: ParseableEmail + 'static, Database: DatabaseLike + 'static>(
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long we wait for the next email before checking if we were cancelled
+const CANCEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Returned from the parsing pipeline to stop it early, and by the
+/// reader once it can't send emails anymore because we were cancelled
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The import was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// An email of any format, together with the name of the
+/// [`ps_core::Source`] it was read from
+pub struct SourceEmail {
+    pub source: Arc<String>,
+    pub mail: Box<dyn ParseableEmail>,
+}
+
+/// The emails from `emails` until the reader is done. If the reader is busy
+/// without sending anything, the channel is checked for a cancellation at
+/// least every [`CANCEL_INTERVAL`], which ends the emails with `Cancelled`.
+fn incoming<'a>(
+    emails: &'a Receiver<SourceEmail>,
+    cancel: &'a CancelToken,
+) -> impl Iterator<Item = Result<SourceEmail, Cancelled>> + 'a {
+    let mut cancelled = false;
+    std::iter::from_fn(move || loop {
+        if cancelled {
+            return None;
+        }
+        match emails.recv_timeout(CANCEL_INTERVAL) {
+            Ok(n) => return Some(Ok(n)),
+            // The reader also stops once it notices the cancellation
+            Err(_) if cancel.is_cancelled() => {
+                cancelled = true;
+                return Some(Err(Cancelled));
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    })
+}
+
+/// Parse the emails from `emails` as they come in and write them into the
+/// `database`. `reader` is the thread that reads the emails of all sources;
+/// its error is returned once all the emails it sent have been written.
+/// If `cancel` is cancelled, the emails that were written so far are kept
+/// and [`Message::Cancelled`] is sent instead of [`Message::Done`].
+pub fn into_database<Database: DatabaseLike + 'static>(
     config: &Config,
-    mut emails: Vec<Mail>,
+    emails: Receiver<SourceEmail>,
+    reader: JoinHandle<Result<()>>,
     tx: MessageSender,
     database: Database,
+    cancel: CancelToken,
 ) -> Result<usize> {
-    let total = emails.len();
-    tracing::info!("Loaded {} emails", &total);
-
-    // First, communicate the total amount of mails received
-    if let Err(e) = tx.send(Message::WriteTotal(total)) {
-        bail!("Channel Failure {:?}", &e);
-    }
-
     // Save the config into the database
     if let Err(e) = database.save_config(config.clone()) {
         bail!("Could not save config to database {:?}", &e);
     }
 
+    // When updating an existing database, the emails it has are skipped
+    let known = database.fingerprints()?;
+    if !known.is_empty() {
+        tracing::info!("Updating database with {} emails", known.len());
+    }
+
     // Consume the connection to begin the import. It will return the `handle` to use for
     // waiting for the database to finish importing, and the `sender` to submit work.
     let (sender, handle) = database.import();
 
-    // Iterate over the mails..
-    emails
-        // in paralell..
-        .par_iter_mut()
-        // parsing them
-        .map(|raw_mail| parse_email(raw_mail, &config.sender_emails))
-        // and inserting them into SQLite
-        .for_each(|entry| {
-            // Try to write the message into the database
-            if let Err(e) = match entry {
-                Ok(mail) => sender.send(DBMessage::Mail(Box::new(mail))),
-                Err(e) => sender.send(DBMessage::Error(e)),
-            } {
-                tracing::error!("Error Inserting into Database: {:?}", &e);
-            }
-            // Signal the write
-            if let Err(e) = tx.send(Message::WriteOne) {
-                tracing::error!("Channel Failure: {:?}", &e);
-            }
-        });
+    // The readers use the global thread pool. If parsing used it too, readers that
+    // wait for space in the `emails` channel could block the parsing that makes space.
+    let pool = rayon::ThreadPoolBuilder::new().build()?;
+
+    // The formats only estimate the total amount of mails while reading.
+    // The exact total is sent once reading is done
+    let received = AtomicUsize::new(0);
+
+    // `try_for_each` stops at the first `Err`, which we only return when cancelled
+    let cancelled = pool
+        .install(|| {
+            // Iterate over the mails as they come in..
+            incoming(&emails, &cancel)
+                // in paralell..
+                .par_bridge()
+                // parsing them
+                .map(|raw_mail| {
+                    let mut raw_mail = raw_mail?;
+                    if cancel.is_cancelled() {
+                        return Err(Cancelled);
+                    }
+                    received.fetch_add(1, Ordering::Relaxed);
+                    Ok(parse_email(
+                        &mut *raw_mail.mail,
+                        &raw_mail.source,
+                        config,
+                        &known,
+                    ))
+                })
+                // and inserting them into SQLite
+                .try_for_each(|entry| {
+                    let entry = entry?;
+                    // Try to write the message into the database
+                    if let Err(e) = match entry {
+                        Ok(Some(mail)) => match config.duplicates {
+                            DuplicatePolicy::Keep => sender.send(DBMessage::Mail(Box::new(mail))),
+                            DuplicatePolicy::Merge => {
+                                sender.send(DBMessage::MergeMail(Box::new(mail)))
+                            }
+                        },
+                        Ok(None) => Ok(()),
+                        Err(e) => sender.send(DBMessage::Error(e)),
+                    } {
+                        tracing::error!("Error Inserting into Database: {:?}", &e);
+                    }
+                    // Signal the write
+                    if let Err(e) = tx.send(Message::WriteOne) {
+                        tracing::error!("Channel Failure: {:?}", &e);
+                    }
+                    Ok(())
+                })
+        })
+        .is_err();
+
+    // Dropping the receiver makes the reader fail on the next email it
+    // sends, so it stops (instead of waiting for space) if we were cancelled
+    drop(emails);
+
+    let total = received.into_inner();
+    if cancelled {
+        tracing::info!("Import cancelled after {} emails", &total);
+    } else {
+        // Reading is done, now we know the exact total
+        tracing::info!("Loaded {} emails", &total);
+        if let Err(e) = tx.send(Message::WriteTotal(total)) {
+            bail!("Channel Failure {:?}", &e);
+        }
+    }
+    let read_result = match cancelled {
+        // The reader stops by itself once it notices the cancellation. That can take
+        // a while (e.g. during a slow IMAP command or while `readpst` converts a
+        // file), and it doesn't write anything, so we don't wait for it.
+        true => Ok(()),
+        false => match reader.join() {
+            Ok(n) => n,
+            Err(e) => Err(eyre::eyre!("Join Error: {:?}", &e)),
+        },
+    };
 
     // Tell SQLite there's no more work coming. This will exit the listening loop
-    if let Err(e) = sender.send(DBMessage::Done) {
+    let finished = if cancelled {
+        DBMessage::Cancelled
+    } else {
+        DBMessage::Done
+    };
+    if let Err(e) = sender.send(finished) {
         bail!("Channel Failure {:?}", &e);
     }
 
@@ -68,10 +192,18 @@ pub fn into_database<Mail: ParseableEmail + 'static, Database: DatabaseLike + 's
         Err(e) => Err(eyre::eyre!("Join Error: {:?}", &e)),
     };
 
+    // If reading failed, the caller reports the error instead of being done
+    read_result?;
+
     // Tell the caller that we're done processing. This will allow leaving the
     // display loop
     tracing::trace!("Messaging Done");
-    if let Err(e) = tx.send(Message::Done) {
+    let done = if cancelled {
+        Message::Cancelled
+    } else {
+        Message::Done
+    };
+    if let Err(e) = tx.send(done) {
         bail!("Channel Failure {:?}", &e);
     }
 
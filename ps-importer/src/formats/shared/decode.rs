@@ -0,0 +1,229 @@
+//! Decoding of header values into readable strings.
+//!
+//! Names and subjects are often in RFC 2047 encoded words
+//! (`=?ISO-8859-1?Q?Keld_J=F8rn?=`, `=?UTF-8?B?R3LDvMOfZQ==?=`), and older
+//! mailers also put raw bytes in a legacy charset (e.g. Shift_JIS or KOI8-R)
+//! into the headers. Without decoding, these end up garbled in the database.
+//!
+//! The base64 and quoted-printable decoding is also used for the bodies.
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+use std::borrow::Cow;
+
+use super::headers::{header_value, raw_header_value};
+
+/// Return the decoded value of the first header with the given `name`.
+/// Raw bytes that are not UTF-8 are decoded with the `charset` of the email.
+pub fn decoded_header(content: &[u8], name: &str) -> Option<String> {
+    header_text(content, name).map(|e| decode_words(&e))
+}
+
+/// Like [`decoded_header`], but the encoded words are kept. This is needed
+/// to split address lists, as the decoded names can contain commas.
+pub fn header_text(content: &[u8], name: &str) -> Option<String> {
+    let raw = raw_header_value(content, name)?;
+    Some(decode_raw(&raw, content_charset(content)).into_owned())
+}
+
+/// Raw text that isn't valid UTF-8 is decoded with the `fallback` charset
+pub fn decode_raw<'a>(raw: &'a [u8], fallback: &'static Encoding) -> Cow<'a, str> {
+    match std::str::from_utf8(raw) {
+        Ok(n) => Cow::Borrowed(n),
+        Err(_) => fallback.decode_without_bom_handling(raw).0,
+    }
+}
+
+/// The charset of the email from its `Content-Type` header. Headers with
+/// raw 8 bit bytes are usually in the same charset as the body.
+/// Without one, we go with Windows-1252, which is what most of these
+/// mailers used (and is a superset of ISO-8859-1).
+pub fn content_charset(content: &[u8]) -> &'static Encoding {
+    header_value(content, "Content-Type")
+        .and_then(|value| {
+            value
+                .split(';')
+                .filter_map(|e| e.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+                .and_then(|(_, charset)| {
+                    Encoding::for_label(charset.trim().trim_matches('"').as_bytes())
+                })
+        })
+        .unwrap_or(WINDOWS_1252)
+}
+
+/// Decode all RFC 2047 encoded words in `text`. Text that isn't
+/// a valid encoded word is kept as it is.
+pub fn decode_words(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    // Whitespace between two encoded words is not part of the text
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_word(candidate) {
+            Some((decoded, length)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    result.push_str(before);
+                }
+                result.push_str(&decoded);
+                after_word = true;
+                rest = &candidate[length..];
+            }
+            None => {
+                result.push_str(before);
+                result.push_str("=?");
+                after_word = false;
+                rest = &candidate[2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Decode the encoded word (`=?charset?encoding?text?=`) at the start
+/// of `word`. Returns the decoded text and the length of the encoded word.
+fn decode_word(word: &str) -> Option<(String, usize)> {
+    let inner = word.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let text = &inner[..inner.find("?=")?];
+    if [charset, encoding, text]
+        .iter()
+        .any(|e| e.contains(char::is_whitespace))
+    {
+        return None;
+    }
+    let length = 2 + charset.len() + 1 + encoding.len() + 1 + text.len() + 2;
+    let bytes = match encoding {
+        "B" | "b" => decode_base64(text.as_bytes())?,
+        // `_` is a space, an underscore is encoded as `=5F`
+        "Q" | "q" => decode_quoted_printable(text.replace('_', " ").as_bytes()),
+        _ => return None,
+    };
+    // RFC 2231 allows a language after the charset (`UTF-8*en`)
+    let charset = charset.split('*').next().unwrap_or(charset);
+    let encoding = Encoding::for_label(charset.as_bytes()).unwrap_or(UTF_8);
+    let decoded = encoding.decode_without_bom_handling(&bytes).0;
+    Some((decoded.into_owned(), length))
+}
+
+/// The line breaks and the padding (which some mailers leave out) are
+/// ignored. A single character at the end can't be decoded and is dropped.
+pub fn decode_base64(text: &[u8]) -> Option<Vec<u8>> {
+    let mut characters: Vec<u8> = text
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+        .collect();
+    if characters.len() % 4 == 1 {
+        characters.pop();
+    }
+    base64::decode_config(&characters, base64::STANDARD_NO_PAD).ok()
+}
+
+/// `=` followed by a line break is a soft line break, the line
+/// continues on the next one. Invalid escapes are kept as they are.
+pub fn decode_quoted_printable(text: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut index = 0;
+    while index < text.len() {
+        if text[index] != b'=' {
+            result.push(text[index]);
+            index += 1;
+            continue;
+        }
+        let rest = &text[index + 1..];
+        if rest.starts_with(b"\r\n") {
+            index += 3;
+            continue;
+        }
+        if rest.starts_with(b"\n") {
+            index += 2;
+            continue;
+        }
+        let hex = rest
+            .get(..2)
+            .and_then(|e| std::str::from_utf8(e).ok())
+            .and_then(|e| u8::from_str_radix(e, 16).ok());
+        match hex {
+            Some(n) => {
+                result.push(n);
+                index += 3;
+            }
+            None => {
+                result.push(b'=');
+                index += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_words() {
+        // The examples from RFC 2047
+        assert_eq!(
+            decode_words("=?ISO-8859-1?Q?Keld_J=F8rn_Simonsen?="),
+            "Keld Jørn Simonsen"
+        );
+        assert_eq!(decode_words("=?ISO-8859-1?Q?a?= b"), "a b");
+        assert_eq!(decode_words("=?ISO-8859-1?Q?a?=  =?ISO-8859-1?Q?b?="), "ab");
+        assert_eq!(
+            decode_words("=?ISO-8859-1?B?SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=?= =?ISO-8859-2?B?dSB1bmRlcnN0YW5kIHRoZSBleGFtcGxlLg==?="),
+            "If you can read this you understand the example."
+        );
+        // Mixed with plain text, in lower case and without padding
+        assert_eq!(
+            decode_words("Re: =?utf-8?b?R3LDvMOfZQ?= aus Köln"),
+            "Re: Grüße aus Köln"
+        );
+        // ISO-2022-JP is what most Japanese mailers use
+        assert_eq!(
+            decode_words("=?ISO-2022-JP?B?GyRCRnxLXDhsJE4lYSE8JWsbKEI=?="),
+            "日本語のメール"
+        );
+        // Things that look like encoded words, but aren't
+        assert_eq!(decode_words("1 =? 2"), "1 =? 2");
+        assert_eq!(decode_words("=?utf-8?x?abc?="), "=?utf-8?x?abc?=");
+        assert_eq!(decode_words("=?utf-8?q?a b?="), "=?utf-8?q?a b?=");
+    }
+
+    #[test]
+    fn test_decode_body_encodings() {
+        assert_eq!(
+            decode_base64(b"SGVsbG8g\r\nV29ybGQ=\r\n"),
+            Some(b"Hello World".to_vec())
+        );
+        assert_eq!(
+            decode_quoted_printable(b"Gr=C3=BC=\r\n=C3=9Fe =3D=\n a=ZZ"),
+            "Grüße = a=ZZ".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_decode_raw_headers() {
+        // Shift_JIS and KOI8-R bytes, as sent by old Japanese and Russian mailers
+        let content = b"Subject: \x93\xfa\x96\x7b\x8c\xea\r\nContent-Type: text/plain; charset=\"Shift_JIS\"\r\n\r\n";
+        assert_eq!(
+            decoded_header(content, "Subject"),
+            Some("日本語".to_owned())
+        );
+        let content = b"Subject: \xf0\xd2\xc9\xd7\xc5\xd4\r\nContent-Type: text/plain; charset=koi8-r\r\n\r\n";
+        assert_eq!(
+            decoded_header(content, "Subject"),
+            Some("Привет".to_owned())
+        );
+        // Without a charset, we assume Windows-1252
+        let content = b"Subject: Caf\xe9\r\n\r\n";
+        assert_eq!(decoded_header(content, "Subject"), Some("Café".to_owned()));
+        // Folded encoded words
+        let content = b"Subject: =?UTF-8?Q?Gr=C3=BC?=\r\n =?UTF-8?Q?=C3=9Fe?=\r\n\r\n";
+        assert_eq!(decoded_header(content, "Subject"), Some("Grüße".to_owned()));
+    }
+}
@@ -4,19 +4,22 @@ use rayon::prelude::*;
 
 use std::path::{Path, PathBuf};
 
+use super::super::EmailSender;
+use super::parse::ParseableEmail;
 use ps_core::{Message, MessageSender};
 
 /// Call `FolderAction` on all files in all sub folders in
-/// folder `folder`.
+/// folder `folder` and send the results into `emails`.
 pub fn folders_in<FolderAction, ActionResult, P>(
     folder: P,
     sender: MessageSender,
+    emails: EmailSender<ActionResult>,
     action: FolderAction,
-) -> Result<Vec<ActionResult>>
+) -> Result<()>
 where
     P: AsRef<Path>,
     FolderAction: Fn(PathBuf, MessageSender) -> Result<Vec<ActionResult>> + Send + Sync,
-    ActionResult: Send,
+    ActionResult: ParseableEmail + 'static,
 {
     let folder = folder.as_ref();
     if !folder.exists() {
@@ -27,10 +30,13 @@ where
     let items: Vec<_> = std::fs::read_dir(&folder)?.collect();
     let total = items.len();
     sender.send(Message::ReadTotal(total))?;
-    Ok(items
+    items
         .into_iter()
         .par_bridge()
         .filter_map(|entry| {
+            if emails.is_cancelled() {
+                return None;
+            }
             let path = entry
                 .map_err(|e| tracing::error!("{} {:?}", &folder.display(), &e))
                 .ok()?
@@ -45,7 +51,8 @@ where
                 .ok()
         })
         .flatten()
-        .collect())
+        .try_for_each(|email| emails.send(email))?;
+    Ok(())
 }
 
 pub fn emails_in<O, F, P: AsRef<Path>>(path: P, sender: MessageSender, make: F) -> Result<Vec<O>>
@@ -74,3 +81,25 @@ where
     sender.send(Message::ReadOne).unwrap();
     result
 }
+
+/// The amount of files directly in `folder` whose name ends with one of
+/// `suffixes` (or all of them if there are none), to estimate the amount of
+/// emails before they're read
+pub fn count_files<P: AsRef<Path>>(folder: P, suffixes: &[&str]) -> usize {
+    let entries = match std::fs::read_dir(folder.as_ref()) {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!("{} {:?}", folder.as_ref().display(), &e);
+            return 0;
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|e| !e.is_dir()).unwrap_or(false))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            suffixes.is_empty() || suffixes.iter().any(|e| name.ends_with(e))
+        })
+        .count()
+}
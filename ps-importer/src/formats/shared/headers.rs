@@ -0,0 +1,91 @@
+//! Minimal access to the raw header section of an email.
+//!
+//! Some formats store additional information in (non-standard) headers
+//! (e.g. `X-Mozilla-Status` or `X-Gmail-Labels`) which `email_parser`
+//! doesn't expose. These helpers find them in the raw bytes without
+//! parsing the whole email.
+
+/// Return the unfolded value of the first header with the given `name`.
+/// The name is compared case-insensitively.
+pub fn header_value(content: &[u8], name: &str) -> Option<String> {
+    header_values(content, name).into_iter().next()
+}
+
+/// Return the unfolded values of all headers with the given `name`.
+/// The name is compared case-insensitively.
+pub fn header_values(content: &[u8], name: &str) -> Vec<String> {
+    raw_header_values(content, name)
+        .iter()
+        .map(|e| String::from_utf8_lossy(e).into_owned())
+        .collect()
+}
+
+/// Like [`header_value`], but returns the bytes, as headers can be in
+/// any charset. See [`super::decode`] to turn them into a string.
+pub fn raw_header_value(content: &[u8], name: &str) -> Option<Vec<u8>> {
+    raw_header_values(content, name).into_iter().next()
+}
+
+fn raw_header_values(content: &[u8], name: &str) -> Vec<Vec<u8>> {
+    let mut values = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    for line in header_lines(content) {
+        // Folded lines start with whitespace and continue the previous header
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if let Some(value) = current.as_mut() {
+                value.push(b' ');
+                value.extend_from_slice(trim(line));
+            }
+            continue;
+        }
+        if let Some(value) = current.take() {
+            values.push(value);
+        }
+        if let Some(position) = line.iter().position(|c| *c == b':') {
+            let key = String::from_utf8_lossy(&line[..position]);
+            if key.trim().eq_ignore_ascii_case(name) {
+                current = Some(trim(&line[position + 1..]).to_vec());
+            }
+        }
+    }
+    if let Some(value) = current {
+        values.push(value);
+    }
+    values
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|c| !c.is_ascii_whitespace())
+        .map(|e| e + 1)
+        .unwrap_or(start);
+    &bytes[start..end]
+}
+
+/// The lines of the header section, without line endings.
+/// The header section ends with the first empty line.
+fn header_lines(content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    content
+        .split(|c| *c == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .take_while(|line| !line.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_values() {
+        let content =
+            b"From: a@b.c\r\nX-Test: one\r\n two\r\nx-test: three\r\n\r\nX-Test: body\r\n";
+        assert_eq!(header_value(content, "from"), Some("a@b.c".to_owned()));
+        assert_eq!(header_values(content, "X-Test"), vec!["one two", "three"]);
+        assert_eq!(header_value(content, "Subject"), None);
+    }
+}
@@ -0,0 +1,385 @@
+//! A tolerant reader for the headers of emails that `email_parser` rejects.
+//!
+//! Real archives contain plenty of mails with slightly malformed dates
+//! (`Fri, 32 Jun 2006 ...`, `2006-06-09 00:44:16`, comments, wrong weekdays)
+//! or addresses (unquoted commas, missing brackets). Instead of dropping them,
+//! we read whatever we can and record which headers were malformed.
+
+use ps_core::chrono::prelude::*;
+
+use super::decode::{decode_words, decoded_header, header_text};
+use super::headers::header_values;
+
+/// The characters that can't be in an unquoted display name or address
+const SPECIALS: &[char] = &['(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '"'];
+
+/// Date formats besides RFC 2822 that we see in the wild
+const DATE_FORMATS: &[&str] = &[
+    "%d %b %Y %H:%M:%S %z",
+    "%d %b %Y %H:%M:%S %:z",
+    "%d %b %Y %H:%M %z",
+    "%d %b %y %H:%M:%S %z",
+    "%Y-%m-%d %H:%M:%S %z",
+    "%Y-%m-%dT%H:%M:%S%z",
+];
+
+/// Date formats without a time zone, which we read as UTC
+const NAIVE_DATE_FORMATS: &[&str] = &[
+    "%d %b %Y %H:%M:%S",
+    "%d %b %Y %H:%M",
+    // `asctime`, without the weekday
+    "%b %d %H:%M:%S %Y",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+];
+
+/// The named time zones that old mailers use instead of an offset
+const ZONES: &[(&str, &str)] = &[
+    ("UT", "+0000"),
+    ("UTC", "+0000"),
+    ("GMT", "+0000"),
+    ("Z", "+0000"),
+    ("EST", "-0500"),
+    ("EDT", "-0400"),
+    ("CST", "-0600"),
+    ("CDT", "-0500"),
+    ("MST", "-0700"),
+    ("MDT", "-0600"),
+    ("PST", "-0800"),
+    ("PDT", "-0700"),
+    ("CET", "+0100"),
+    ("CEST", "+0200"),
+];
+
+/// What could be read out of an email that `email_parser` rejected
+#[derive(Debug)]
+pub struct LenientEmail {
+    /// The display name and address of the sender
+    pub sender: Option<(String, String)>,
+    /// If there is no readable `Date`, the time the email was received.
+    /// If that is missing too, the start of the Unix epoch.
+    /// The time zone of the sender is kept.
+    pub datetime: DateTime<FixedOffset>,
+    pub subject: Option<String>,
+    /// The display names and addresses in `To`
+    pub to: Vec<(String, String)>,
+    pub is_reply: bool,
+    /// The headers that were malformed or missing. If all of them
+    /// look fine, the problem was somewhere else, which is `other`.
+    pub failed_headers: Vec<String>,
+}
+
+/// Read the headers of `content` as far as possible. If there's no `From`,
+/// `Date` and `Subject` at all, this is not an email and `None` is returned.
+pub fn parse_leniently(content: &[u8]) -> Option<LenientEmail> {
+    let from = header_text(content, "From");
+    let date = header_text(content, "Date");
+    let subject = decoded_header(content, "Subject");
+    if from.is_none() && date.is_none() && subject.is_none() {
+        return None;
+    }
+    let mut failed_headers = Vec::new();
+
+    let sender = from
+        .as_deref()
+        .and_then(|e| addresses(e).into_iter().next());
+    if !from.as_deref().map(is_strict_mailbox).unwrap_or(false) {
+        failed_headers.push("from".to_owned());
+    }
+
+    let strict_date = date
+        .as_deref()
+        .and_then(|e| DateTime::parse_from_rfc2822(strip_comments(e).trim()).ok());
+    let datetime = match strict_date {
+        Some(n) => n,
+        None => {
+            failed_headers.push("date".to_owned());
+            date.as_deref()
+                .and_then(parse_date)
+                .or_else(|| received_date(content))
+                .unwrap_or_else(|| utc().timestamp(0, 0))
+        }
+    };
+
+    if failed_headers.is_empty() {
+        failed_headers.push("other".to_owned());
+    }
+
+    let to = header_text(content, "To")
+        .map(|e| addresses(&e))
+        .unwrap_or_default();
+    let is_reply = header_text(content, "In-Reply-To")
+        .map(|e| !e.trim().is_empty())
+        .unwrap_or(false);
+
+    Some(LenientEmail {
+        sender,
+        datetime,
+        subject,
+        to,
+        is_reply,
+        failed_headers,
+    })
+}
+
+/// The (display name, address) pairs in an address list
+pub fn addresses(value: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    // Parts without an address are usually a display name with an
+    // unquoted comma (`Doe, John <john@example.com>`)
+    let mut name: Option<String> = None;
+    for part in split_addresses(value) {
+        let part = match name.take() {
+            Some(name) => format!("{},{}", name, part),
+            None => part.to_owned(),
+        };
+        match mailbox(&part) {
+            Some(n) => result.push(n),
+            None if !part.contains('@') => name = Some(part),
+            None => (),
+        }
+    }
+    result
+}
+
+/// Split at the commas that are not in a quoted name or in brackets
+fn split_addresses(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut bracketed) = (false, false);
+    let mut start = 0;
+    for (index, character) in value.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '<' if !quoted => bracketed = true,
+            '>' if !quoted => bracketed = false,
+            ',' if !quoted && !bracketed => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Read `Name <local@domain>`, `local@domain (Name)` or just something
+/// that contains an `@`
+fn mailbox(part: &str) -> Option<(String, String)> {
+    let (name, address) = match part.split_once('<') {
+        Some((name, rest)) => (name, rest.split('>').next().unwrap_or(rest)),
+        None => {
+            let address = part.split_whitespace().find(|e| e.contains('@'))?;
+            let name = part
+                .split_once('(')
+                .and_then(|(_, e)| e.split(')').next())
+                .unwrap_or("");
+            (name, address)
+        }
+    };
+    let address = address.trim_matches(|c: char| c.is_whitespace() || "<>\"'(),;".contains(c));
+    let (local, domain) = address.split_once('@')?;
+    if local.is_empty() || domain.is_empty() {
+        return None;
+    }
+    let name = decode_words(name.trim().trim_matches('"').trim());
+    Some((name, address.to_owned()))
+}
+
+/// Does `value` look like a valid `From`: `Name <local@domain>`,
+/// `"Name" <local@domain>` or `local@domain`?
+fn is_strict_mailbox(value: &str) -> bool {
+    let value = value.trim();
+    match value.split_once('<') {
+        Some((name, rest)) => {
+            let name = name.trim();
+            let is_quoted = name.len() >= 2 && name.starts_with('"') && name.ends_with('"');
+            let address = match rest.strip_suffix('>') {
+                Some(n) => n,
+                None => return false,
+            };
+            (is_quoted || !name.contains(SPECIALS)) && is_strict_address(address)
+        }
+        None => is_strict_address(value),
+    }
+}
+
+fn is_strict_address(address: &str) -> bool {
+    match address.split_once('@') {
+        Some((local, domain)) => [local, domain]
+            .iter()
+            .all(|e| !e.is_empty() && !e.contains(SPECIALS) && !e.contains(char::is_whitespace)),
+        None => false,
+    }
+}
+
+/// Try the formats that are common in malformed dates
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let cleaned = clean_date(value);
+    if let Ok(n) = DateTime::parse_from_rfc2822(&cleaned) {
+        return Some(n);
+    }
+    for format in DATE_FORMATS {
+        if let Ok(n) = DateTime::parse_from_str(&cleaned, format) {
+            return Some(n);
+        }
+    }
+    for format in NAIVE_DATE_FORMATS {
+        if let Ok(n) = NaiveDateTime::parse_from_str(&cleaned, format) {
+            return Some(DateTime::from_utc(n, utc()));
+        }
+    }
+    None
+}
+
+/// UTC as a fixed offset, for dates without a time zone
+fn utc() -> FixedOffset {
+    FixedOffset::east(0)
+}
+
+/// Remove comments and the weekday (which is often wrong or localized),
+/// shorten month names (`June`) and replace named time zones with their offset
+fn clean_date(value: &str) -> String {
+    let without_comments = strip_comments(value);
+    let mut tokens: Vec<&str> = without_comments
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|e| !e.is_empty())
+        .collect();
+    // Weekday names are followed by a day or a month name (`asctime`)
+    let is_weekday = |e: &str| e.len() >= 3 && e.chars().all(char::is_alphabetic);
+    if tokens.len() > 1 && is_weekday(tokens[0]) && !is_month(tokens[0]) {
+        tokens.remove(0);
+    }
+    tokens
+        .into_iter()
+        .map(|token| {
+            if is_month(token) && token.chars().all(char::is_alphabetic) {
+                return &token[..3];
+            }
+            ZONES
+                .iter()
+                .find(|(name, _)| token.eq_ignore_ascii_case(name))
+                .map(|(_, offset)| *offset)
+                .unwrap_or(token)
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Remove the `(comments)`, such as the name of the time zone
+fn strip_comments(value: &str) -> String {
+    let mut depth = 0;
+    value
+        .chars()
+        .filter(|c| {
+            match c {
+                '(' => depth += 1,
+                ')' if depth > 0 => {
+                    depth -= 1;
+                    return false;
+                }
+                _ => (),
+            }
+            depth == 0
+        })
+        .collect()
+}
+
+fn is_month(token: &str) -> bool {
+    const MONTHS: &[&str] = &[
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let token = token.to_lowercase();
+    MONTHS.iter().any(|e| token.starts_with(e))
+}
+
+/// The date of the newest `Received` header (`...; Fri, 9 Jun 2006 00:44:16 -0700`)
+fn received_date(content: &[u8]) -> Option<DateTime<FixedOffset>> {
+    header_values(content, "Received")
+        .iter()
+        .filter_map(|e| e.rsplit_once(';'))
+        .find_map(|(_, date)| {
+            DateTime::parse_from_rfc2822(date.trim())
+                .ok()
+                .or_else(|| parse_date(date))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let expected = Utc.ymd(2006, 6, 9).and_hms(7, 44, 16);
+        for date in [
+            // A wrong weekday
+            "Mon, 9 Jun 2006 00:44:16 -0700",
+            "Fri, 9 Jun 2006 00:44:16 -0700 (PDT)",
+            "Fri,  9 Jun 2006 00:44:16 PDT",
+            "Freitag, 9 Jun 2006 00:44:16 -0700",
+            "9 June 2006 00:44:16 -07:00",
+            "2006-06-09 07:44:16",
+            "2006-06-09T00:44:16-0700",
+            "Fri Jun  9 07:44:16 2006",
+        ] {
+            assert_eq!(
+                parse_date(date).map(|e| e.with_timezone(&Utc)),
+                Some(expected),
+                "{}",
+                date
+            );
+        }
+        assert_eq!(parse_date("yesterday"), None);
+        // The time zone of the sender is kept
+        let date = parse_date("Fri, 9 Jun 2006 00:44:16 PDT").expect("Expected Date");
+        assert_eq!(date.offset().local_minus_utc(), -7 * 3600);
+        assert_eq!(date.hour(), 0);
+    }
+
+    #[test]
+    fn test_addresses() {
+        assert_eq!(
+            addresses("Doe, John <john@example.com>, \"Roe, Jane\" <jane@example.org>"),
+            vec![
+                ("Doe, John".to_owned(), "john@example.com".to_owned()),
+                ("Roe, Jane".to_owned(), "jane@example.org".to_owned()),
+            ]
+        );
+        assert_eq!(
+            addresses("john@example.com (John Doe); undisclosed-recipients:;"),
+            vec![("John Doe".to_owned(), "john@example.com".to_owned())]
+        );
+        assert!(is_strict_mailbox("\"Doe, John\" <john@example.com>"));
+        assert!(is_strict_mailbox("=?UTF-8?Q?J=C3=BCrgen?= <j@example.de>"));
+        assert!(!is_strict_mailbox("Doe, John <john@example.com>"));
+        assert!(!is_strict_mailbox("John <john at example.com>"));
+    }
+
+    #[test]
+    fn test_parse_leniently() {
+        let content = b"From: Doe, John <john@example.com>\r\nDate: 2006-06-09 07:44:16\r\nSubject: =?UTF-8?Q?Gr=C3=BC=C3=9Fe?=\r\n\r\nBody\r\n";
+        let mail = parse_leniently(content).expect("Expected Mail");
+        assert_eq!(
+            mail.sender.map(|e| e.1),
+            Some("john@example.com".to_owned())
+        );
+        assert_eq!(
+            mail.datetime.with_timezone(&Utc),
+            Utc.ymd(2006, 6, 9).and_hms(7, 44, 16)
+        );
+        assert_eq!(mail.subject, Some("Grüße".to_owned()));
+        assert_eq!(mail.failed_headers, vec!["from", "date"]);
+
+        // Without a date, we use the date it was received
+        let content = b"Received: from a by b; Fri, 9 Jun 2006 00:44:16 -0700\r\nFrom: john@example.com\r\n\r\n";
+        let mail = parse_leniently(content).expect("Expected Mail");
+        assert_eq!(
+            mail.datetime.with_timezone(&Utc),
+            Utc.ymd(2006, 6, 9).and_hms(7, 44, 16)
+        );
+        assert_eq!(mail.failed_headers, vec!["date"]);
+
+        assert!(parse_leniently(b"Just some text\r\n").is_none());
+    }
+}
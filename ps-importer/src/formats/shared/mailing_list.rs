@@ -0,0 +1,127 @@
+//! Detection of mailing lists, newsletters and other bulk mail.
+//!
+//! Mailing list software adds the `List-*` headers of RFC 2369 and RFC 2919,
+//! and most newsletters and notification mails at least have a
+//! `List-Unsubscribe` header or `Precedence: bulk`.
+
+use super::headers::header_value;
+
+/// The headers that only mails sent to a list of recipients have
+const LIST_HEADERS: &[&str] = &["List-Id", "List-Unsubscribe", "List-Post"];
+
+/// The `Precedence` values of automated mails
+const BULK_PRECEDENCES: &[&str] = &["bulk", "list", "junk"];
+
+/// The mailing list the email was sent to. This is the `List-Id`
+/// (`rust-users.lists.rust-lang.org`) or, if there is none, the
+/// address from `List-Post` or the domain from `List-Unsubscribe`.
+pub fn mailing_list(content: &[u8]) -> Option<String> {
+    header_value(content, "List-Id")
+        .and_then(|e| list_id(&e))
+        .or_else(|| header_value(content, "List-Post").and_then(|e| list_post(&e)))
+        .or_else(|| header_value(content, "List-Unsubscribe").and_then(|e| list_domain(&e)))
+}
+
+/// Was the email sent to many recipients at once?
+pub fn is_bulk(content: &[u8]) -> bool {
+    if LIST_HEADERS
+        .iter()
+        .any(|e| header_value(content, e).is_some())
+    {
+        return true;
+    }
+    header_value(content, "Precedence")
+        .map(|e| {
+            let precedence = e.trim().to_lowercase();
+            BULK_PRECEDENCES.contains(&precedence.as_str())
+        })
+        .unwrap_or(false)
+}
+
+/// The identifier in `"Description" <identifier>`. Some lists
+/// leave out the brackets, then it's the whole value.
+fn list_id(value: &str) -> Option<String> {
+    let id = match value.rsplit_once('<') {
+        Some((_, rest)) => rest.split('>').next().unwrap_or(rest),
+        None => value,
+    };
+    let id = id.trim().to_lowercase();
+    match id.is_empty() {
+        true => None,
+        false => Some(id),
+    }
+}
+
+/// The address in `<mailto:list@example.org>`. This is `NO` for
+/// announcement lists that can't be posted to.
+fn list_post(value: &str) -> Option<String> {
+    urls(value)
+        .find_map(|e| e.strip_prefix("mailto:"))
+        .map(|e| e.split('?').next().unwrap_or(e).to_lowercase())
+        .filter(|e| e.contains('@'))
+}
+
+/// The domain of the first `mailto:` or `https://` url in
+/// `<https://example.com/unsubscribe?id=1>, <mailto:leave@example.com>`
+fn list_domain(value: &str) -> Option<String> {
+    urls(value)
+        .find_map(|url| match url.strip_prefix("mailto:") {
+            Some(address) => address
+                .split('?')
+                .next()
+                .and_then(|e| e.rsplit_once('@'))
+                .map(|(_, domain)| domain),
+            None => url
+                .split_once("://")
+                .and_then(|(_, rest)| rest.split(&['/', '?', ':'][..]).next()),
+        })
+        .map(|e| e.trim().to_lowercase())
+        .filter(|e| !e.is_empty())
+}
+
+/// The urls in angle brackets
+fn urls(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|e| e.split_once('>'))
+        .map(|(url, _)| url.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mailing_list() {
+        let content = b"List-Id: \"Rust Users\" <Rust-Users.lists.rust-lang.org>\r\nList-Post: <mailto:users@rust-lang.org>\r\n\r\n";
+        assert_eq!(
+            mailing_list(content),
+            Some("rust-users.lists.rust-lang.org".to_owned())
+        );
+        assert!(is_bulk(content));
+
+        let content = b"List-Post: <mailto:users@rust-lang.org?subject=help>\r\n\r\n";
+        assert_eq!(
+            mailing_list(content),
+            Some("users@rust-lang.org".to_owned())
+        );
+
+        // Newsletters often only have a way to unsubscribe
+        let content = b"List-Unsubscribe: <https://News.example.com/unsubscribe?id=1>,\r\n <mailto:leave-1@example.com>\r\n\r\n";
+        assert_eq!(mailing_list(content), Some("news.example.com".to_owned()));
+        let content =
+            b"List-Unsubscribe: <mailto:leave-1@bounces.example.com?subject=unsubscribe>\r\n\r\n";
+        assert_eq!(
+            mailing_list(content),
+            Some("bounces.example.com".to_owned())
+        );
+
+        let content = b"Precedence: Bulk\r\n\r\n";
+        assert_eq!(mailing_list(content), None);
+        assert!(is_bulk(content));
+
+        let content = b"Precedence: first-class\r\nSubject: Hi\r\n\r\n";
+        assert!(!is_bulk(content));
+    }
+}
@@ -0,0 +1,320 @@
+//! A minimal walk over the MIME tree of an email to find its attachments.
+//! The helpers to split the parts are also used to extract the text.
+//!
+//! The parts are never decoded. The size of an attachment is computed
+//! from its encoded size instead.
+
+use ps_core::Attachment;
+
+use super::decode::decode_words;
+use super::headers::header_value;
+
+/// Nested multiparts deeper than this are ignored
+const MAX_DEPTH: usize = 20;
+
+/// The attachments in the MIME tree of `content`. A part is an attachment
+/// if it has `Content-Disposition: attachment` or a filename.
+pub fn attachments(content: &[u8]) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    walk(content, 0, &mut attachments);
+    attachments
+}
+
+fn walk(part: &[u8], depth: usize, attachments: &mut Vec<Attachment>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let body = body(part);
+    let content_type = header_value(part, "Content-Type").unwrap_or_default();
+    let mime_type = mime_type(&content_type);
+    let disposition = header_value(part, "Content-Disposition").unwrap_or_default();
+    let is_attachment = is_attachment(&disposition);
+
+    if mime_type.starts_with("multipart/") {
+        if let Some(boundary) = parameter(&content_type, "boundary") {
+            for child in parts(body, &boundary) {
+                walk(child, depth + 1, attachments);
+            }
+        }
+        return;
+    }
+    // Forwarded emails can have attachments of their own
+    if mime_type == "message/rfc822" && !is_attachment {
+        walk(body, depth + 1, attachments);
+        return;
+    }
+
+    let encoding = header_value(part, "Content-Transfer-Encoding").unwrap_or_default();
+    let size = || decoded_size(body, &encoding);
+    if let Some(attachment) = attachment(&content_type, &disposition, size) {
+        attachments.push(attachment);
+    }
+}
+
+/// The attachment of a (not multipart) part with the `Content-Type` and
+/// `Content-Disposition` header values, if it is one. `size` is only
+/// called for attachments.
+pub fn attachment(
+    content_type: &str,
+    disposition: &str,
+    size: impl FnOnce() -> usize,
+) -> Option<Attachment> {
+    let filename = parameter(disposition, "filename").or_else(|| parameter(content_type, "name"));
+    if !is_attachment(disposition) && filename.is_none() {
+        return None;
+    }
+    Some(Attachment {
+        filename: filename.map(|e| decode_words(&e)).unwrap_or_default(),
+        mime_type: mime_type(content_type),
+        size: size(),
+    })
+}
+
+/// The lowercased type in a `Content-Type` value, `text/plain` if there is none
+pub(super) fn mime_type(content_type: &str) -> String {
+    match content_type.split(';').next().map(|e| e.trim()) {
+        Some(n) if !n.is_empty() => n.to_lowercase(),
+        _ => "text/plain".to_owned(),
+    }
+}
+
+/// Does the `Content-Disposition` value mark an attachment?
+pub(super) fn is_attachment(disposition: &str) -> bool {
+    disposition
+        .trim_start()
+        .to_lowercase()
+        .starts_with("attachment")
+}
+
+/// Everything after the first empty line
+pub(super) fn body(part: &[u8]) -> &[u8] {
+    let mut offset = 0;
+    for line in part.split(|c| *c == b'\n') {
+        offset += line.len() + 1;
+        if line.strip_suffix(b"\r").unwrap_or(line).is_empty() {
+            return &part[offset.min(part.len())..];
+        }
+    }
+    &[]
+}
+
+/// The parts of a multipart `body`, between the `--boundary` lines
+pub(super) fn parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+    for line in body.split(|c| *c == b'\n') {
+        let line_start = offset;
+        offset += line.len() + 1;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !line.starts_with(delimiter.as_bytes()) {
+            continue;
+        }
+        if let Some(start) = start {
+            // The line break before the delimiter belongs to it
+            let end = line_start.saturating_sub(1).max(start);
+            let end = match end > start && body[end - 1] == b'\r' {
+                true => end - 1,
+                false => end,
+            };
+            parts.push(&body[start..end]);
+        }
+        if line[delimiter.len()..].starts_with(b"--") {
+            return parts;
+        }
+        start = Some(offset.min(body.len()));
+    }
+    // Without a closing delimiter, the last part runs until the end
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+/// The value of the `name` parameter in a header value like
+/// `attachment; filename="report.pdf"`. RFC 2231 parameters
+/// (`filename*=UTF-8''r%C3%A9sum%C3%A9.pdf`), also split into
+/// several (`filename*0=`, `filename*1=`), are supported.
+pub(super) fn parameter(value: &str, name: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended: Vec<(usize, String)> = Vec::new();
+    for (key, raw) in parameters(value) {
+        let key = key.to_lowercase();
+        if key == name {
+            plain = Some(raw);
+            continue;
+        }
+        let rest = match key.strip_prefix(name).and_then(|e| e.strip_prefix('*')) {
+            Some(n) => n,
+            None => continue,
+        };
+        let (index, is_encoded) = match rest.strip_suffix('*') {
+            Some(n) => (n, true),
+            None => (rest, rest.is_empty()),
+        };
+        let index = match index {
+            "" => 0,
+            n => match n.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => continue,
+            },
+        };
+        let text = match is_encoded {
+            true => percent_decode(&raw),
+            false => raw,
+        };
+        extended.push((index, text));
+    }
+    if extended.is_empty() {
+        return plain;
+    }
+    extended.sort_by_key(|e| e.0);
+    Some(extended.into_iter().map(|e| e.1).collect())
+}
+
+/// The `key=value` pairs after the first `;`, with quotes removed
+fn parameters(value: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut rest = match value.split_once(';') {
+        Some((_, rest)) => rest,
+        None => return result,
+    };
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(';').trim().to_owned();
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                let next = quoted[end..].trim_start_matches('"');
+                (quoted[..end].to_owned(), next)
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim().to_owned(), &after[end..])
+            }
+        };
+        result.push((key, value));
+        rest = match next.split_once(';') {
+            Some((_, n)) => n,
+            None => break,
+        };
+    }
+    result
+}
+
+/// Decode `charset'language'text` with `%XX` escapes. The charset is
+/// almost always UTF-8, so the others are read as UTF-8 too.
+fn percent_decode(value: &str) -> String {
+    let text = value.splitn(3, '\'').nth(2).unwrap_or(value);
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = match bytes[index] {
+            b'%' => bytes
+                .get(index + 1..index + 3)
+                .and_then(|e| std::str::from_utf8(e).ok())
+                .and_then(|e| u8::from_str_radix(e, 16).ok()),
+            _ => None,
+        };
+        match hex {
+            Some(n) => {
+                result.push(n);
+                index += 3;
+            }
+            None => {
+                result.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Base64 has 3 bytes in every 4 characters, other
+/// encodings are about as large as their contents
+fn decoded_size(body: &[u8], encoding: &str) -> usize {
+    if encoding.trim().eq_ignore_ascii_case("base64") {
+        let characters = body
+            .iter()
+            .filter(|c| c.is_ascii_alphanumeric() || **c == b'+' || **c == b'/')
+            .count();
+        return characters * 3 / 4;
+    }
+    body.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &[u8] = b"From: john@example.com\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+This is a multi-part message in MIME format.\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Hello\r\n\
+--inner\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+\r\n\
+<p>Hello</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+SGVsbG8g\r\n\
+V29ybGQh\r\n\
+--outer\r\n\
+Content-Type: IMAGE/PNG\r\n\
+Content-Disposition: inline; filename*=UTF-8''r%C3%A9sum%C3%A9.png\r\n\
+\r\n\
+12345\r\n\
+--outer\r\n\
+Content-Type: text/plain\r\n\
+Content-Disposition: attachment\r\n\
+\r\n\
+notes\r\n\
+--outer--\r\n";
+
+    #[test]
+    fn test_attachments() {
+        let found = attachments(MAIL);
+        let summary: Vec<(&str, &str, usize)> = found
+            .iter()
+            .map(|e| (e.filename.as_str(), e.mime_type.as_str(), e.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("report.pdf", "application/pdf", 12),
+                ("résumé.png", "image/png", 5),
+                ("", "text/plain", 5),
+            ]
+        );
+        assert!(attachments(b"Subject: Hi\r\n\r\nJust text\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_parameter() {
+        assert_eq!(
+            parameter("attachment; filename=\"a; b.pdf\"; size=3", "filename"),
+            Some("a; b.pdf".to_owned())
+        );
+        assert_eq!(
+            parameter(
+                "attachment; filename*0=\"long\"; filename*1=\"name.txt\"",
+                "filename"
+            ),
+            Some("longname.txt".to_owned())
+        );
+        assert_eq!(parameter("text/plain; charset=utf-8", "name"), None);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod archive;
 pub mod database;
+pub mod decode;
 pub mod filesystem;
+pub mod headers;
+pub mod lenient;
+pub mod mailing_list;
+pub mod mime;
 pub mod parse;
+pub mod text;
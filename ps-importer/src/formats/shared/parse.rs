@@ -8,11 +8,19 @@ use std::borrow::Cow;
 use std::collections::HashSet;
 use std::path::Path;
 
-use ps_core::{EmailEntry, EmailMeta};
+use ps_core::{
+    Attachment, Config, EmailEntry, EmailMeta, Fingerprint, Identity, Recipient, RecipientKind,
+};
+
+use super::decode::{decode_words, decoded_header, header_text};
+use super::lenient::{addresses, parse_leniently, LenientEmail};
+use super::mailing_list::{is_bulk, mailing_list};
+use super::mime::attachments;
+use super::text::body_text;
 
 /// Different `importer`s can implement this trait to provide the necessary
 /// data to parse their data into a `EmailEntry`.
-pub trait ParseableEmail: Send + Sized + Sync {
+pub trait ParseableEmail: Send + Sync {
     /// This will be called once before `message`, `path` and `meta`
     /// are called. It can be used to perform parsing operations
     fn prepare(&mut self) -> Result<()>;
@@ -23,26 +31,138 @@ pub trait ParseableEmail: Send + Sized + Sync {
     /// Optional meta information if they're available.
     /// (Depending on the `importer` capabilities and system)
     fn meta(&self) -> Result<Option<EmailMeta>>;
+    /// The size of the whole email in bytes, if `message` is only a part
+    /// of it (such as the headers of an IMAP mail)
+    fn size(&self) -> Option<usize> {
+        None
+    }
+    /// The attachments, if `message` doesn't have the parts they're in
+    fn attachments(&self) -> Option<Vec<Attachment>> {
+        None
+    }
+}
+
+/// Headers that mail clients change when an email is read, flagged or moved
+const VOLATILE_HEADERS: &[&str] = &[
+    "status",
+    "x-status",
+    "x-keywords",
+    "x-mozilla-status",
+    "x-mozilla-status2",
+    "x-mozilla-keys",
+];
+
+/// Identifies an email across imports, so that updating a database only adds
+/// the emails that are new. Emails with a `Message-ID` are identified by it.
+/// Otherwise by their path and contents, without what changes when a client
+/// marks an email as read: The info suffix (`:2,S`) and the `new` / `cur`
+/// folder of Maildir files and status headers such as `X-Mozilla-Status`.
+pub fn fingerprint(path: &Path, content: &[u8], message_id: Option<&str>) -> i64 {
+    if let Some(message_id) = message_id {
+        return Fingerprint::message_id(message_id);
+    }
+    let mut fingerprint = Fingerprint::default();
+    fingerprint.write(stable_path(path).as_bytes());
+    fingerprint.write(&[0]);
+    let mut skipping = false;
+    let mut lines = content.split_inclusive(|c| *c == b'\n');
+    for line in lines.by_ref() {
+        if line == b"\n" || line == b"\r\n" {
+            fingerprint.write(line);
+            break;
+        }
+        // Folded lines continue the header before
+        let is_continued = line.starts_with(b" ") || line.starts_with(b"\t");
+        if !is_continued {
+            skipping = is_volatile(line);
+        }
+        if !skipping {
+            fingerprint.write(line);
+        }
+    }
+    for line in lines {
+        fingerprint.write(line);
+    }
+    fingerprint.finish()
+}
+
+fn is_volatile(line: &[u8]) -> bool {
+    let name = match line.iter().position(|c| *c == b':') {
+        Some(n) => String::from_utf8_lossy(&line[..n]),
+        None => return false,
+    };
+    VOLATILE_HEADERS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(name.trim_end()))
 }
 
-pub fn parse_email<Entry: ParseableEmail>(
+/// The path without the parts that change when a Maildir file is read or flagged
+fn stable_path(path: &Path) -> String {
+    let mut name = path
+        .file_name()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if let Some(index) = name.rfind(|c: char| c == ':' || c == ';' || c == '!') {
+        if name[(index + 1)..].starts_with("2,") {
+            name.truncate(index);
+        }
+    }
+    let folder = path.parent().unwrap_or_else(|| Path::new(""));
+    let folder = match folder.file_name() {
+        Some(n) if n == "new" => folder.with_file_name("cur"),
+        _ => folder.to_path_buf(),
+    };
+    folder.join(name).to_string_lossy().into_owned()
+}
+
+/// Parse the email from the source named `source`, unless its fingerprint
+/// is in `known`, which means it was already imported. In that case,
+/// `None` is returned. The identities and the time zone come from `config`.
+pub fn parse_email<Entry: ParseableEmail + ?Sized>(
     entry: &mut Entry,
-    sender_emails: &HashSet<String>,
-) -> Result<EmailEntry> {
+    source: &str,
+    config: &Config,
+    known: &HashSet<i64>,
+) -> Result<Option<EmailEntry>> {
     if let Err(e) = entry.prepare() {
         tracing::error!("Prepare Error: {:?}", e);
         return Err(e);
     }
     let content = entry.message()?;
+    let message_id = message_id(&content);
+    let fingerprint = fingerprint(entry.path(), &content, message_id.as_deref());
+    if known.contains(&fingerprint) {
+        tracing::trace!("Skipping known {}", entry.path().display());
+        return Ok(None);
+    }
     match email_parser::email::Email::parse(&content) {
         Ok(email) => {
             let path = entry.path();
             tracing::trace!("Parsing {}", path.display());
             let (sender_name, _, sender_local_part, sender_domain) =
                 mailbox_to_string(&email.sender);
+            // Names in a legacy charset can't be read by `email_parser`,
+            // but we can decode them from the raw header
+            let sender_name = match sender_name.contains(char::REPLACEMENT_CHARACTER) {
+                true => decoded_header(&content, "From")
+                    .and_then(|e| display_name(&e))
+                    .unwrap_or(sender_name),
+                false => sender_name,
+            };
 
             let datetime = emaildatetime_to_chrono(&email.date);
-            let subject = email.subject.map(|e| e.to_string()).unwrap_or_default();
+            // `email_parser` keeps the whitespace after `Subject:`, and so do the
+            // databases of older versions. We keep it so that updating them doesn't
+            // split the same subject into two.
+            let subject = match email.subject {
+                Some(raw) => {
+                    let indent = &raw[..raw.len() - raw.trim_start().len()];
+                    let decoded =
+                        decoded_header(&content, "Subject").unwrap_or_else(|| decode_words(&raw));
+                    format!("{}{}", indent, decoded.trim_start())
+                }
+                None => decoded_header(&content, "Subject").unwrap_or_default(),
+            };
 
             let to_count = match email.to.as_ref() {
                 Some(n) => n.len(),
@@ -54,34 +174,61 @@ pub fn parse_email<Entry: ParseableEmail>(
             };
             let to_group = to.as_ref().map(|e| e.0.clone()).flatten();
             let to_first = to.as_ref().map(|e| (e.1.clone(), e.2.clone()));
+            let recipients = recipients(&content);
 
             let is_reply = email.in_reply_to.map(|v| !v.is_empty()).unwrap_or(false);
 
             let meta = entry.meta()?;
 
+            let references = references(&content);
+
             // In order to determine the sender, we have to
             // build up the address again :-(
-            let is_send = {
-                let email = format!("{}@{}", sender_local_part, sender_domain);
-                sender_emails.contains(&email)
-            };
+            let (is_send, identity) = identity(
+                &config.identities,
+                &format!("{}@{}", sender_local_part, sender_domain),
+                &recipients,
+            );
 
-            Ok(EmailEntry {
+            Ok(Some(EmailEntry {
                 path: path.to_path_buf(),
+                source: source.to_owned(),
+                fingerprint,
+                message_id,
+                references,
                 sender_domain,
                 sender_local_part,
                 sender_name,
                 datetime,
+                display_datetime: config.timezone.convert(&datetime),
                 subject,
+                size: entry.size().unwrap_or(content.len()),
                 meta,
                 is_reply,
+                mailing_list: mailing_list(&content),
+                is_bulk: is_bulk(&content),
+                attachments: entry.attachments().unwrap_or_else(|| attachments(&content)),
+                body: body_text(&content),
                 to_count,
                 to_group,
                 to_first,
+                recipients,
                 is_send,
-            })
+                identity,
+                failed_headers: None,
+            }))
         }
         Err(error) => {
+            // Read what we can, instead of dropping the email
+            if let Some(email) = parse_leniently(&content) {
+                tracing::info!(
+                    "Parsed email partially: {:?} [{}]",
+                    &error,
+                    entry.path().display()
+                );
+                return lenient_entry(email, entry, source, fingerprint, &content, config)
+                    .map(Some);
+            }
             let error = eyre!(
                 "Could not parse email (trace to see contents): {:?} [{}]",
                 &error,
@@ -98,13 +245,133 @@ pub fn parse_email<Entry: ParseableEmail>(
     }
 }
 
+/// The entry for an email that `email_parser` rejected
+fn lenient_entry<Entry: ParseableEmail + ?Sized>(
+    email: LenientEmail,
+    entry: &Entry,
+    source: &str,
+    fingerprint: i64,
+    content: &[u8],
+    config: &Config,
+) -> Result<EmailEntry> {
+    let (sender_name, sender_address) = email.sender.unwrap_or_default();
+    let (sender_local_part, sender_domain) = sender_address
+        .split_once('@')
+        .map(|(local, domain)| (local.to_owned(), domain.to_owned()))
+        .unwrap_or_default();
+    let recipients = recipients(content);
+    let (is_send, identity) = identity(&config.identities, &sender_address, &recipients);
+    Ok(EmailEntry {
+        path: entry.path().to_path_buf(),
+        source: source.to_owned(),
+        fingerprint,
+        message_id: message_id(content),
+        references: references(content),
+        sender_domain,
+        sender_local_part,
+        sender_name,
+        datetime: email.datetime,
+        display_datetime: config.timezone.convert(&email.datetime),
+        subject: email.subject.unwrap_or_default(),
+        size: entry.size().unwrap_or(content.len()),
+        meta: entry.meta()?,
+        is_reply: email.is_reply,
+        mailing_list: mailing_list(content),
+        is_bulk: is_bulk(content),
+        attachments: entry.attachments().unwrap_or_else(|| attachments(content)),
+        body: body_text(content),
+        to_count: email.to.len(),
+        to_group: None,
+        to_first: email.to.into_iter().next(),
+        recipients,
+        is_send,
+        identity,
+        failed_headers: Some(email.failed_headers),
+    })
+}
+
+/// Was the email sent by one of the `identities` and which one sent or
+/// (if the user didn't send it) received it in `To`, `Cc` or `Bcc`?
+fn identity(
+    identities: &[Identity],
+    sender: &str,
+    recipients: &[Recipient],
+) -> (bool, Option<String>) {
+    if let Some(identity) = Identity::find(identities, sender) {
+        return (true, Some(identity.name.clone()));
+    }
+    let identity = recipients
+        .iter()
+        .find_map(|e| Identity::find(identities, &e.address));
+    (false, identity.map(|e| e.name.clone()))
+}
+
+/// All the addresses in `To`, `Cc` and `Bcc`. `email_parser` only reads `To`,
+/// so they're read from the raw headers.
+fn recipients(content: &[u8]) -> Vec<Recipient> {
+    RecipientKind::all()
+        .iter()
+        .flat_map(|kind| {
+            header_text(content, kind.header())
+                .map(|e| addresses(&e))
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |(name, address)| Recipient {
+                    name,
+                    address,
+                    kind: *kind,
+                })
+        })
+        .collect()
+}
+
+/// The `Message-ID` without the angle brackets and whitespace
+fn message_id(content: &[u8]) -> Option<String> {
+    let value = super::headers::header_value(content, "Message-ID")?;
+    let id = value
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+    if id.is_empty() {
+        return None;
+    }
+    Some(id.to_owned())
+}
+
+/// The ids in `References` followed by `In-Reply-To`, so that the
+/// direct parent is last. Mailers that only set one of them are common.
+fn references(content: &[u8]) -> Vec<String> {
+    let mut ids = super::headers::header_value(content, "References")
+        .map(|e| message_ids(&e))
+        .unwrap_or_default();
+    let parent = super::headers::header_value(content, "In-Reply-To")
+        .and_then(|e| message_ids(&e).into_iter().next());
+    if let Some(parent) = parent {
+        ids.retain(|e| e != &parent);
+        ids.push(parent);
+    }
+    ids
+}
+
+/// The ids in angle brackets (`<a@example.com> <b@example.com>`)
+fn message_ids(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|e| e.split_once('>'))
+        .map(|(id, _)| id.trim().to_owned())
+        .filter(|e| !e.is_empty() && !e.contains(char::is_whitespace))
+        .collect()
+}
+
 /// Returns a conversion from address to the fields we care about:
 /// ([group name], display name, email address)
 fn address_to_name_string(address: &Address) -> Option<(Option<String>, String, String)> {
     match address {
         Address::Group((names, boxes)) => match (names.first(), boxes.first()) {
             (group_name, Some(mailbox)) => {
-                let group = group_name.map(|e| e.to_string());
+                let group = group_name.map(|e| decode_words(e));
                 let (display_name, address, _, _) = mailbox_to_string(mailbox);
                 Some((group, display_name, address))
             }
@@ -128,13 +395,23 @@ fn mailbox_to_string(mailbox: &Mailbox) -> (String, String, String, String) {
         None => "".to_owned(),
     };
     (
-        names,
+        decode_words(&names),
         emailaddress_to_string(&mailbox.address),
         mailbox.address.local_part.to_string(),
         mailbox.address.domain.to_string(),
     )
 }
 
+/// The display name in a `From` or `To` header value (`Name <local@domain>`)
+fn display_name(value: &str) -> Option<String> {
+    let (name, _) = value.split_once('<')?;
+    let name = name.trim().trim_matches('"').trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_owned())
+}
+
 fn emailaddress_to_string(address: &EmailAddress) -> String {
     format!(
         "{}@{}",
@@ -143,15 +420,76 @@ fn emailaddress_to_string(address: &EmailAddress) -> String {
     )
 }
 
-fn emaildatetime_to_chrono(dt: &email_parser::time::DateTime) -> chrono::DateTime<Utc> {
-    Utc.ymd(
-        dt.date.year as i32,
-        dt.date.month_number() as u32,
-        dt.date.day as u32,
-    )
-    .and_hms(
-        dt.time.time.hour as u32,
-        dt.time.time.minute as u32,
-        dt.time.time.second as u32,
-    )
+/// The date in the time zone of the sender
+fn emaildatetime_to_chrono(dt: &email_parser::time::DateTime) -> chrono::DateTime<FixedOffset> {
+    let zone = &dt.time.zone;
+    let seconds = (zone.hour_offset as i32 * 60 + zone.minute_offset as i32) * 60;
+    let offset = match zone.sign {
+        true => FixedOffset::east_opt(seconds),
+        false => FixedOffset::west_opt(seconds),
+    }
+    .unwrap_or_else(|| FixedOffset::east(0));
+    offset
+        .ymd(
+            dt.date.year as i32,
+            dt.date.month_number() as u32,
+            dt.date.day as u32,
+        )
+        .and_hms(
+            dt.time.time.hour as u32,
+            dt.time.time.minute as u32,
+            dt.time.time.second as u32,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let content = b"Subject: Hi\r\nStatus: O\r\n\r\nHello\r\n";
+        let seen = b"Subject: Hi\r\nStatus: RO\r\nX-Mozilla-Status: 0001\r\n\r\nHello\r\n";
+        let new = fingerprint(Path::new("mails/new/1.M1P1.host"), content, None);
+        assert_eq!(
+            new,
+            fingerprint(Path::new("mails/cur/1.M1P1.host:2,S"), seen, None)
+        );
+        assert_ne!(
+            new,
+            fingerprint(
+                Path::new("mails/new/1.M1P1.host"),
+                b"Subject: Hi\r\n\r\nBye\r\n",
+                None
+            )
+        );
+        assert_ne!(
+            new,
+            fingerprint(Path::new("other/new/1.M1P1.host"), content, None)
+        );
+        assert_eq!(
+            fingerprint(Path::new("a"), content, Some("<1@example.com>")),
+            fingerprint(Path::new("b"), seen, Some("<1@example.com>"))
+        );
+    }
+
+    #[test]
+    fn test_identity() {
+        let identities = Identity::parse_list("Work: *@work.example.com; me@example.com");
+        let content = b"From: jane@example.org\r\nTo: list@example.org\r\n\
+Cc: John <John@Work.example.com>\r\n\r\nHi\r\n";
+        let recipients = recipients(content);
+        assert_eq!(
+            identity(&identities, "jane@example.org", &recipients),
+            (false, Some("Work".to_owned()))
+        );
+        assert_eq!(
+            identity(&identities, "ME@example.com", &recipients),
+            (true, Some("me@example.com".to_owned()))
+        );
+        assert_eq!(
+            identity(&identities, "jane@example.org", &[]),
+            (false, None)
+        );
+    }
 }
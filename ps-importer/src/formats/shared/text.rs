@@ -0,0 +1,241 @@
+//! Extraction of the readable text of an email for the full-text search.
+//!
+//! The text parts are decoded from quoted-printable or base64 and from
+//! their charset, and HTML is reduced to its text. Attachments are skipped,
+//! and of the alternatives in `multipart/alternative` only one is used.
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+use super::decode::{decode_base64, decode_quoted_printable, decode_raw};
+use super::headers::header_value;
+use super::mime::{body, is_attachment, mime_type, parameter, parts};
+
+/// Nested multiparts deeper than this are ignored
+const MAX_DEPTH: usize = 20;
+
+/// Longer texts are cut off, as the index would otherwise
+/// mostly grow with long quoted replies and newsletters
+const MAX_LENGTH: usize = 100_000;
+
+/// The text of the email in `content`, with the whitespace collapsed
+pub fn body_text(content: &[u8]) -> String {
+    let mut texts = Vec::new();
+    collect(content, 0, &mut texts);
+    let mut text = String::new();
+    for word in texts.iter().flat_map(|e| e.split_whitespace()) {
+        if text.len() + word.len() + 1 > MAX_LENGTH {
+            break;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(word);
+    }
+    text
+}
+
+fn collect(part: &[u8], depth: usize, texts: &mut Vec<String>) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let content_type = header_value(part, "Content-Type").unwrap_or_default();
+    let disposition = header_value(part, "Content-Disposition").unwrap_or_default();
+    if is_attachment(&disposition) {
+        return;
+    }
+    let body = body(part);
+    let kind = mime_type(&content_type);
+    if let Some(subtype) = kind.strip_prefix("multipart/") {
+        let boundary = match parameter(&content_type, "boundary") {
+            Some(n) => n,
+            None => return,
+        };
+        let children = parts(body, &boundary);
+        // The alternatives have the same text, the plain one is the easiest to read
+        if subtype == "alternative" {
+            let plain = children
+                .iter()
+                .find(|e| {
+                    mime_type(&header_value(e, "Content-Type").unwrap_or_default()) == "text/plain"
+                })
+                .or_else(|| children.last());
+            if let Some(child) = plain {
+                collect(child, depth + 1, texts);
+            }
+            return;
+        }
+        for child in children {
+            collect(child, depth + 1, texts);
+        }
+        return;
+    }
+    match kind.as_str() {
+        "message/rfc822" => collect(body, depth + 1, texts),
+        "text/plain" => texts.push(decode(part, body, &content_type)),
+        "text/html" => texts.push(strip_html(&decode(part, body, &content_type))),
+        _ => {}
+    }
+}
+
+/// Undo the `Content-Transfer-Encoding` and the charset of a text part
+fn decode(part: &[u8], body: &[u8], content_type: &str) -> String {
+    let encoding = header_value(part, "Content-Transfer-Encoding")
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    // Broken base64 results in no text
+    let bytes = match encoding.as_str() {
+        "base64" => decode_base64(body).unwrap_or_default(),
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.to_vec(),
+    };
+    // Without a charset, it's either UTF-8 or (most likely) Windows-1252
+    match parameter(content_type, "charset").and_then(|e| Encoding::for_label(e.trim().as_bytes()))
+    {
+        Some(charset) => charset.decode_without_bom_handling(&bytes).0.into_owned(),
+        None => decode_raw(&bytes, WINDOWS_1252).into_owned(),
+    }
+}
+
+/// The text in `html`, without the tags, comments, scripts and styles
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..start]));
+        rest = &rest[start..];
+        let head: Vec<u8> = rest
+            .bytes()
+            .take(7)
+            .map(|e| e.to_ascii_lowercase())
+            .collect();
+        let end = if head.starts_with(b"<!--") {
+            rest.find("-->").map(|e| e + 3)
+        } else if head.starts_with(b"<script") {
+            find_lowercase(rest, "</script>")
+        } else if head.starts_with(b"<style") {
+            find_lowercase(rest, "</style>")
+        } else {
+            rest.find('>').map(|e| e + 1)
+        };
+        // Tags like `<br>` or `</td>` separate words
+        text.push(' ');
+        rest = match end {
+            Some(n) => &rest[n..],
+            None => "",
+        };
+    }
+    text.push_str(&decode_entities(rest));
+    text
+}
+
+/// The end of the first `needle` (in lowercase) in `text`, regardless of the case.
+/// Only the text up to the match is compared, as it is searched from the
+/// start of each script or style.
+fn find_lowercase(text: &str, needle: &str) -> Option<usize> {
+    text.as_bytes()
+        .windows(needle.len())
+        .position(|e| e.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|e| e + needle.len())
+}
+
+/// Replace the common named and all numeric character references
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest
+            .find(';')
+            .filter(|e| *e <= 10)
+            .and_then(|end| entity(&rest[1..end]).map(|e| (e, end)));
+        match decoded {
+            Some((character, end)) => {
+                result.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The character for the reference `&name;`
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(&['x', 'X'][..]) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_text() {
+        let content = b"Content-Type: multipart/mixed; boundary=outer\r\n\
+\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Viele Gr=C3=BC=C3=9Fe aus einer sehr lan=\r\n\
+gen Zeile\r\n\
+--inner\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Only in the HTML</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: text/plain; name=notes.txt\r\n\
+Content-Disposition: attachment\r\n\
+\r\n\
+Only in the attachment\r\n\
+--outer--\r\n";
+        assert_eq!(
+            body_text(content),
+            "Viele Gr\u{fc}\u{df}e aus einer sehr langen Zeile"
+        );
+
+        // `<style>p { color: red }</style><p>Caf\xe9 &amp; <b>Bar</b></p>` in Latin-1
+        let content = b"Content-Type: text/html; charset=iso-8859-1\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+PHN0eWxlPnAgeyBjb2xvcjogcmVkIH08L3N0eWxlPjxwPkNhZukgJmFtcDsgPGI+QmFyPC9iPjwv\r\n\
+cD4=\r\n";
+        assert_eq!(body_text(content), "Caf\u{e9} & Bar");
+
+        let content = b"Subject: Hi\r\n\r\nJust  some\r\n text\r\n";
+        assert_eq!(body_text(content), "Just some text");
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<SCRIPT>alert(1)</script>a&lt;b <!-- c --> &#100;&#x65; &unknown; &")
+                .trim(),
+            "a<b   de &unknown; &"
+        );
+    }
+}
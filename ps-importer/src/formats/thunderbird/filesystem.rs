@@ -0,0 +1,136 @@
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use super::super::mbox::{estimate_mails, read_mbox_file};
+use ps_core::eyre::{bail, eyre, Result};
+use ps_core::tracing;
+use ps_core::{Message, MessageSender, Source};
+
+use super::super::EmailSender;
+use super::mail::Mail;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The folders within a profile that contain the mail accounts
+const ACCOUNT_FOLDERS: &[&str] = &["ImapMail", "Mail"];
+
+/// The suffix of the directory that contains the sub folders of a folder
+const SUB_FOLDER_SUFFIX: &str = ".sbd";
+
+/// Find the default Thunderbird profile of the current user
+pub fn default_profile() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    let profiles = "~/Library/Thunderbird/Profiles";
+
+    #[cfg(target_os = "windows")]
+    let profiles = "~/AppData/Roaming/Thunderbird/Profiles";
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let profiles = "~/.thunderbird";
+
+    let profiles = PathBuf::from(shellexpand::tilde(profiles).to_string());
+    let mut candidates: Vec<PathBuf> = std::fs::read_dir(&profiles)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.contains(".default"))
+                    .unwrap_or(false)
+        })
+        .collect();
+    // Newer Thunderbird versions create a `.default` profile that stays
+    // empty and a `.default-release` profile that is actually used.
+    candidates.sort_by_key(|p| !p.to_string_lossy().ends_with(".default-release"));
+    candidates.into_iter().next()
+}
+
+pub fn read_emails(
+    source: &Source,
+    sender: MessageSender,
+    emails: EmailSender<Mail>,
+) -> Result<()> {
+    let root = &source.emails_folder_path;
+    if !root.exists() {
+        bail!("Folder {} does not exist", &root.display());
+    }
+
+    let mboxes: Vec<(PathBuf, Vec<String>)> = WalkDir::new(root)
+        .into_iter()
+        .take_while(|_| !emails.is_cancelled())
+        .filter_map(|e| match e {
+            Ok(n) if n.path().is_file() && is_mbox_file(n.path()) => {
+                tracing::trace!("Found mbox file {}", n.path().display());
+                let tags = folder_tags(root, n.path());
+                Some((n.path().to_path_buf(), tags))
+            }
+            Err(e) => {
+                tracing::info!("Could not read folder: {}", e);
+                if let Err(e) = sender.send(Message::Error(eyre!("Could not read folder: {:?}", e)))
+                {
+                    tracing::error!("Error sending error {}", e);
+                }
+                None
+            }
+            _ => None,
+        })
+        .collect();
+    sender.send(Message::ReadTotal(mboxes.len()))?;
+    emails.estimate(estimate_mails(mboxes.iter().map(|(path, _)| path)));
+
+    mboxes
+        .into_par_iter()
+        .filter_map(|(mbox_file, tags)| {
+            if emails.is_cancelled() {
+                return None;
+            }
+            let (path, tags) = (Arc::new(mbox_file.clone()), Arc::new(tags));
+            let mails = read_mbox_file(&mbox_file, |mail| Mail::new(&path, &tags, mail));
+            // We're done reading the mbox
+            if let Err(e) = sender.send(Message::ReadOne) {
+                tracing::error!("Error sending: {}", e);
+            }
+            mails
+        })
+        .flatten()
+        .try_for_each(|mail| emails.send(mail))?;
+    Ok(())
+}
+
+/// Thunderbird mailboxes have no extension (and folder names may contain dots),
+/// so we identify them by their contents.
+fn is_mbox_file(path: &Path) -> bool {
+    if path.extension().map(|e| e == "msf").unwrap_or(false) {
+        return false;
+    }
+    let mut buffer = [0u8; 5];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut buffer)) {
+        Ok(_) => &buffer == b"From ",
+        Err(_) => false,
+    }
+}
+
+/// Build the nested tags for a mailbox out of its path.
+/// `ImapMail/imap.example.com/Archives.sbd/2021` becomes
+/// `["Archives", "Archives/2021"]`.
+fn folder_tags(root: &Path, mbox_file: &Path) -> Vec<String> {
+    let relative = mbox_file.strip_prefix(root).unwrap_or(mbox_file);
+    let mut components: Vec<&str> = relative.iter().filter_map(|e| e.to_str()).collect();
+
+    // If the root is a whole profile, skip the account folder
+    // (e.g. `ImapMail/imap.example.com`).
+    if components.len() > 2 && ACCOUNT_FOLDERS.contains(&components[0]) {
+        components.drain(0..2);
+    }
+
+    let mut tags = Vec::new();
+    let mut current = Vec::new();
+    for component in components {
+        current.push(component.trim_end_matches(SUB_FOLDER_SUFFIX));
+        tags.push(current.join("/"));
+    }
+    tags
+}
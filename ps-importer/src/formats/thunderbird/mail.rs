@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ps_core::eyre::Result;
+use ps_core::EmailMeta;
+
+use super::super::mbox::MboxMail;
+use super::super::shared::headers::header_value;
+use super::super::shared::parse::ParseableEmail;
+
+/// The message has been read
+const MSG_FLAG_READ: u32 = 0x0001;
+
+/// The message has been deleted but the folder was not compacted yet
+const MSG_FLAG_EXPUNGED: u32 = 0x0008;
+
+pub struct Mail {
+    // The path of the mbox, shared by all mails in it
+    path: Arc<PathBuf>,
+    // This is parsed out of the `X-Mozilla-Status` header
+    is_seen: bool,
+    // This is parsed out of the `path`
+    tags: Arc<Vec<String>>,
+    // The mail within the (memory-mapped) mbox
+    mail: MboxMail,
+}
+
+impl Mail {
+    pub fn new(path: &Arc<PathBuf>, tags: &Arc<Vec<String>>, mail: MboxMail) -> Option<Self> {
+        let status = header_value(mail.data(), "X-Mozilla-Status")
+            .and_then(|e| u32::from_str_radix(e.trim(), 16).ok())
+            .unwrap_or(0);
+        // Deleted mails stay in the mbox until Thunderbird compacts the folder
+        if status & MSG_FLAG_EXPUNGED != 0 {
+            return None;
+        }
+        Some(Self {
+            path: path.clone(),
+            is_seen: status & MSG_FLAG_READ != 0,
+            tags: tags.clone(),
+            mail,
+        })
+    }
+}
+
+impl ParseableEmail for Mail {
+    fn prepare(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn message(&self) -> Result<Cow<'_, [u8]>> {
+        Ok(self.mail.data().into())
+    }
+    fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+    fn meta(&self) -> Result<Option<EmailMeta>> {
+        Ok(Some(EmailMeta {
+            tags: self.tags.to_vec(),
+            is_seen: self.is_seen,
+            thread_id: None,
+        }))
+    }
+}
@@ -0,0 +1,33 @@
+//! Thunderbird stores each folder as an extension-less mbox file
+//! (next to a `.msf` index file). Sub folders of a folder `Archives`
+//! live in a directory called `Archives.sbd`.
+//! A profile contains the `ImapMail` folder (one folder per IMAP account)
+//! and the `Mail` folder (`Local Folders` and POP accounts).
+
+mod filesystem;
+mod mail;
+
+use std::path::PathBuf;
+
+use super::{EmailSender, ImporterFormat, Result};
+use ps_core::{MessageSender, Source};
+
+#[derive(Default)]
+pub struct Thunderbird {}
+
+impl ImporterFormat for Thunderbird {
+    type Item = mail::Mail;
+
+    fn default_path() -> Option<PathBuf> {
+        filesystem::default_profile()
+    }
+
+    fn emails(
+        &self,
+        source: &Source,
+        sender: MessageSender,
+        emails: EmailSender<Self::Item>,
+    ) -> Result<()> {
+        filesystem::read_emails(source, sender, emails)
+    }
+}
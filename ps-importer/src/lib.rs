@@ -4,60 +4,82 @@
 //! by reading and parsing the data and writing it into a database (which is defined as a
 //! generic type but most probably the `ps-database` module).
 //!
-//! Currently, the importer requires the construction of a specific type as well as the
-//! configuration of the importer format in a configuration.
+//! The importer is constructed with a configuration that lists the sources
+//! (a folder or archive and its format, or a server) to import from.
+//! The emails of all sources are written into the same database.
 //!
 //! ``` rs
 //! https://github.com/terhechte/postsack/issues/11
 //! let path = "tests/resources/mbox";
 //! let config =
 //!     ps_core::Config::new(None, path, vec!["".to_string()], ps_core::FormatType::Mbox).expect("Config");
-//! let importer = mbox_importer(config.clone());
+//! let importer = importer(config.clone());
 //!
 //! // Next, crate a database and run the importer
 //! // let database = Database::new(&config.database_path).unwrap();
-//! // let (_receiver, handle) = importer.import(database).unwrap();
+//! // let (_receiver, handle) = importer.import(database, CancelToken::default()).unwrap();
 //! ```
 
-use ps_core::eyre::Result;
+use ps_core::eyre::{bail, eyre, Result};
+use ps_core::tracing;
 
+mod detect;
 pub(crate) mod formats;
 
-use formats::{shared, ImporterFormat};
+pub use detect::detect_format;
+use formats::shared::database::{Cancelled, SourceEmail};
+use formats::{shared, EmailSender, ImporterFormat};
 
+use std::sync::{atomic::AtomicUsize, Arc};
 use std::{path::PathBuf, thread::JoinHandle};
 
 use ps_core::{
-    crossbeam_channel::unbounded, Config, DatabaseLike, FormatType, Importerlike, Message,
-    MessageReceiver,
+    crossbeam_channel::{bounded, unbounded, Sender},
+    CancelToken, Config, DatabaseLike, FormatType, Importerlike, Message, MessageReceiver,
+    MessageSender, Source,
 };
 
-pub struct Importer<Format: ImporterFormat> {
+/// How many emails can be read ahead of parsing
+const EMAIL_BUFFER_SIZE: usize = 4096;
+
+pub struct Importer {
     config: Config,
-    format: Format,
 }
 
-impl<Format: ImporterFormat + 'static> Importer<Format> {
-    pub fn new(config: Config, format: Format) -> Self {
-        Self { config, format }
+impl Importer {
+    pub fn new(config: Config) -> Self {
+        Self { config }
     }
 }
 
-impl<Format: ImporterFormat + 'static> Importerlike for Importer<Format> {
+impl Importerlike for Importer {
     fn import<Database: DatabaseLike + 'static>(
         self,
         database: Database,
+        cancel: CancelToken,
     ) -> Result<(MessageReceiver, JoinHandle<Result<()>>)> {
-        let Importer { format, .. } = self;
         let (sender, receiver) = unbounded();
 
         let config = self.config;
         let handle: JoinHandle<Result<()>> = std::thread::spawn(move || {
             let outer_sender = sender.clone();
             let processed = move || {
-                let emails = format.emails(&config, sender.clone())?;
-                let processed =
-                    shared::database::into_database(&config, emails, sender.clone(), database)?;
+                // The emails are read in a separate thread, so that reading,
+                // parsing and writing happen at the same time
+                let (email_sender, emails) = bounded(EMAIL_BUFFER_SIZE);
+                let (sources, reader_sender) = (config.sources.clone(), sender.clone());
+                let reader_cancel = cancel.clone();
+                let reader = std::thread::spawn(move || {
+                    read_sources(&sources, reader_sender, email_sender, reader_cancel)
+                });
+                let processed = shared::database::into_database(
+                    &config,
+                    emails,
+                    reader,
+                    sender.clone(),
+                    database,
+                    cancel,
+                )?;
 
                 Ok(processed)
             };
@@ -76,16 +98,144 @@ impl<Format: ImporterFormat + 'static> Importerlike for Importer<Format> {
     }
 }
 
-pub fn gmail_importer(config: Config) -> Importer<formats::Gmail> {
-    Importer::new(config, formats::Gmail::default())
+/// Import the emails of all the sources in `config`
+pub fn importer(config: Config) -> Importer {
+    Importer::new(config)
+}
+
+/// Like [`importer`], but all the sources are read as `format`,
+/// which is what the importers for one format did before there were sources
+fn format_importer(mut config: Config, format: FormatType) -> Importer {
+    for source in config.sources.iter_mut() {
+        source.format = format;
+    }
+    Importer::new(config)
+}
+
+pub fn gmail_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::GmailVault)
+}
+
+pub fn applemail_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::AppleMail)
+}
+
+pub fn mbox_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Mbox)
+}
+
+pub fn imap_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Imap)
+}
+
+pub fn maildir_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Maildir)
+}
+
+pub fn thunderbird_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Thunderbird)
+}
+
+pub fn notmuch_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Notmuch)
 }
 
-pub fn applemail_importer(config: Config) -> Importer<formats::AppleMail> {
-    Importer::new(config, formats::AppleMail::default())
+pub fn outlook_importer(config: Config) -> Importer {
+    format_importer(config, FormatType::Outlook)
 }
 
-pub fn mbox_importer(config: Config) -> Importer<formats::Mbox> {
-    Importer::new(config, formats::Mbox::default())
+/// Read the emails of the sources one after another, until `cancel` is cancelled.
+/// A source that can't be read (e.g. a moved folder or a failed IMAP login) is
+/// reported and the next one is read. Their errors are returned at the end.
+fn read_sources(
+    sources: &[Source],
+    sender: MessageSender,
+    emails: Sender<SourceEmail>,
+    cancel: CancelToken,
+) -> Result<()> {
+    // The emails sent so far, so that each source can add its estimate to them
+    let sent = Arc::new(AtomicUsize::new(0));
+    let mut failures = Vec::new();
+    for source in sources {
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        let result = match source.format {
+            FormatType::AppleMail => {
+                read_source::<formats::AppleMail>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::GmailVault => {
+                read_source::<formats::Gmail>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Mbox => {
+                read_source::<formats::Mbox>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Maildir => {
+                read_source::<formats::Maildir>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Thunderbird => {
+                read_source::<formats::Thunderbird>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Outlook => {
+                read_source::<formats::Outlook>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Imap => {
+                read_source::<formats::Imap>(source, &sender, &emails, &cancel, &sent)
+            }
+            FormatType::Notmuch => {
+                read_source::<formats::Notmuch>(source, &sender, &emails, &cancel, &sent)
+            }
+        };
+        match result {
+            Ok(()) => (),
+            Err(e) if e.is::<Cancelled>() => return Err(e),
+            Err(e) => {
+                tracing::error!("Could not read {}: {:?}", source.name(), &e);
+                if let Err(e) = sender.send(Message::Error(eyre!(
+                    "Could not read {}: {}",
+                    source.name(),
+                    e
+                ))) {
+                    tracing::error!("Error sending error {}", e);
+                }
+                failures.push(format!("{}: {}", source.name(), e));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        bail!(
+            "Could not read {} of {} sources:\n{}",
+            failures.len(),
+            sources.len(),
+            failures.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Read the emails of `source` with `Format` and send them into `emails`
+/// together with the name of the source
+fn read_source<Format: ImporterFormat + Default + 'static>(
+    source: &Source,
+    sender: &MessageSender,
+    emails: &Sender<SourceEmail>,
+    cancel: &CancelToken,
+    sent: &Arc<AtomicUsize>,
+) -> Result<()> {
+    let emails = EmailSender::new(
+        emails.clone(),
+        sender.clone(),
+        source.name(),
+        cancel.clone(),
+        sent.clone(),
+    );
+    Format::default().emails(source, sender.clone(), emails)?;
+    // Formats skip the rest of their folders or files once cancelled,
+    // which looks like they're done
+    if cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    Ok(())
 }
 
 pub fn default_path(format: &FormatType) -> Option<PathBuf> {
@@ -93,5 +243,24 @@ pub fn default_path(format: &FormatType) -> Option<PathBuf> {
         FormatType::AppleMail => formats::AppleMail::default_path(),
         FormatType::GmailVault => formats::Gmail::default_path(),
         FormatType::Mbox => formats::Mbox::default_path(),
+        FormatType::Maildir => formats::Maildir::default_path(),
+        FormatType::Thunderbird => formats::Thunderbird::default_path(),
+        FormatType::Outlook => formats::Outlook::default_path(),
+        FormatType::Imap => formats::Imap::default_path(),
+        FormatType::Notmuch => formats::Notmuch::default_path(),
+    }
+}
+
+/// Fails with an explanation if `format` needs a program that is not installed
+pub fn check_requirements(format: &FormatType) -> Result<()> {
+    match format {
+        FormatType::AppleMail => formats::AppleMail::requirements(),
+        FormatType::GmailVault => formats::Gmail::requirements(),
+        FormatType::Mbox => formats::Mbox::requirements(),
+        FormatType::Maildir => formats::Maildir::requirements(),
+        FormatType::Thunderbird => formats::Thunderbird::requirements(),
+        FormatType::Outlook => formats::Outlook::requirements(),
+        FormatType::Imap => formats::Imap::requirements(),
+        FormatType::Notmuch => formats::Notmuch::requirements(),
     }
 }